@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use crate::extensions::extension;
+use crate::file_system::{canonical_order, FileNode};
+
+/// The kinds of archives that can be scanned into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+}
+
+impl ArchiveKind {
+    fn of(path: &Path) -> Option<Self> {
+        match extension(path).as_str() {
+            "zip" => Some(ArchiveKind::Zip),
+            "tar" => Some(ArchiveKind::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// An archive's contents as a directory at the archive's path.
+#[derive(Debug)]
+pub(crate) struct ArchiveTree {
+    /// Its files sized as they'd be extracted
+    pub(crate) root: FileNode,
+    /// How much every file takes up inside the archive, by its path in `root`
+    pub(crate) compressed_sizes: HashMap<PathBuf, u64>,
+}
+
+/// Whether `path` is named like an archive that can be scanned into.
+pub(crate) fn is_archive(path: &Path) -> bool {
+    ArchiveKind::of(path).is_some()
+}
+
+/// Reads the entries of the archive at `path` without extracting anything.
+pub(crate) fn read_archive(path: &Path) -> io::Result<ArchiveTree> {
+    let file = BufReader::new(File::open(path)?);
+    match ArchiveKind::of(path) {
+        Some(ArchiveKind::Zip) => read_zip(path, file),
+        Some(ArchiveKind::Tar) => read_tar(path, file),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a zip or tar archive",
+        )),
+    }
+}
+
+/// Reads the zip archive `reader` found at `path`.
+pub(crate) fn read_zip(path: &Path, reader: impl Read + Seek) -> io::Result<ArchiveTree> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+    let mut tree = ArchiveBuilder::default();
+    for index in 0..archive.len() {
+        // Raw, so nothing is decompressed only to be listed
+        let entry = archive.by_index_raw(index).map_err(io::Error::other)?;
+        // Names escaping the archive, like `../x`, are left out
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        if entry.is_dir() {
+            tree.directory(&name);
+        } else {
+            tree.file(&name, entry.size(), entry.compressed_size());
+        }
+    }
+    Ok(tree.finish(path))
+}
+
+/// Reads the tar archive `reader` found at `path`, whose files take up as much as extracted.
+pub(crate) fn read_tar(path: &Path, reader: impl Read) -> io::Result<ArchiveTree> {
+    let mut archive = tar::Archive::new(reader);
+    let mut tree = ArchiveBuilder::default();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let name = entry.path()?.into_owned();
+        if !name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            continue;
+        }
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            tree.directory(&name);
+        } else if kind.is_file() {
+            tree.file(&name, entry.size(), entry.size());
+        }
+    }
+    Ok(tree.finish(path))
+}
+
+/// A directory of an archive, collected from entries in any order.
+#[derive(Debug, Default)]
+struct ArchiveBuilder {
+    entries: BTreeMap<OsString, ArchiveEntry>,
+}
+
+#[derive(Debug)]
+enum ArchiveEntry {
+    Directory(ArchiveBuilder),
+    File { size: u64, compressed_size: u64 },
+}
+
+impl ArchiveBuilder {
+    /// The directory at `name`, and every one above it, made if missing.
+    fn directory(&mut self, name: &Path) -> &mut ArchiveBuilder {
+        let mut directory = self;
+        for component in name.components() {
+            let Component::Normal(component) = component else {
+                continue;
+            };
+            let entry = directory
+                .entries
+                .entry(component.to_owned())
+                .or_insert_with(|| ArchiveEntry::Directory(ArchiveBuilder::default()));
+            // A file and a directory of the same name, the directory wins
+            if let ArchiveEntry::File { .. } = entry {
+                *entry = ArchiveEntry::Directory(ArchiveBuilder::default());
+            }
+            let ArchiveEntry::Directory(child) = entry else {
+                unreachable!()
+            };
+            directory = child;
+        }
+        directory
+    }
+
+    fn file(&mut self, name: &Path, size: u64, compressed_size: u64) {
+        let Some(file_name) = name.file_name() else {
+            return;
+        };
+        let parent = self.directory(name.parent().unwrap_or(Path::new("")));
+        parent.entries.insert(
+            file_name.to_owned(),
+            ArchiveEntry::File {
+                size,
+                compressed_size,
+            },
+        );
+    }
+
+    fn finish(self, path: &Path) -> ArchiveTree {
+        let mut compressed_sizes = HashMap::new();
+        let root = self.into_node(path, &mut compressed_sizes);
+        ArchiveTree {
+            root,
+            compressed_sizes,
+        }
+    }
+
+    fn into_node(self, path: &Path, compressed_sizes: &mut HashMap<PathBuf, u64>) -> FileNode {
+        let mut children: Vec<_> = self
+            .entries
+            .into_iter()
+            .map(|(name, entry)| {
+                let path = path.join(name);
+                match entry {
+                    ArchiveEntry::Directory(directory) => {
+                        directory.into_node(&path, compressed_sizes)
+                    }
+                    ArchiveEntry::File {
+                        size,
+                        compressed_size,
+                    } => {
+                        compressed_sizes.insert(path.clone(), compressed_size);
+                        FileNode::File {
+                            path,
+                            size: Some(size),
+                        }
+                    }
+                }
+            })
+            .collect();
+        children.sort_by(canonical_order);
+        FileNode::Directory {
+            path: path.to_path_buf(),
+            children: children.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::path::{Path, PathBuf};
+
+    use druid::im::vector;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::{read_tar, read_zip};
+    use crate::file_system::FileNode;
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn zip_entries_become_a_tree() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file("docs/readme.txt", options).unwrap();
+        zip.write_all(&[b'a'; 1000]).unwrap();
+        zip.add_directory("empty/", options).unwrap();
+        zip.start_file(
+            "top.bin",
+            options.compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(&[1, 2, 3]).unwrap();
+        // Not below the archive, so left out
+        zip.start_file("../escaped", options).unwrap();
+        let zip = zip.finish().unwrap();
+
+        let tree = read_zip(Path::new("/x/a.zip"), zip).unwrap();
+        assert_eq!(
+            tree.root,
+            FileNode::Directory {
+                path: "/x/a.zip".into(),
+                children: vector![
+                    FileNode::Directory {
+                        path: "/x/a.zip/docs".into(),
+                        children: vector![file("/x/a.zip/docs/readme.txt", 1000)],
+                    },
+                    FileNode::Directory {
+                        path: "/x/a.zip/empty".into(),
+                        children: vector![],
+                    },
+                    file("/x/a.zip/top.bin", 3),
+                ],
+            }
+        );
+        let compressed = |path: &str| tree.compressed_sizes[&PathBuf::from(path)];
+        assert!(compressed("/x/a.zip/docs/readme.txt") < 1000);
+        assert_eq!(compressed("/x/a.zip/top.bin"), 3);
+    }
+
+    #[test]
+    fn tar_entries_become_a_tree() {
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_cksum();
+        tar.append_data(&mut header, "a/b/c.txt", &b"hello"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let tree = read_tar(Path::new("/t.tar"), &tar[..]).unwrap();
+        assert_eq!(
+            tree.root,
+            FileNode::Directory {
+                path: "/t.tar".into(),
+                children: vector![FileNode::Directory {
+                    path: "/t.tar/a".into(),
+                    children: vector![FileNode::Directory {
+                        path: "/t.tar/a/b".into(),
+                        children: vector![file("/t.tar/a/b/c.txt", 5)],
+                    }],
+                }],
+            }
+        );
+        assert_eq!(tree.compressed_sizes[&PathBuf::from("/t.tar/a/b/c.txt")], 5);
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use druid::widget::Controller;
+use druid::{Env, Event, EventCtx, LifeCycle, LifeCycleCtx, TimerToken, UpdateCtx, Widget};
+
+use crate::AppState;
+
+/// Which rescan timer is running, so only the latest one triggers a scan.
+///
+/// Druid timers can't be cancelled, so a timer that's no longer wanted is ignored when it fires.
+#[derive(Debug, Default)]
+pub(crate) struct RescanTimer {
+    /// Seconds between rescans and the folder they're for, `None` when disabled
+    schedule: Option<(u64, String)>,
+    active: Option<TimerToken>,
+}
+
+impl RescanTimer {
+    /// Adopts the settings, returning how long to set a new timer for if they changed and
+    /// rescanning is enabled. Any running timer is dropped when they change.
+    pub(crate) fn update(&mut self, secs: Option<u64>, folder: &str) -> Option<Duration> {
+        let schedule = secs
+            .filter(|secs| *secs > 0)
+            .map(|secs| (secs, folder.to_owned()));
+        if schedule == self.schedule {
+            return None;
+        }
+        self.schedule = schedule;
+        self.active = None;
+        self.interval()
+    }
+
+    /// Remembers the timer set for the returned interval.
+    pub(crate) fn started(&mut self, token: TimerToken) {
+        self.active = Some(token);
+    }
+
+    /// Whether `token` is the running timer, returning how long to set the next one for if so.
+    pub(crate) fn fire(&mut self, token: TimerToken) -> Option<Duration> {
+        if self.active != Some(token) {
+            return None;
+        }
+        self.active = None;
+        self.interval()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule
+            .as_ref()
+            .map(|(secs, _)| Duration::from_secs(*secs))
+    }
+}
+
+/// Rescans the folder every `AppState::auto_rescan_secs`, for folders that can't be watched.
+#[derive(Debug, Default)]
+pub(crate) struct AutoRescan {
+    timer: RescanTimer,
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for AutoRescan {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        if let Event::Timer(token) = event {
+            if let Some(interval) = self.timer.fire(*token) {
+                // Not restarting a scan that's taking longer than the interval
+                if !data.scanning {
+                    tracing::debug!("Rescanning `{}`", data.folder);
+                    crate::start_scan(ctx.get_external_handle(), data);
+                }
+                self.timer.started(ctx.request_timer(interval));
+                ctx.set_handled();
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            if let Some(interval) = self.timer.update(data.auto_rescan_secs, &data.folder) {
+                self.timer.started(ctx.request_timer(interval));
+            }
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &AppState,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let Some(interval) = self.timer.update(data.auto_rescan_secs, &data.folder) {
+            self.timer.started(ctx.request_timer(interval));
+        }
+        child.update(ctx, old_data, data, env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use druid::TimerToken;
+
+    use super::RescanTimer;
+
+    #[test]
+    fn only_latest_timer_rescans() {
+        let mut timer = RescanTimer::default();
+        assert_eq!(timer.update(None, "/data"), None);
+        assert_eq!(timer.update(Some(0), "/data"), None);
+
+        assert_eq!(timer.update(Some(5), "/data"), Some(Duration::from_secs(5)));
+        let first = TimerToken::next();
+        timer.started(first);
+        // Unchanged settings keep the running timer
+        assert_eq!(timer.update(Some(5), "/data"), None);
+        assert_eq!(timer.fire(TimerToken::next()), None);
+        assert_eq!(timer.fire(first), Some(Duration::from_secs(5)));
+        let second = TimerToken::next();
+        timer.started(second);
+        assert_eq!(timer.fire(first), None);
+
+        // A new folder drops the running timer
+        assert_eq!(
+            timer.update(Some(5), "/other"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(timer.fire(second), None);
+        let third = TimerToken::next();
+        timer.started(third);
+
+        // Disabling stops it
+        assert_eq!(timer.update(None, "/other"), None);
+        assert_eq!(timer.fire(third), None);
+    }
+}
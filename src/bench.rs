@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::time::{Duration, Instant};
+
+use crate::file_system::{traverse_reporting, ScanError, ScanOptions};
+use crate::metadata::{MetadataProvider, RealFs};
+use crate::units::format_int;
+
+/// How often `--bench-scan=` scans its folder
+pub(crate) const BENCH_ITERATIONS: usize = 5;
+
+/// How long repeated scans of the same folder took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BenchSummary {
+    pub(crate) iterations: usize,
+    /// Files found by the last scan
+    pub(crate) files: u64,
+    pub(crate) min: Duration,
+    pub(crate) median: Duration,
+    pub(crate) max: Duration,
+}
+
+impl BenchSummary {
+    /// Files scanned per second at the median duration.
+    pub(crate) fn files_per_second(&self) -> f64 {
+        self.files as f64 / self.median.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl std::fmt::Display for BenchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} scans of {} files: min {:.1?}, median {:.1?}, max {:.1?}, {} files/s",
+            self.iterations,
+            format_int(self.files, ','),
+            self.min,
+            self.median,
+            self.max,
+            format_int(self.files_per_second() as u64, ',')
+        )
+    }
+}
+
+/// Attaches to the console `--bench-scan=` was run from, so what it prints shows up there.
+///
+/// Windows doesn't give the app a console of its own, as it's a GUI program.
+#[cfg(windows)]
+pub(crate) fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    // SAFETY: it takes no pointers. Failing, say when started from Explorer or with the output
+    // redirected, leaves the output going where it went before.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Other platforms print to the console the app was run from regardless.
+#[cfg(not(windows))]
+pub(crate) fn attach_console() {}
+
+/// Scans `path` `iterations` times, at least once, without any UI.
+pub(crate) fn bench_scan(
+    path: &Path,
+    options: &ScanOptions,
+    iterations: usize,
+) -> Result<BenchSummary, ScanError> {
+    bench_scan_in(&RealFs, path, options, iterations)
+}
+
+/// Scans `path` in `fs` like [`bench_scan`].
+fn bench_scan_in(
+    fs: &impl MetadataProvider,
+    path: &Path,
+    options: &ScanOptions,
+    iterations: usize,
+) -> Result<BenchSummary, ScanError> {
+    let cancelled = AtomicBool::new(false);
+    let mut durations = Vec::with_capacity(iterations);
+    let mut files = 0;
+    for _ in 0..iterations.max(1) {
+        let files_found = AtomicU64::new(0);
+        let start = Instant::now();
+        let result = traverse_reporting(fs, path, options, &files_found, &|_| {}, &cancelled)?;
+        durations.push(start.elapsed());
+        files = result.root.descendant_file_count();
+    }
+    durations.sort_unstable();
+    Ok(BenchSummary {
+        iterations: durations.len(),
+        files,
+        min: durations[0],
+        median: durations[durations.len() / 2],
+        max: durations[durations.len() - 1],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{bench_scan_in, BenchSummary};
+    use crate::file_system::{ScanError, ScanOptions};
+    use crate::metadata::MemoryFs;
+
+    #[test]
+    fn benches_the_given_iterations() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .file("/root/a", 1)
+            .dir("/root/sub")
+            .file("/root/sub/b", 2);
+        let options = ScanOptions::default();
+        let summary = bench_scan_in(&fs, Path::new("/root"), &options, 3).unwrap();
+        assert_eq!(summary.iterations, 3);
+        assert_eq!(summary.files, 2);
+        assert!(summary.min <= summary.median && summary.median <= summary.max);
+        // Asking for none still scans once
+        assert_eq!(
+            bench_scan_in(&fs, Path::new("/root"), &options, 0)
+                .unwrap()
+                .iterations,
+            1
+        );
+        assert!(matches!(
+            bench_scan_in(&fs, Path::new("/missing"), &options, 3),
+            Err(ScanError::NotFound { .. })
+        ));
+
+        let summary = BenchSummary {
+            iterations: 5,
+            files: 12_000,
+            min: Duration::from_millis(40),
+            median: Duration::from_millis(50),
+            max: Duration::from_millis(70),
+        };
+        assert_eq!(summary.files_per_second(), 240_000.0);
+        assert_eq!(
+            summary.to_string(),
+            "5 scans of 12,000 files: min 40.0ms, median 50.0ms, max 70.0ms, 240,000 files/s"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use druid::widget::{Button, Flex, Label, ViewSwitcher};
+use druid::{Data, Widget, WidgetExt};
+
+use crate::file_system::FileNode;
+use crate::units::format_size;
+use crate::AppState;
+
+/// One directory on the way from the scanned folder to the one drilled into.
+#[derive(Debug, Clone, PartialEq, Eq, Data)]
+pub(crate) struct Crumb {
+    #[data(eq)]
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+}
+
+/// `root` and every directory below it down to `view_root`, outermost first.
+///
+/// Stops at the deepest one still in the tree, so it's just `root` if `view_root` is `None`.
+pub(crate) fn breadcrumbs(root: &FileNode, view_root: Option<&Path>) -> Vec<Crumb> {
+    let mut crumbs = vec![Crumb {
+        path: root.path().clone(),
+        size: root.size(),
+    }];
+    let Some(view_root) = view_root else {
+        return crumbs;
+    };
+    let mut node = root;
+    while node.path() != view_root {
+        let FileNode::Directory { children, .. } = node else {
+            break;
+        };
+        let Some(child) = children
+            .iter()
+            .find(|child| view_root.starts_with(child.path()))
+        else {
+            break;
+        };
+        node = child;
+        crumbs.push(Crumb {
+            path: node.path().clone(),
+            size: node.size(),
+        });
+    }
+    crumbs
+}
+
+/// The scanned folder and the directories drilled into below it, each a button jumping there.
+pub(crate) fn breadcrumb_bar() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |state: &AppState, _env| state.breadcrumbs.clone(),
+        |crumbs, _state, _env| {
+            let mut row = Flex::row();
+            for (depth, crumb) in crumbs.iter().cloned().enumerate() {
+                // The scanned folder by its whole path, the rest by their names
+                let name = match crumb.path.file_name() {
+                    Some(name) if depth > 0 => name.to_string_lossy().into_owned(),
+                    _ => crumb.path.display().to_string(),
+                };
+                if depth > 0 {
+                    row.add_child(Label::new("›"));
+                }
+                let target = (depth > 0).then_some(crumb.path);
+                row.add_child(
+                    Button::new(format!("{name} ({})", format_size(crumb.size))).on_click(
+                        move |_ctx, state: &mut AppState, _env| {
+                            state.set_view_root(target.clone());
+                            state.select_only(None);
+                        },
+                    ),
+                );
+            }
+            Box::new(row)
+        },
+    )
+    .expand_width()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use druid::im::vector;
+
+    use super::{breadcrumbs, Crumb};
+    use crate::file_system::FileNode;
+
+    #[test]
+    fn crumbs_down_to_view_root() {
+        let root = FileNode::Directory {
+            path: "/root".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/root/a".into(),
+                    children: vector![
+                        FileNode::Directory {
+                            path: "/root/a/b".into(),
+                            children: vector![FileNode::File {
+                                path: "/root/a/b/1".into(),
+                                size: Some(4),
+                            }],
+                        },
+                        FileNode::File {
+                            path: "/root/a/2".into(),
+                            size: Some(2),
+                        },
+                    ],
+                },
+                FileNode::File {
+                    path: "/root/3".into(),
+                    size: Some(1),
+                },
+            ],
+        };
+        let crumb = |path: &str, size| Crumb {
+            path: path.into(),
+            size,
+        };
+
+        assert_eq!(breadcrumbs(&root, None), [crumb("/root", 7)]);
+        assert_eq!(
+            breadcrumbs(&root, Some(Path::new("/root/a/b"))),
+            [
+                crumb("/root", 7),
+                crumb("/root/a", 6),
+                crumb("/root/a/b", 4)
+            ]
+        );
+        // A folder that's gone since ends the trail at its deepest remaining ancestor
+        assert_eq!(
+            breadcrumbs(&root, Some(Path::new("/root/a/gone"))),
+            [crumb("/root", 7), crumb("/root/a", 6)]
+        );
+    }
+}
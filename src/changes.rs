@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use druid::widget::Controller;
+use druid::{Data, Env, Event, EventCtx, TimerToken, UpdateCtx, Widget};
+
+use crate::file_system::FileNode;
+use crate::units::{format_int, format_size, plural};
+use crate::AppState;
+
+/// How long the changes of a rescan stay shown
+const NOTICE_DURATION: Duration = Duration::from_secs(8);
+
+/// What a rescan found different from the scan before it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) struct ScanChanges {
+    pub(crate) added: u64,
+    pub(crate) added_bytes: u64,
+    pub(crate) removed: u64,
+    pub(crate) removed_bytes: u64,
+    /// Change of the total size, including files that only changed size
+    pub(crate) net_bytes: i64,
+}
+
+impl ScanChanges {
+    /// The files added and removed from `old` to `new`, by path.
+    pub(crate) fn between(old: &FileNode, new: &FileNode) -> Self {
+        let sizes = |root: &FileNode| -> HashMap<PathBuf, u64> {
+            root.flatten_sorted().into_iter().collect()
+        };
+        let (old_sizes, new_sizes) = (sizes(old), sizes(new));
+        let mut changes = Self {
+            net_bytes: new.size() as i64 - old.size() as i64,
+            ..Default::default()
+        };
+        for (path, size) in &new_sizes {
+            if !old_sizes.contains_key(path) {
+                changes.added += 1;
+                changes.added_bytes += size;
+            }
+        }
+        for (path, size) in &old_sizes {
+            if !new_sizes.contains_key(path) {
+                changes.removed += 1;
+                changes.removed_bytes += size;
+            }
+        }
+        changes
+    }
+
+    /// What a rescan from `old` to `new` changed, if both have the same root and anything did.
+    pub(crate) fn of_rescan(old: Option<&FileNode>, new: Option<&FileNode>) -> Option<Self> {
+        let (old, new) = (old?, new?);
+        let changes = (old.path() == new.path()).then(|| Self::between(old, new))?;
+        (changes != Self::default()).then_some(changes)
+    }
+
+    /// The changes as one line for the status bar, counts grouped with `separator`.
+    pub(crate) fn summary(&self, separator: char) -> String {
+        let net = match self.net_bytes {
+            0 => String::from("no change"),
+            net if net > 0 => format!("+{}", format_size(net.unsigned_abs())),
+            net => format!("−{}", format_size(net.unsigned_abs())),
+        };
+        // Nothing added or removed has no size worth showing
+        let bytes = |sign, bytes| match bytes {
+            0 => String::new(),
+            bytes => format!(" ({sign}{})", format_size(bytes)),
+        };
+        format!(
+            "Since the last scan: {} {} added{}, {} removed{}, net {net}",
+            format_int(self.added, separator),
+            plural(self.added, "file", "files"),
+            bytes('+', self.added_bytes),
+            format_int(self.removed, separator),
+            bytes('−', self.removed_bytes)
+        )
+    }
+}
+
+/// Clears `AppState::scan_changes` a while after they're set, so they're only shown briefly.
+#[derive(Debug, Default)]
+pub(crate) struct ChangesNotice {
+    timer: Option<TimerToken>,
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for ChangesNotice {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        if let Event::Timer(token) = event {
+            if self.timer == Some(*token) {
+                self.timer = None;
+                data.scan_changes = None;
+                ctx.set_handled();
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &AppState,
+        data: &AppState,
+        env: &Env,
+    ) {
+        // A newer rescan's changes get shown as long again
+        if data.scan_changes.is_some() && !data.scan_changes.same(&old_data.scan_changes) {
+            self.timer = Some(ctx.request_timer(NOTICE_DURATION));
+        }
+        child.update(ctx, old_data, data, env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+
+    use super::ScanChanges;
+    use crate::file_system::FileNode;
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn changes_between_scans() {
+        let old = FileNode::Directory {
+            path: "/".into(),
+            children: vector![file("/kept", 10), file("/grown", 5), file("/gone", 7)],
+        };
+        let new = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                file("/kept", 10),
+                file("/grown", 8),
+                file("/new1", 100),
+                file("/new2", 2)
+            ],
+        };
+        let changes = ScanChanges::between(&old, &new);
+        assert_eq!(
+            changes,
+            ScanChanges {
+                added: 2,
+                added_bytes: 102,
+                removed: 1,
+                removed_bytes: 7,
+                net_bytes: 98,
+            }
+        );
+        assert_eq!(
+            changes.summary(','),
+            "Since the last scan: 2 files added (+102 B), 1 removed (−7 B), net +98 B"
+        );
+
+        let back = ScanChanges::between(&new, &old);
+        assert_eq!(back.net_bytes, -98);
+        assert!(back.summary(',').ends_with("net −98 B"));
+        let grown = ScanChanges {
+            added: 1_234,
+            net_bytes: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            grown.summary(','),
+            "Since the last scan: 1,234 files added, 0 removed, net +3 B"
+        );
+    }
+
+    #[test]
+    fn rescans_without_changes_have_none() {
+        let old = FileNode::Directory {
+            path: "/".into(),
+            children: vector![file("/kept", 10)],
+        };
+        let grown = FileNode::Directory {
+            path: "/".into(),
+            children: vector![file("/kept", 11)],
+        };
+        assert_eq!(ScanChanges::of_rescan(Some(&old), Some(&old)), None);
+        assert_eq!(
+            ScanChanges::of_rescan(Some(&old), Some(&grown)).map(|changes| changes.net_bytes),
+            Some(1)
+        );
+        // Scans of different folders aren't compared
+        assert_eq!(
+            ScanChanges::of_rescan(Some(&old), Some(&file("/other", 10))),
+            None
+        );
+        assert_eq!(ScanChanges::of_rescan(None, Some(&old)), None);
+    }
+}
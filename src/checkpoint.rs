@@ -0,0 +1,153 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64};
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_system::{traverse_checkpointed, FileNode, ScanError, ScanOptions, ScanResult};
+
+/// Subdirectories of a scanned folder that were completely scanned, so an interrupted scan
+/// can resume without walking them again.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    pub(crate) root: PathBuf,
+    pub(crate) finished: Vec<FileNode>,
+}
+
+pub(crate) fn write(file: &Path, checkpoint: &Checkpoint) -> io::Result<()> {
+    // Moved over the old checkpoint once complete, so being interrupted while writing keeps it
+    let partial = file.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_vec(checkpoint)?)?;
+    std::fs::rename(partial, file)
+}
+
+/// Reads the checkpoint in `file`, `None` if there's none.
+pub(crate) fn read(file: &Path) -> io::Result<Option<Checkpoint>> {
+    match std::fs::read(file) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Scans `path` while checkpointing to `file`, resuming from the checkpoint there if
+/// `options.resume` is set and it's of the same folder. The checkpoint is removed once done,
+/// but kept if the scan is cancelled.
+pub(crate) fn traverse_resumable(
+    path: &PathBuf,
+    options: &ScanOptions,
+    files_found: &AtomicU64,
+    file: &Path,
+    cancelled: &AtomicBool,
+) -> Result<ScanResult, ScanError> {
+    let finished = match options.resume.then(|| read(file)) {
+        Some(Ok(Some(checkpoint))) if checkpoint.root == *path => {
+            tracing::info!(
+                "Resuming scan of `{}` with {} finished directories",
+                path.display(),
+                checkpoint.finished.len()
+            );
+            checkpoint.finished
+        }
+        Some(Err(err)) => {
+            tracing::warn!("Failed reading checkpoint, scanning from scratch: {err}");
+            Vec::new()
+        }
+        _ => Vec::new(),
+    };
+
+    let save = |finished: &[FileNode]| {
+        let checkpoint = Checkpoint {
+            root: path.clone(),
+            finished: finished.to_vec(),
+        };
+        if let Err(err) = write(file, &checkpoint) {
+            tracing::warn!("Failed writing checkpoint: {err}");
+        }
+    };
+    let result = traverse_checkpointed(path, options, files_found, finished, &save, cancelled);
+    if let Err(ScanError::Cancelled) = result {
+        return result;
+    }
+
+    match std::fs::remove_file(file) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            tracing::warn!("Failed removing checkpoint: {err}");
+        }
+        _ => {}
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64};
+
+    use super::{read, traverse_resumable, write, Checkpoint};
+    use crate::file_system::{traverse, FileNode, ScanOptions};
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("checkpoint.json");
+        assert_eq!(read(&file).unwrap(), None);
+
+        let checkpoint = Checkpoint {
+            root: "/root".into(),
+            finished: vec![FileNode::Directory {
+                path: "/root/a".into(),
+                children: druid::im::vector![FileNode::File {
+                    path: "/root/a/1".into(),
+                    size: None,
+                }],
+            }],
+        };
+        write(&file, &checkpoint).unwrap();
+        assert_eq!(read(&file).unwrap(), Some(checkpoint));
+    }
+
+    #[test]
+    fn resumed_scan_matches_uninterrupted_scan() {
+        let scanned = tempfile::tempdir().unwrap();
+        for sub in ["a", "b"] {
+            std::fs::create_dir(scanned.path().join(sub)).unwrap();
+        }
+        for (file, size) in [("a/x", 1), ("b/y", 2), ("z", 3)] {
+            std::fs::write(scanned.path().join(file), vec![0u8; size]).unwrap();
+        }
+        let root = scanned.path().to_path_buf();
+        let options = ScanOptions {
+            resume: true,
+            ..Default::default()
+        };
+        let full = traverse(&root, &options, &AtomicU64::new(0), &Default::default())
+            .unwrap()
+            .root;
+
+        // As if interrupted after finishing `a`
+        let FileNode::Directory { children, .. } = &full else {
+            panic!("scanned a file");
+        };
+        let state = tempfile::tempdir().unwrap();
+        let file = state.path().join("checkpoint.json");
+        let checkpoint = Checkpoint {
+            root: root.clone(),
+            finished: vec![children[0].clone()],
+        };
+        write(&file, &checkpoint).unwrap();
+        // Only still found if `a` isn't walked again
+        std::fs::remove_file(scanned.path().join("a/x")).unwrap();
+
+        let files_found = AtomicU64::new(0);
+        let resumed = traverse_resumable(
+            &root,
+            &options,
+            &files_found,
+            &file,
+            &AtomicBool::new(false),
+        );
+        assert_eq!(resumed.unwrap().root, full);
+        assert_eq!(files_found.into_inner(), 3);
+        assert_eq!(read(&file).unwrap(), None);
+    }
+}
@@ -0,0 +1,145 @@
+/// Whether `--from-stdin` asks to show the paths piped in instead of scanning a folder.
+pub(crate) fn from_stdin_arg(mut args: impl Iterator<Item = String>) -> bool {
+    args.any(|arg| arg == "--from-stdin")
+}
+
+/// The folder to scan on launch, given as the first positional argument or as `--folder=`.
+///
+/// File managers may pass it as a `file://` URL, which is decoded to a path. `args` are the
+/// arguments without the program name. The GUI subsystem on Windows only detaches the console,
+/// the arguments still arrive.
+pub(crate) fn folder_arg(mut args: impl Iterator<Item = String>) -> Option<String> {
+    let mut positional = None;
+    while let Some(arg) = args.next() {
+        if arg == "--bench-scan" {
+            // Its folder follows, and isn't the one to open
+            args.next();
+            continue;
+        }
+        if let Some(folder) = arg.strip_prefix("--folder=") {
+            return Some(from_file_url(folder));
+        }
+        if positional.is_none() && !arg.starts_with("--") {
+            positional = Some(from_file_url(&arg));
+        }
+    }
+    positional
+}
+
+/// The folder to time scans of without opening a window, given as `--bench-scan <path>` or
+/// `--bench-scan=`.
+pub(crate) fn bench_scan_arg(mut args: impl Iterator<Item = String>) -> Option<String> {
+    let mut folder = None;
+    while let Some(arg) = args.next() {
+        if arg == "--bench-scan" {
+            folder = args.next().map(|path| from_file_url(&path));
+        } else if let Some(path) = arg.strip_prefix("--bench-scan=") {
+            folder = Some(from_file_url(path));
+        }
+    }
+    folder
+}
+
+/// The smallest subtree size in bytes the text report expands, given as `--report-min-size=`.
+pub(crate) fn report_min_size_arg(args: impl Iterator<Item = String>) -> Option<u64> {
+    args.filter_map(|arg| arg.strip_prefix("--report-min-size=")?.parse().ok())
+        .last()
+}
+
+/// The path of a `file://` URL, anything else as it is.
+fn from_file_url(arg: &str) -> String {
+    let Some(path) = arg.strip_prefix("file://") else {
+        return arg.to_owned();
+    };
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bench_scan_arg, folder_arg, from_stdin_arg, report_min_size_arg};
+
+    fn folder(args: &[&str]) -> Option<String> {
+        folder_arg(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn folder_from_either_form() {
+        assert_eq!(folder(&[]), None);
+        assert_eq!(folder(&["/home/user"]).as_deref(), Some("/home/user"));
+        assert_eq!(
+            folder(&["--folder=/home/user"]).as_deref(),
+            Some("/home/user")
+        );
+        // The option wins over a positional argument
+        assert_eq!(
+            folder(&["/tmp", "--folder=/home/user"]).as_deref(),
+            Some("/home/user")
+        );
+        assert_eq!(folder(&["--verbose"]), None);
+
+        assert_eq!(
+            folder(&["--folder=file:///home/user/My%20Files"]).as_deref(),
+            Some("/home/user/My Files")
+        );
+        assert_eq!(
+            folder(&["file:///home/user/100%"]).as_deref(),
+            Some("/home/user/100%")
+        );
+    }
+
+    #[test]
+    fn report_min_size_in_bytes() {
+        let min_size = |args: &[&str]| report_min_size_arg(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(min_size(&["/home/user"]), None);
+        assert_eq!(min_size(&["--report-min-size=4096", "/home"]), Some(4096));
+        assert_eq!(min_size(&["--report-min-size=lots"]), None);
+        // The folder isn't mistaken for the option's value
+        assert_eq!(folder(&["--report-min-size=4096"]), None);
+    }
+
+    #[test]
+    fn from_stdin_flag() {
+        let from_stdin = |args: &[&str]| from_stdin_arg(args.iter().map(|arg| arg.to_string()));
+        assert!(!from_stdin(&["/home/user"]));
+        assert!(from_stdin(&["--from-stdin"]));
+        assert_eq!(folder(&["--from-stdin"]), None);
+    }
+
+    #[test]
+    fn bench_scan_folder() {
+        let bench = |args: &[&str]| bench_scan_arg(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(bench(&["/home/user"]), None);
+        assert_eq!(
+            bench(&["--bench-scan=/home/user"]).as_deref(),
+            Some("/home/user")
+        );
+        assert_eq!(
+            bench(&["--bench-scan", "/home/user"]).as_deref(),
+            Some("/home/user")
+        );
+        assert_eq!(folder(&["--bench-scan=/home/user"]), None);
+        assert_eq!(folder(&["--bench-scan", "/home/user"]), None);
+        assert_eq!(
+            folder(&["--bench-scan", "/data", "/home/user"]).as_deref(),
+            Some("/home/user")
+        );
+    }
+}
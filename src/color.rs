@@ -0,0 +1,566 @@
+use druid::piet::GradientStop;
+use druid::text::{Formatter, Selection, Validation, ValidationError};
+use druid::{Color, Data, Lens, RadialGradient};
+
+/// Minimum contrast ratio between a fill and its contrasting color.
+/// WCAG asks for 3:1 for graphical objects such as outlines.
+pub(crate) const MIN_CONTRAST_RATIO: f64 = 3.0;
+
+/// How much of their brightness dimmed colors keep
+const DIM_FACTOR: f64 = 0.35;
+
+/// Height of a cushion's ridges, higher makes the edges darker
+const CUSHION_HEIGHT: f64 = 1.0;
+/// Brightness of a cushion where it faces away from the light
+const CUSHION_AMBIENT: f64 = 0.4;
+/// Number of gradient steps approximating a cushion from center to edge
+const CUSHION_STEPS: usize = 6;
+
+/// Saturation and lightness of colors picked by name
+const NAME_SATURATION: f64 = 0.65;
+const NAME_LIGHTNESS: f64 = 0.5;
+/// Lightness of directory borders, brighter than the files they surround
+const DIRECTORY_BORDER_LIGHTNESS: f64 = 0.75;
+
+/// The Okabe-Ito palette, whose colors stay apart with the common kinds of color blindness.
+/// Its black is left out, as that's what everything is drawn on.
+const OKABE_ITO: [Color; 7] = [
+    Color::rgb8(0xe6, 0x9f, 0x00),
+    Color::rgb8(0x56, 0xb4, 0xe9),
+    Color::rgb8(0x00, 0x9e, 0x73),
+    Color::rgb8(0xf0, 0xe4, 0x42),
+    Color::rgb8(0x00, 0x72, 0xb2),
+    Color::rgb8(0xd5, 0x5e, 0x00),
+    Color::rgb8(0xcc, 0x79, 0xa7),
+];
+
+/// Which colors names and the position gradient are colored from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum Palette {
+    /// Any hue names hash to, and red to blue along the positions
+    #[default]
+    Vivid,
+    /// The Okabe-Ito colors, and orange to blue along the positions
+    OkabeIto,
+}
+
+impl Palette {
+    /// The gradient this palette's positions run along.
+    pub(crate) fn gradient(self) -> Gradient {
+        match self {
+            Palette::Vivid => Gradient::default(),
+            Palette::OkabeIto => Gradient {
+                start: OKABE_ITO[0],
+                end: OKABE_ITO[4],
+            },
+        }
+    }
+
+    /// A color of this palette that's always the same for the same `name`.
+    pub(crate) fn name_color(self, name: &str) -> Color {
+        match self {
+            Palette::Vivid => name_color(name),
+            Palette::OkabeIto => OKABE_ITO[(name_hash(name) % OKABE_ITO.len() as u64) as usize],
+        }
+    }
+}
+
+/// How the visualization picks a node's color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum ColorMode {
+    /// A gradient along the nodes' order
+    #[default]
+    Position,
+    /// A hue hashed from the node's extension, or name for directories
+    ByNameHash,
+}
+
+/// The colors the position gradient runs between.
+#[derive(Debug, Clone, PartialEq, Data, Lens)]
+pub(crate) struct Gradient {
+    pub(crate) start: Color,
+    pub(crate) end: Color,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            start: Color::rgb(1.0, 0.0, 0.0),
+            end: Color::rgb(0.0, 0.0, 1.0),
+        }
+    }
+}
+
+impl Gradient {
+    /// The color at `t` from 0.0 at the start to 1.0 at the end.
+    pub(crate) fn at(&self, t: f64) -> Color {
+        lerp_color(&self.start, &self.end, t)
+    }
+}
+
+/// Interpolates each channel linearly from `a` at `t` = 0.0 to `b` at `t` = 1.0.
+pub(crate) fn lerp_color(a: &Color, b: &Color, t: f64) -> Color {
+    let (a_red, a_green, a_blue, a_alpha) = a.as_rgba();
+    let (b_red, b_green, b_blue, b_alpha) = b.as_rgba();
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    Color::rgba(
+        lerp(a_red, b_red),
+        lerp(a_green, b_green),
+        lerp(a_blue, b_blue),
+        lerp(a_alpha, b_alpha),
+    )
+}
+
+/// Edits a color as `#rrggbb` hex.
+pub(crate) struct HexColorFormatter;
+
+impl Formatter<Color> for HexColorFormatter {
+    fn format(&self, value: &Color) -> String {
+        let (red, green, blue, _) = value.as_rgba8();
+        format!("#{red:02x}{green:02x}{blue:02x}")
+    }
+
+    fn validate_partial_input(&self, _input: &str, _sel: &Selection) -> Validation {
+        // Anything may be on the way to a valid color, it's checked once editing is done
+        Validation::success()
+    }
+
+    fn value(&self, input: &str) -> Result<Color, ValidationError> {
+        Color::from_hex_str(input.trim()).map_err(ValidationError::new)
+    }
+}
+
+fn scale_brightness(color: Color, factor: f64) -> Color {
+    let (red, green, blue, alpha) = color.as_rgba();
+    Color::rgba(red * factor, green * factor, blue * factor, alpha)
+}
+
+/// Darkens `color` so a highlighted color stands out next to it.
+pub(crate) fn dim(color: Color) -> Color {
+    scale_brightness(color, DIM_FACTOR)
+}
+
+/// Mutes `color` towards the grey of its brightness, by `amount` from 0.0 for unchanged to
+/// 1.0 for fully grey, so directories stand apart from the files drawn next to them.
+pub(crate) fn tint_directory(color: Color, amount: f64) -> Color {
+    let (red, green, blue, alpha) = color.as_rgba();
+    let grey = 0.299 * red + 0.587 * green + 0.114 * blue;
+    lerp_color(
+        &color,
+        &Color::rgba(grey, grey, grey, alpha),
+        amount.clamp(0.0, 1.0),
+    )
+}
+
+/// `name` hashed with 64 bit FNV-1a rather than `std`'s hasher, whose output may change
+/// between Rust versions, so a name keeps its color across runs and builds.
+fn name_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Hue in degrees picked by hashing `name`.
+fn name_hue(name: &str) -> f64 {
+    (name_hash(name) % 360) as f64
+}
+
+/// Converts a hue in degrees and saturation and lightness from 0.0 to 1.0 to a color.
+fn hsl(hue: f64, saturation: f64, lightness: f64) -> Color {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let lightest = lightness - chroma / 2.0;
+    Color::rgb(red + lightest, green + lightest, blue + lightest)
+}
+
+/// A color that's always the same for the same `name`.
+pub(crate) fn name_color(name: &str) -> Color {
+    hsl(name_hue(name), NAME_SATURATION, NAME_LIGHTNESS)
+}
+
+/// A subtle color for the border of the directory `name`, translucent so it doesn't hide the
+/// files along it.
+pub(crate) fn directory_border_color(name: &str) -> Color {
+    hsl(name_hue(name), NAME_SATURATION, DIRECTORY_BORDER_LIGHTNESS).with_alpha(0.6)
+}
+
+/// Brightness from 0.0 to 1.0 of a cushion at `(u, v)` within its cell, both from 0.0 to 1.0.
+///
+/// The cushion is the height field `4h·u(1-u) + 4h·v(1-v)` lit from straight above,
+/// so it's brightest in the center and darkens towards the edges.
+pub(crate) fn cushion_brightness(u: f64, v: f64) -> f64 {
+    let slope_u = 4.0 * CUSHION_HEIGHT * (1.0 - 2.0 * u);
+    let slope_v = 4.0 * CUSHION_HEIGHT * (1.0 - 2.0 * v);
+    // Cosine between the surface normal (-slope_u, -slope_v, 1) and the light (0, 0, 1)
+    let lit = 1.0 / (1.0 + slope_u * slope_u + slope_v * slope_v).sqrt();
+    CUSHION_AMBIENT + (1.0 - CUSHION_AMBIENT) * lit
+}
+
+/// A brush shading a cell of `color` like a cushion, to be filled into the cell's rect.
+pub(crate) fn cushion_gradient(color: Color) -> RadialGradient {
+    let stops = (0..=CUSHION_STEPS)
+        .map(|step| {
+            let distance = step as f64 / CUSHION_STEPS as f64;
+            GradientStop {
+                pos: distance as f32,
+                color: scale_brightness(color, cushion_brightness(0.5 + distance / 2.0, 0.5)),
+            }
+        })
+        .collect::<Vec<_>>();
+    RadialGradient::new(0.5, stops)
+}
+
+/// Relative luminance as defined by WCAG, from linearized sRGB components.
+fn relative_luminance((red, green, blue): (f64, f64, f64)) -> f64 {
+    let linearize = |channel: f64| {
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linearize(red) + 0.7152 * linearize(green) + 0.0722 * linearize(blue)
+}
+
+/// WCAG contrast ratio between two luminances, ranging from 1.0 to 21.0.
+fn contrast_ratio(a: f64, b: f64) -> f64 {
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// A color as red, green, blue and alpha from 0.0 to 1.0, independent of any UI toolkit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::opaque(0.0, 0.0, 0.0);
+    pub const WHITE: Rgba = Rgba::opaque(1.0, 1.0, 1.0);
+
+    pub const fn opaque(red: f64, green: f64, blue: f64) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha: 1.0,
+        }
+    }
+
+    /// The opaque color this looks like when drawn over black.
+    fn over_black(self) -> Self {
+        Self::opaque(
+            self.red * self.alpha,
+            self.green * self.alpha,
+            self.blue * self.alpha,
+        )
+    }
+
+    fn luminance(self) -> f64 {
+        relative_luminance((self.red, self.green, self.blue))
+    }
+}
+
+impl From<Color> for Rgba {
+    fn from(color: Color) -> Self {
+        let (red, green, blue, alpha) = color.as_rgba();
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+}
+
+impl From<Rgba> for Color {
+    fn from(color: Rgba) -> Self {
+        Color::rgba(color.red, color.green, color.blue, color.alpha)
+    }
+}
+
+/// Returns an opaque color that stands out against `color`.
+///
+/// Translucent colors are treated as drawn over a black background, like the visualization's.
+/// The inverted color is used if its contrast ratio reaches `min_contrast`, otherwise black or
+/// white, which reach at least ~4.6:1 but not necessarily more.
+pub fn get_contrasting_color<C: Into<Rgba> + From<Rgba>>(color: C, min_contrast: f64) -> C {
+    let color = color.into().over_black();
+    let inverted = Rgba::opaque(1.0 - color.red, 1.0 - color.green, 1.0 - color.blue);
+    if contrast_ratio(color.luminance(), inverted.luminance()) >= min_contrast {
+        return inverted.into();
+    }
+    get_black_or_white::<Rgba>(color).into()
+}
+
+/// Returns black or white, whichever contrasts most with `color`.
+///
+/// Translucent colors are treated as drawn over a black background.
+pub fn get_black_or_white<C: Into<Rgba> + From<Rgba>>(color: C) -> C {
+    let luminance = color.into().over_black().luminance();
+    // One of them always reaches at least ~4.6:1
+    if contrast_ratio(luminance, 0.0) >= contrast_ratio(luminance, 1.0) {
+        Rgba::BLACK.into()
+    } else {
+        Rgba::WHITE.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::text::Formatter;
+    use druid::Color;
+
+    use super::{
+        contrast_ratio, cushion_brightness, dim, get_black_or_white, get_contrasting_color, hsl,
+        lerp_color, name_color, name_hue, relative_luminance, tint_directory, HexColorFormatter,
+        Palette, Rgba, DIM_FACTOR, MIN_CONTRAST_RATIO, OKABE_ITO,
+    };
+
+    fn luminance(color: Color) -> f64 {
+        let (red, green, blue, alpha) = color.as_rgba();
+        relative_luminance((red * alpha, green * alpha, blue * alpha))
+    }
+
+    #[test]
+    fn contrasting_color_has_sufficient_contrast() {
+        let colors = [
+            Color::BLACK,
+            Color::WHITE,
+            Color::grey(0.5),
+            Color::rgb(0.5, 0.5, 0.6),
+            Color::rgb(0.3, 0.4, 0.9),
+            Color::rgb(0.5, 0.0, 0.5),
+            Color::rgb(1.0, 0.0, 0.0),
+            Color::rgba(1.0, 1.0, 1.0, 0.4),
+        ];
+        for color in colors {
+            let contrasting = get_contrasting_color(color, MIN_CONTRAST_RATIO);
+            let ratio = contrast_ratio(luminance(color), luminance(contrasting));
+            assert!(ratio >= MIN_CONTRAST_RATIO, "{color:?} only got {ratio}");
+        }
+    }
+
+    #[test]
+    fn contrast_through_rgba() {
+        let navy = Rgba::opaque(0.0, 0.0, 0.5);
+        assert_eq!(
+            get_contrasting_color(navy, MIN_CONTRAST_RATIO),
+            Rgba::opaque(1.0, 1.0, 0.5)
+        );
+        assert_eq!(get_black_or_white(navy), Rgba::WHITE);
+        assert_eq!(get_black_or_white(Rgba::opaque(1.0, 1.0, 0.5)), Rgba::BLACK);
+        // Drawn over black, a faint white is dark
+        let faint = Rgba {
+            alpha: 0.1,
+            ..Rgba::WHITE
+        };
+        assert_eq!(get_black_or_white(faint), Rgba::WHITE);
+
+        let color = Color::rgba8(0x12, 0x34, 0x56, 0x78);
+        assert_eq!(Color::from(Rgba::from(color)), color);
+        assert_eq!(get_black_or_white(Color::grey(0.9)), Color::BLACK);
+    }
+
+    #[test]
+    fn contrast_meets_configured_threshold() {
+        let fills = (0..=20).flat_map(|step| {
+            let value = step as f64 / 20.0;
+            [
+                Color::grey(value),
+                Color::rgb(value, 0.5, 1.0 - value),
+                Color::rgb(0.8, value, 0.2),
+            ]
+        });
+        for fill in fills {
+            for min_contrast in [1.0, 2.0, 3.0, 4.0, 4.5] {
+                let outline = get_contrasting_color(fill, min_contrast);
+                let ratio = contrast_ratio(luminance(fill), luminance(outline));
+                assert!(
+                    ratio >= min_contrast,
+                    "{fill:?} only got {ratio} of {min_contrast}"
+                );
+            }
+        }
+        // A mid-tone's inverse barely differs, so a strict threshold falls back
+        let mid = Rgba::opaque(0.5, 0.5, 0.5);
+        assert_eq!(get_contrasting_color(mid, 1.0), Rgba::opaque(0.5, 0.5, 0.5));
+        assert_eq!(get_contrasting_color(mid, 3.0), Rgba::BLACK);
+    }
+
+    #[test]
+    fn contrasting_color_is_opaque() {
+        let (.., alpha) =
+            get_contrasting_color(Color::rgba(0.2, 0.8, 0.2, 0.3), MIN_CONTRAST_RATIO).as_rgba();
+        assert_eq!(alpha, 1.0);
+    }
+
+    #[test]
+    fn dimming_darkens_and_keeps_alpha() {
+        let (red, green, blue, alpha) = dim(Color::rgba(1.0, 0.5, 0.0, 0.8)).as_rgba();
+        // Colors are stored with 8 bits per channel
+        let close = |a: f64, b: f64| (a - b).abs() < 1.0 / 255.0;
+        assert!(close(red, DIM_FACTOR) && close(green, 0.5 * DIM_FACTOR) && blue == 0.0);
+        assert!(close(alpha, 0.8));
+        assert!(luminance(dim(Color::rgb(0.2, 0.9, 0.4))) < luminance(Color::rgb(0.2, 0.9, 0.4)));
+    }
+
+    #[test]
+    fn cushion_is_brightest_in_the_center() {
+        let center = cushion_brightness(0.5, 0.5);
+        assert_eq!(center, 1.0);
+        for (u, v) in [(0.0, 0.5), (1.0, 0.5), (0.5, 0.0), (0.5, 1.0), (0.0, 0.0)] {
+            assert!(cushion_brightness(u, v) < center, "({u}, {v})");
+        }
+        assert_eq!(cushion_brightness(0.2, 0.5), cushion_brightness(0.8, 0.5));
+        assert!(cushion_brightness(0.0, 0.0) < cushion_brightness(0.0, 0.5));
+        assert!(cushion_brightness(0.25, 0.5) > cushion_brightness(0.1, 0.5));
+    }
+
+    #[test]
+    fn name_colors_are_deterministic() {
+        assert_eq!(name_color("rs"), name_color("rs"));
+        // Pinned so a changed hash, which would recolor everything between runs, gets noticed
+        assert_eq!(name_hue("rs"), 122.0);
+        assert_eq!(name_hue(""), 77.0);
+    }
+
+    #[test]
+    fn name_hues_spread() {
+        let names = [
+            "rs", "toml", "md", "png", "jpg", "txt", "json", "lock", "html", "css",
+        ];
+        let mut hues: Vec<_> = names.iter().map(|name| name_hue(name) as u32).collect();
+        hues.sort_unstable();
+        hues.dedup();
+        assert_eq!(hues.len(), names.len());
+        assert!(hues.last().unwrap() - hues.first().unwrap() > 180);
+    }
+
+    #[test]
+    fn hsl_converts_primaries() {
+        let rgb = |color: Color| {
+            let (red, green, blue, _) = color.as_rgba();
+            (red, green, blue)
+        };
+        assert_eq!(rgb(hsl(0.0, 1.0, 0.5)), (1.0, 0.0, 0.0));
+        assert_eq!(rgb(hsl(120.0, 1.0, 0.5)), (0.0, 1.0, 0.0));
+        assert_eq!(rgb(hsl(240.0, 1.0, 0.5)), (0.0, 0.0, 1.0));
+        assert_eq!(rgb(hsl(0.0, 0.0, 1.0)), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn lerp_color_interpolates_channels() {
+        let teal = Color::rgb8(0x00, 0x80, 0x80);
+        let orange = Color::rgba8(0xff, 0x80, 0x00, 0x00);
+        assert_eq!(lerp_color(&teal, &orange, 0.0), teal);
+        assert_eq!(lerp_color(&teal, &orange, 1.0), orange);
+        let (red, green, blue, alpha) = lerp_color(&teal, &orange, 0.5).as_rgba8();
+        assert_eq!((red, green, blue), (0x80, 0x80, 0x40));
+        assert_eq!(alpha, 0x80);
+    }
+
+    #[test]
+    fn hex_colors_round_trip() {
+        let color = Color::rgb8(0x12, 0xab, 0xef);
+        assert_eq!(HexColorFormatter.format(&color), "#12abef");
+        assert_eq!(HexColorFormatter.value("#12abef").unwrap(), color);
+        assert!(HexColorFormatter.value("teal").is_err());
+    }
+
+    #[test]
+    fn directory_tint_mutes_towards_grey() {
+        let orange = Color::rgba(1.0, 0.5, 0.0, 0.8);
+        assert_eq!(tint_directory(orange, 0.0), orange);
+        let (red, green, blue, alpha) = tint_directory(orange, 1.0).as_rgba8();
+        assert!(red == green && green == blue, "{red} {green} {blue}");
+        assert_eq!(alpha, orange.as_rgba8().3);
+        let (red, green, blue, _) = tint_directory(orange, 0.5).as_rgba();
+        // Less saturated, but with its hue kept
+        assert!(red > green && green > blue && red < 1.0 && blue > 0.0);
+        assert_eq!(tint_directory(orange, 2.0), tint_directory(orange, 1.0));
+    }
+
+    /// `color` as seen with full protanopia, deuteranopia and tritanopia, simulated as by
+    /// Machado, Oliveira and Fernandes.
+    fn color_blind_views(color: Color) -> [(f64, f64, f64); 3] {
+        const SIMULATIONS: [[[f64; 3]; 3]; 3] = [
+            [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        ];
+        let linearize = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let encode = |c: f64| {
+            let c = c.clamp(0.0, 1.0);
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        let (red, green, blue, _) = color.as_rgba();
+        let linear = [linearize(red), linearize(green), linearize(blue)];
+        SIMULATIONS.map(|matrix| {
+            let [red, green, blue] = matrix
+                .map(|row| encode(row.iter().zip(linear).map(|(factor, c)| factor * c).sum()));
+            (red, green, blue)
+        })
+    }
+
+    #[test]
+    fn color_blind_palette_stays_distinguishable() {
+        let distance = |(a_red, a_green, a_blue): (f64, f64, f64), (b_red, b_green, b_blue)| {
+            f64::hypot(
+                f64::hypot(a_red - b_red, a_green - b_green),
+                a_blue - b_blue,
+            )
+        };
+        let gradient = Palette::OkabeIto.gradient();
+        let pairs = (0..OKABE_ITO.len())
+            .flat_map(|a| (a + 1..OKABE_ITO.len()).map(move |b| (OKABE_ITO[a], OKABE_ITO[b])))
+            .chain([(gradient.start, gradient.end)]);
+        for (a, b) in pairs {
+            for (a_seen, b_seen) in color_blind_views(a).into_iter().zip(color_blind_views(b)) {
+                assert!(distance(a_seen, b_seen) > 0.1, "{a:?} and {b:?}");
+            }
+        }
+
+        assert_eq!(
+            Palette::OkabeIto.name_color("rs"),
+            Palette::OkabeIto.name_color("rs")
+        );
+        assert!(OKABE_ITO.contains(&Palette::OkabeIto.name_color("toml")));
+        assert_eq!(Palette::Vivid.name_color("rs"), name_color("rs"));
+        assert_eq!(Palette::Vivid.gradient(), Default::default());
+    }
+}
@@ -0,0 +1,172 @@
+use std::io;
+use std::path::Path;
+
+use druid::Data;
+
+use crate::file_system::FileNode;
+
+/// Where deleted files go.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum DeleteMode {
+    /// To the trash or recycle bin, where they can be restored from
+    #[default]
+    Trash,
+    /// Off the disk for good
+    Permanent,
+}
+
+impl DeleteMode {
+    /// Deletes `node` from disk through `deleter`, a whole directory with all it contains.
+    ///
+    /// Whether it's removed as a directory goes by what's on disk, as a scanned archive is a
+    /// directory in the tree but a file on disk.
+    pub(crate) fn delete(self, node: &FileNode, deleter: &impl Deleter) -> io::Result<()> {
+        let path = node.path();
+        if !node.is_selectable() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a stand-in for several files can't be deleted",
+            ));
+        }
+        match self {
+            DeleteMode::Trash => deleter.trash(path),
+            DeleteMode::Permanent if deleter.is_dir(path)? => deleter.remove_dir_all(path),
+            DeleteMode::Permanent => deleter.remove_file(path),
+        }
+    }
+}
+
+/// What deleting goes through, so tests can tell how a file was deleted without touching the
+/// trash.
+pub(crate) trait Deleter {
+    /// Whether `path` is a directory on disk, not following symlinks.
+    fn is_dir(&self, path: &Path) -> io::Result<bool>;
+    fn trash(&self, path: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The system's trash and filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SystemDeleter;
+
+impl Deleter for SystemDeleter {
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        Ok(std::fs::symlink_metadata(path)?.is_dir())
+    }
+
+    fn trash(&self, path: &Path) -> io::Result<()> {
+        trash::delete(path).map_err(io::Error::other)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_dir_all(path)
+    }
+}
+
+#[cfg(test)]
+pub(crate) use mock::MockDeleter;
+
+#[cfg(test)]
+mod mock {
+    use std::cell::RefCell;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::Deleter;
+
+    /// Records what it's asked to delete, by which way, without deleting anything.
+    #[derive(Debug, Default)]
+    pub(crate) struct MockDeleter {
+        pub(crate) calls: RefCell<Vec<(&'static str, PathBuf)>>,
+        /// What's a directory on disk, everything else being a file
+        pub(crate) directories: Vec<PathBuf>,
+    }
+
+    impl MockDeleter {
+        fn record(&self, call: &'static str, path: &Path) -> io::Result<()> {
+            self.calls.borrow_mut().push((call, path.to_path_buf()));
+            Ok(())
+        }
+    }
+
+    impl Deleter for MockDeleter {
+        fn is_dir(&self, path: &Path) -> io::Result<bool> {
+            Ok(self.directories.iter().any(|directory| directory == path))
+        }
+
+        fn trash(&self, path: &Path) -> io::Result<()> {
+            self.record("trash", path)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.record("remove_file", path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.record("remove_dir_all", path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use druid::im::vector;
+
+    use super::{DeleteMode, MockDeleter};
+    use crate::file_system::FileNode;
+
+    #[test]
+    fn trash_mode_trashes_instead_of_unlinking() {
+        let file = FileNode::File {
+            path: "/a/file".into(),
+            size: Some(1),
+        };
+        let directory = FileNode::Directory {
+            path: "/a/dir".into(),
+            children: vector![],
+        };
+        // Scanned into, but a file on disk
+        let archive = FileNode::Directory {
+            path: "/a/archive.zip".into(),
+            children: vector![],
+        };
+        let calls = |mode: DeleteMode| {
+            let deleter = MockDeleter {
+                directories: vec!["/a/dir".into()],
+                ..Default::default()
+            };
+            mode.delete(&file, &deleter).unwrap();
+            mode.delete(&directory, &deleter).unwrap();
+            mode.delete(&archive, &deleter).unwrap();
+            deleter.calls.into_inner()
+        };
+        let (file, directory, archive) = (
+            PathBuf::from("/a/file"),
+            PathBuf::from("/a/dir"),
+            PathBuf::from("/a/archive.zip"),
+        );
+        assert_eq!(
+            calls(DeleteMode::Trash),
+            [
+                ("trash", file.clone()),
+                ("trash", directory.clone()),
+                ("trash", archive.clone())
+            ]
+        );
+        assert_eq!(
+            calls(DeleteMode::Permanent),
+            [
+                ("remove_file", file),
+                ("remove_dir_all", directory),
+                ("remove_file", archive)
+            ]
+        );
+    }
+}
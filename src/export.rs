@@ -0,0 +1,151 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use druid::piet::{Device, RenderContext};
+use druid::Size;
+use serde::Serialize;
+
+use crate::visualization_widget::draw_state;
+
+/// A file as it's written to JSON.
+#[derive(Debug, Serialize)]
+struct ExportedFile<'a> {
+    path: std::borrow::Cow<'a, str>,
+    size: u64,
+}
+
+/// Renders the visualization of `data` at `size` pixels into a PNG file at `path`.
+pub(crate) fn export_png(
+    data: &crate::AppState,
+    size: Size,
+    path: &Path,
+) -> Result<(), druid::piet::Error> {
+    let mut device = Device::new()?;
+    let mut target = device.bitmap_target(size.width as usize, size.height as usize, 1.0)?;
+    {
+        let mut rc = target.render_context();
+        draw_state(&mut rc, data, size);
+        rc.finish()?;
+    }
+    target.save_to_file(path)
+}
+
+/// Writes `files` as CSV with a `path,size` header, quoting paths where needed.
+pub(crate) fn write_csv(files: &[(PathBuf, u64)], out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "path,size")?;
+    for (path, size) in files {
+        let path = path.to_string_lossy();
+        if path.contains([',', '"', '\n', '\r']) {
+            writeln!(out, "\"{}\",{size}", path.replace('"', "\"\""))?;
+        } else {
+            writeln!(out, "{path},{size}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `files` as a JSON array of objects with a `path` and a `size`.
+pub(crate) fn write_json(files: &[(PathBuf, u64)], out: &mut impl Write) -> std::io::Result<()> {
+    let files: Vec<_> = files
+        .iter()
+        .map(|(path, size)| ExportedFile {
+            path: path.to_string_lossy(),
+            size: *size,
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &files)?;
+    writeln!(out)
+}
+
+/// What `write` writes, as a string for the clipboard.
+pub(crate) fn to_clipboard_text(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+    let mut buffer = Vec::new();
+    // Writing to memory only fails if serializing does, which it doesn't for paths and sizes
+    if let Err(err) = write(&mut buffer) {
+        tracing::warn!("Failed writing the files to copy: {err}");
+    }
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+    use druid::Size;
+
+    use super::{export_png, to_clipboard_text, write_csv, write_json};
+    use crate::file_system::FileNode;
+    use crate::AppState;
+
+    #[test]
+    fn export_writes_png_of_requested_size() {
+        let state = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/".into(),
+                children: vector![
+                    FileNode::File {
+                        path: "/1".into(),
+                        size: Some(1),
+                    },
+                    FileNode::File {
+                        path: "/2".into(),
+                        size: Some(2),
+                    },
+                ],
+            }),
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.png");
+        export_png(&state, Size::new(64.0, 32.0), &path).unwrap();
+
+        let png = std::fs::read(path).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // The IHDR chunk comes first and starts with the big-endian width and height
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 64);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 32);
+    }
+
+    #[test]
+    fn copied_files_match_the_export_of_what_is_shown() {
+        let mut state = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/".into(),
+                children: vector![
+                    FileNode::File {
+                        path: "/a,b.txt".into(),
+                        size: Some(1),
+                    },
+                    FileNode::File {
+                        path: "/c.log".into(),
+                        size: Some(2),
+                    },
+                    FileNode::File {
+                        path: "/d.txt".into(),
+                        size: Some(3),
+                    },
+                ],
+            }),
+            ..Default::default()
+        };
+        state.hidden_extensions.insert("log".into());
+        let files = state.visible_files();
+        assert_eq!(files, [("/a,b.txt".into(), 1), ("/d.txt".into(), 3)]);
+
+        let mut csv = Vec::new();
+        write_csv(&files, &mut csv).unwrap();
+        let copied = to_clipboard_text(|out| write_csv(&files, out));
+        assert_eq!(copied.as_bytes(), csv);
+        assert_eq!(copied, "path,size\n\"/a,b.txt\",1\n/d.txt,3\n");
+
+        let copied = to_clipboard_text(|out| write_json(&files, out));
+        let json: serde_json::Value = serde_json::from_str(&copied).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "path": "/a,b.txt", "size": 1 },
+                { "path": "/d.txt", "size": 3 }
+            ])
+        );
+    }
+}
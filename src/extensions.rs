@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use druid::im::{HashMap, HashSet, Vector};
+
+use crate::file_system::FileNode;
+
+/// Lowercase extension of `path`, empty if it has none.
+pub(crate) fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// The extensions listed in `text`, separated by commas or spaces, lowercase and without a
+/// leading dot. `None` if there are none.
+pub(crate) fn parse_extensions(text: &str) -> Option<Vec<String>> {
+    let extensions: Vec<_> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|extension| extension.trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect();
+    (!extensions.is_empty()).then_some(extensions)
+}
+
+/// Total size of the files with each extension, largest first.
+pub(crate) fn extension_sizes(root: &FileNode) -> Vector<(String, u64)> {
+    let mut sizes = HashMap::<String, u64>::new();
+    for file in root {
+        *sizes.entry(extension(file.path())).or_default() += file.size();
+    }
+    let mut sizes: Vec<_> = sizes.into_iter().collect();
+    sizes.sort_by(|(a_name, a_size), (b_name, b_size)| {
+        b_size.cmp(a_size).then_with(|| a_name.cmp(b_name))
+    });
+    sizes.into()
+}
+
+/// `root` without the files below it whose extension is in `hidden`.
+pub(crate) fn without_extensions(root: &FileNode, hidden: &HashSet<String>) -> FileNode {
+    match root {
+        FileNode::Directory { path, children } => FileNode::Directory {
+            path: path.clone(),
+            children: children
+                .iter()
+                .filter(|child| match child {
+                    FileNode::File { path, .. } => !hidden.contains(&extension(path)),
+                    FileNode::Directory { .. }
+                    | FileNode::Symlink { .. }
+                    | FileNode::Aggregate { .. } => true,
+                })
+                .map(|child| without_extensions(child, hidden))
+                .collect(),
+        },
+        FileNode::File { .. } | FileNode::Symlink { .. } | FileNode::Aggregate { .. } => {
+            root.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::{hashset, vector};
+
+    use super::{extension_sizes, parse_extensions, without_extensions};
+    use crate::file_system::FileNode;
+
+    fn tree() -> FileNode {
+        FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![FileNode::File {
+                        path: "/a/1.RS".into(),
+                        size: Some(1),
+                    }],
+                },
+                FileNode::File {
+                    path: "/2.png".into(),
+                    size: Some(2),
+                },
+                FileNode::File {
+                    path: "/3.rs".into(),
+                    size: Some(4),
+                },
+                FileNode::File {
+                    path: "/Makefile".into(),
+                    size: Some(8),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn sizes_by_extension() {
+        assert_eq!(
+            extension_sizes(&tree()),
+            vector![
+                (String::new(), 8),
+                (String::from("rs"), 5),
+                (String::from("png"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn hidden_extensions_are_removed() {
+        let shown = without_extensions(&tree(), &hashset![String::from("rs")]);
+        assert_eq!(shown.size(), 10);
+        assert_eq!(
+            shown
+                .into_vector()
+                .iter()
+                .map(|file| file.path().display().to_string())
+                .collect::<Vec<_>>(),
+            ["/2.png", "/Makefile"]
+        );
+    }
+
+    #[test]
+    fn extension_lists() {
+        assert_eq!(parse_extensions(""), None);
+        assert_eq!(parse_extensions(" , "), None);
+        assert_eq!(
+            parse_extensions("mp4, .MKV  avi,").unwrap(),
+            ["mp4", "mkv", "avi"]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use druid::im::Vector;
+use druid::{Data, Lens};
+
+use crate::extensions::extension;
+use crate::file_system::FileNode;
+
+/// Files listed in the largest files panel
+pub(crate) const LARGEST_FILES_COUNT: usize = 20;
+/// Directories listed in the largest folders panel
+pub(crate) const LARGEST_DIRECTORIES_COUNT: usize = 10;
+
+/// A file in a list, with what it can be sorted by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ListedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    /// `None` if the file's metadata couldn't be read
+    pub(crate) modified: Option<SystemTime>,
+}
+
+impl ListedFile {
+    /// Lists `node`, reading its modification time.
+    fn of(node: &FileNode) -> Self {
+        let path = node.path().clone();
+        let modified = std::fs::symlink_metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Self {
+            size: node.size(),
+            path,
+            modified,
+        }
+    }
+}
+
+/// What a file list is sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum SortKey {
+    #[default]
+    Size,
+    Name,
+    Extension,
+    /// Files without a known modification time come first
+    Modified,
+}
+
+/// How a file list is sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data, Lens)]
+pub(crate) struct FileSort {
+    pub(crate) key: SortKey,
+    pub(crate) descending: bool,
+}
+
+impl Default for FileSort {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Size,
+            descending: true,
+        }
+    }
+}
+
+impl FileSort {
+    /// Compares by the key, ties broken by path so the order is stable.
+    pub(crate) fn compare(&self, a: &ListedFile, b: &ListedFile) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Name => a.path.file_name().cmp(&b.path.file_name()),
+            SortKey::Extension => extension(&a.path).cmp(&extension(&b.path)),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.path.cmp(&b.path));
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    pub(crate) fn sorted(&self, files: &Vector<ListedFile>) -> Vector<ListedFile> {
+        let mut files = files.clone();
+        files.sort_by(|a, b| self.compare(a, b));
+        files
+    }
+}
+
+/// The `n` largest files below `root`, largest first.
+pub(crate) fn largest_files(root: &FileNode, n: usize) -> Vector<ListedFile> {
+    root.clone()
+        .largest_first()
+        .filter(|node| matches!(node, FileNode::File { .. }))
+        .take(n)
+        .map(|node| ListedFile::of(&node))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use druid::im::{vector, Vector};
+
+    use super::{FileSort, ListedFile, SortKey};
+
+    fn files() -> Vector<ListedFile> {
+        let listed = |path: &str, size, modified: Option<u64>| ListedFile {
+            path: path.into(),
+            size,
+            modified: modified.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+        };
+        vector![
+            listed("/b/movie.mkv", 300, Some(20)),
+            listed("/a/notes.txt", 10, None),
+            listed("/c/archive.tar", 200, Some(30)),
+            listed("/a/zeta.MKV", 10, Some(10)),
+        ]
+    }
+
+    fn sorted_paths(key: SortKey, descending: bool) -> Vec<String> {
+        FileSort { key, descending }
+            .sorted(&files())
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sort_by_size() {
+        assert_eq!(
+            sorted_paths(SortKey::Size, true),
+            [
+                "/b/movie.mkv",
+                "/c/archive.tar",
+                "/a/zeta.MKV",
+                "/a/notes.txt"
+            ]
+        );
+        assert_eq!(
+            sorted_paths(SortKey::Size, false),
+            [
+                "/a/notes.txt",
+                "/a/zeta.MKV",
+                "/c/archive.tar",
+                "/b/movie.mkv"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_by_name() {
+        assert_eq!(
+            sorted_paths(SortKey::Name, false),
+            [
+                "/c/archive.tar",
+                "/b/movie.mkv",
+                "/a/notes.txt",
+                "/a/zeta.MKV"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_by_extension() {
+        assert_eq!(
+            sorted_paths(SortKey::Extension, false),
+            [
+                "/a/zeta.MKV",
+                "/b/movie.mkv",
+                "/c/archive.tar",
+                "/a/notes.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_by_modified() {
+        assert_eq!(
+            sorted_paths(SortKey::Modified, true),
+            [
+                "/c/archive.tar",
+                "/b/movie.mkv",
+                "/a/zeta.MKV",
+                "/a/notes.txt"
+            ]
+        );
+    }
+}
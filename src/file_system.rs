@@ -1,23 +1,146 @@
 use druid::im::Vector;
-use std::path::PathBuf;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+use crate::extensions::extension;
+use crate::metadata::{EntryKind, EntryMetadata, MetadataProvider, RealFs};
+use crate::mounts::{duplicate_mount_points, read_mounts};
+use crate::path_case::PathCase;
+use crate::units::plural;
+
+/// Files in one range of sizes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) struct BucketStat {
+    pub(crate) count: u64,
+    pub(crate) bytes: u64,
+}
+
+/// Index of the bucket between the ascending `boundaries` that `size` falls into, each bucket
+/// including its lower boundary.
+pub(crate) fn bucket_of(boundaries: &[u64], size: u64) -> usize {
+    boundaries.partition_point(|boundary| *boundary <= size)
+}
+
+/// How often a resumable scan saves the subtrees it finished
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub(crate) enum FileNode {
     Directory {
         path: PathBuf,
         children: Vector<FileNode>,
     },
     File {
+        path: PathBuf,
+        /// `None` if the file's metadata couldn't be read
+        size: Option<u64>,
+    },
+    /// A symbolic link, which isn't followed
+    Symlink {
+        path: PathBuf,
+        target: PathBuf,
+        /// Whether `target` doesn't exist
+        broken: bool,
+    },
+    /// Stands in for `count` files that aren't shown one by one. There is nothing at `path`
+    /// on disk, so it can't be selected, opened or deleted.
+    Aggregate {
         path: PathBuf,
         size: u64,
+        count: u64,
     },
 }
 
 impl FileNode {
+    /// Size in bytes, leaving out files of unknown size.
     pub(crate) fn size(&self) -> u64 {
         match self {
-            FileNode::Directory { .. } => 0,
-            FileNode::File { size, .. } => *size,
+            FileNode::Directory { children, .. } => children.iter().map(FileNode::size).sum(),
+            FileNode::File { size, .. } => size.unwrap_or(0),
+            FileNode::Symlink { .. } => 0,
+            FileNode::Aggregate { size, .. } => *size,
+        }
+    }
+
+    /// Size in bytes of the files directly in this directory, not in its subdirectories.
+    /// A file is directly in itself.
+    pub(crate) fn direct_file_size(&self) -> u64 {
+        match self {
+            FileNode::Directory { children, .. } => children
+                .iter()
+                .filter(|child| !matches!(child, FileNode::Directory { .. }))
+                .map(FileNode::size)
+                .sum(),
+            leaf => leaf.size(),
+        }
+    }
+
+    /// Whether this node is a real file, directory or symlink that can be selected, rather than
+    /// a stand-in for several.
+    pub(crate) fn is_selectable(&self) -> bool {
+        !matches!(self, FileNode::Aggregate { .. })
+    }
+
+    /// This directory with its contents replaced by one node standing in for all of them, so
+    /// it keeps its size but doesn't take a node per file.
+    fn folded(self) -> FileNode {
+        let FileNode::Directory { path, children } = self else {
+            return self;
+        };
+        if children.is_empty() {
+            return FileNode::Directory { path, children };
+        }
+        let size = children.iter().map(FileNode::size).sum();
+        // Counted through what subdirectories folded before already stand in for
+        let items = children.iter().map(FileNode::descendant_file_count).sum();
+        FileNode::Directory {
+            children: Vector::unit(FileNode::Aggregate {
+                path: path.join(format!("{items} {}", plural(items, "item", "items"))),
+                size,
+                count: items,
+            }),
+            path,
+        }
+    }
+
+    /// How many files of which total size are in each bucket between the ascending `buckets`
+    /// boundaries, one more than there are boundaries. Files of unknown size are left out.
+    pub(crate) fn size_histogram(&self, buckets: &[u64]) -> Vec<BucketStat> {
+        let mut histogram = vec![BucketStat::default(); buckets.len() + 1];
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                FileNode::Directory { children, .. } => stack.extend(children),
+                FileNode::File {
+                    size: Some(size), ..
+                } => {
+                    let bucket = &mut histogram[bucket_of(buckets, *size)];
+                    bucket.count += 1;
+                    bucket.bytes += size;
+                }
+                FileNode::File { size: None, .. }
+                | FileNode::Symlink { .. }
+                | FileNode::Aggregate { .. } => {}
+            }
+        }
+        histogram
+    }
+
+    /// Number of files in this tree, 1 for a file. Symlinks aren't counted.
+    pub(crate) fn descendant_file_count(&self) -> u64 {
+        match self {
+            FileNode::Directory { children, .. } => {
+                children.iter().map(FileNode::descendant_file_count).sum()
+            }
+            FileNode::File { .. } => 1,
+            FileNode::Symlink { .. } => 0,
+            FileNode::Aggregate { count, .. } => *count,
         }
     }
 
@@ -25,14 +148,210 @@ impl FileNode {
         match self {
             FileNode::Directory { path, .. } => path,
             FileNode::File { path, .. } => path,
+            FileNode::Symlink { path, .. } => path,
+            FileNode::Aggregate { path, .. } => path,
         }
     }
 
-    pub(crate) fn as_vector(self) -> Vector<FileNode> {
+    pub(crate) fn into_vector(self) -> Vector<FileNode> {
         self.into_iter().collect()
     }
+
+    /// The files and symlinks of this tree in pre-order, borrowed.
+    pub(crate) fn iter(&self) -> FileNodeRefIterator<'_> {
+        FileNodeRefIterator { stack: vec![self] }
+    }
+
+    /// Every file and symlink of this tree with its size, sorted by path.
+    pub(crate) fn flatten_sorted(&self) -> Vec<(PathBuf, u64)> {
+        let mut files: Vec<_> = self
+            .iter()
+            .map(|node| (node.path().clone(), node.size()))
+            .collect();
+        files.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        files
+    }
+
+    /// The files and symlinks of this tree, largest first, ties in path order.
+    pub(crate) fn largest_first(self) -> LargestFirstIterator {
+        LargestFirstIterator::new(self)
+    }
+
+    /// The node at `path` in this tree.
+    pub(crate) fn find(&self, path: &Path) -> Option<&FileNode> {
+        if self.path() == path {
+            return Some(self);
+        }
+        let FileNode::Directory { children, .. } = self else {
+            return None;
+        };
+        children
+            .iter()
+            .find(|child| path.starts_with(child.path()))?
+            .find(path)
+    }
+
+    /// Removes the node at `path` from the tree below this node, returning its size.
+    ///
+    /// Directory sizes are summed from their children, so every ancestor shrinks by the
+    /// removed size without a rescan. This node itself can't be removed.
+    pub(crate) fn remove_path(&mut self, path: &Path) -> Option<u64> {
+        let FileNode::Directory { children, .. } = self else {
+            return None;
+        };
+        if let Some(index) = children.iter().position(|child| child.path() == path) {
+            return Some(children.remove(index).size());
+        }
+        children
+            .iter_mut()
+            .find(|child| path.starts_with(child.path()))?
+            .remove_path(path)
+    }
+
+    /// Every symlink in this tree, in pre-order.
+    pub(crate) fn symlinks(&self) -> Vec<&FileNode> {
+        self.iter_with_depth()
+            .map(|(node, _)| node)
+            .filter(|node| matches!(node, FileNode::Symlink { .. }))
+            .collect()
+    }
+
+    /// Every node in the tree with its depth in pre-order, the root being depth 0.
+    pub(crate) fn iter_with_depth(&self) -> DepthIterator<'_> {
+        DepthIterator {
+            stack: vec![(self, 0)],
+        }
+    }
+
+    /// The `n` deepest leaves (files and empty directories) with their depth, deepest first.
+    pub(crate) fn deepest_paths(&self, n: usize) -> Vec<(PathBuf, usize)> {
+        // The shallowest leaf kept so far is on top, to make room for a deeper one
+        let mut deepest = BinaryHeap::with_capacity(n + 1);
+        let leaves = self.iter_with_depth().filter(|(node, _)| match node {
+            FileNode::Directory { children, .. } => children.is_empty(),
+            FileNode::File { .. } | FileNode::Symlink { .. } | FileNode::Aggregate { .. } => true,
+        });
+        for (node, depth) in leaves {
+            deepest.push((Reverse(depth), node.path()));
+            if deepest.len() > n {
+                deepest.pop();
+            }
+        }
+        deepest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(Reverse(depth), path)| (path.clone(), depth))
+            .collect()
+    }
+
+    /// The `n` directories below this node with the largest aggregate size, largest first.
+    ///
+    /// Each directory counts everything nested in it, so a directory and its subdirectories
+    /// can all be listed. This node itself isn't.
+    pub(crate) fn largest_directories(&self, n: usize) -> Vec<(&PathBuf, u64)> {
+        fn collect<'a>(node: &'a FileNode, directories: &mut Vec<(&'a PathBuf, u64)>) {
+            if let FileNode::Directory { children, .. } = node {
+                for child in children {
+                    if let FileNode::Directory { path, .. } = child {
+                        directories.push((path, child.size()));
+                        collect(child, directories);
+                    }
+                }
+            }
+        }
+        let mut directories = Vec::new();
+        collect(self, &mut directories);
+        directories.sort_by(|(a_path, a_size), (b_path, b_size)| {
+            b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+        });
+        directories.truncate(n);
+        directories
+    }
+
+    /// Removes every file known to be empty from the tree below this node, returning how many
+    /// were removed. Files of unknown size are kept.
+    pub(crate) fn prune_empty_files(&mut self) -> u64 {
+        let FileNode::Directory { children, .. } = self else {
+            return 0;
+        };
+        let before = children.len();
+        children.retain(|child| !matches!(child, FileNode::File { size: Some(0), .. }));
+        let mut pruned = (before - children.len()) as u64;
+        for child in children.iter_mut() {
+            pruned += child.prune_empty_files();
+        }
+        pruned
+    }
+
+    /// Combines two scans of the same path, `other` winning where they disagree.
+    ///
+    /// Two directories get the children of both, merging children at the same path as told by
+    /// `case`, so each directory's size is the sum of its disjoint parts. Anything else, like two
+    /// files or a file that became a directory, is replaced by `other`.
+    pub(crate) fn merge(self, other: FileNode, case: PathCase) -> FileNode {
+        debug_assert!(
+            case.is_same_path(self.path(), other.path()),
+            "merging nodes at different paths"
+        );
+        let (path, children, other_children) = match (self, other) {
+            (
+                FileNode::Directory { path, children },
+                FileNode::Directory {
+                    children: other_children,
+                    ..
+                },
+            ) => (path, children, other_children),
+            (_, other) => return other,
+        };
+        let mut by_path: HashMap<PathBuf, FileNode> = children
+            .into_iter()
+            .map(|child| (case.key(child.path()), child))
+            .collect();
+        for other_child in other_children {
+            let key = case.key(other_child.path());
+            let merged = match by_path.remove(&key) {
+                Some(child) => child.merge(other_child, case),
+                None => other_child,
+            };
+            by_path.insert(key, merged);
+        }
+        let mut children: Vec<_> = by_path.into_values().collect();
+        children.sort_by(canonical_order);
+        FileNode::Directory {
+            path,
+            children: children.into(),
+        }
+    }
+}
+
+/// What the visualization sizes nodes by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum Metric {
+    #[default]
+    Bytes,
+    /// Files below a node, to find where inodes go
+    FileCount,
+}
+
+impl Metric {
+    pub(crate) fn of(self, node: &FileNode) -> u64 {
+        match self {
+            Metric::Bytes => node.size(),
+            Metric::FileCount => node.descendant_file_count(),
+        }
+    }
 }
 
+/// Canonical order for siblings: directories before files and symlinks, each sorted by path.
+pub(crate) fn canonical_order(a: &FileNode, b: &FileNode) -> std::cmp::Ordering {
+    let is_file = |node: &FileNode| !matches!(node, FileNode::Directory { .. });
+    is_file(a)
+        .cmp(&is_file(b))
+        .then_with(|| a.path().cmp(b.path()))
+}
+
+/// Yields the files and symlinks of a tree in pre-order, the children of every directory in the
+/// order they're stored.
 pub(crate) struct FileNodeIterator {
     pub(crate) stack: Vec<FileNode>,
 }
@@ -53,16 +372,121 @@ impl Iterator for FileNodeIterator {
                     for child in children.into_iter().rev() {
                         self.stack.push(child);
                     }
-                    let child = self.stack.pop();
-                    assert!(
-                        matches!(child, Some(FileNode::File { .. }) | None),
-                        "FileNodeIterator shouldn't return directories"
-                    );
-                    return child;
                 }
-                FileNode::File { path, size } => {
-                    return Some(FileNode::File { path, size });
+                leaf => return Some(leaf),
+            }
+        }
+        None
+    }
+}
+
+/// Yields every node of a tree, directories included, with its depth.
+pub(crate) struct DepthIterator<'a> {
+    stack: Vec<(&'a FileNode, usize)>,
+}
+
+impl<'a> Iterator for DepthIterator<'a> {
+    type Item = (&'a FileNode, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        if let FileNode::Directory { children, .. } = node {
+            self.stack
+                .extend(children.iter().rev().map(|child| (child, depth + 1)));
+        }
+        Some((node, depth))
+    }
+}
+
+/// Yields the files and symlinks of a tree like [`FileNodeIterator`], without taking the tree.
+pub(crate) struct FileNodeRefIterator<'a> {
+    stack: Vec<&'a FileNode>,
+}
+
+impl<'a> Iterator for FileNodeRefIterator<'a> {
+    type Item = &'a FileNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                FileNode::Directory { children, .. } => self.stack.extend(children.iter().rev()),
+                leaf => return Some(leaf),
+            }
+        }
+        None
+    }
+}
+
+impl<'a> IntoIterator for &'a FileNode {
+    type Item = &'a FileNode;
+    type IntoIter = FileNodeRefIterator<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Yields the files and symlinks of a tree largest first, without sorting all of them up front.
+///
+/// Directories are only expanded once they're the largest node left, as none of their
+/// descendants can be larger than them.
+pub(crate) struct LargestFirstIterator {
+    heap: BinaryHeap<(u64, bool, Reverse<PathBuf>, HeapNode)>,
+}
+
+/// A node in the heap of [`LargestFirstIterator`], compared by the key next to it only.
+struct HeapNode(FileNode);
+
+impl PartialEq for HeapNode {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for HeapNode {}
+
+impl PartialOrd for HeapNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapNode {
+    fn cmp(&self, _other: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl LargestFirstIterator {
+    fn new(root: FileNode) -> Self {
+        let mut iterator = LargestFirstIterator {
+            heap: BinaryHeap::new(),
+        };
+        iterator.push(root);
+        iterator
+    }
+
+    fn push(&mut self, node: FileNode) {
+        // A directory as large as a file goes first, it may hold an equally large file with a
+        // smaller path
+        let is_directory = matches!(node, FileNode::Directory { .. });
+        let (size, path) = (node.size(), Reverse(node.path().clone()));
+        self.heap.push((size, is_directory, path, HeapNode(node)));
+    }
+}
+
+impl Iterator for LargestFirstIterator {
+    type Item = FileNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((.., HeapNode(node))) = self.heap.pop() {
+            match node {
+                FileNode::Directory { children, .. } => {
+                    for child in children {
+                        self.push(child);
+                    }
                 }
+                leaf => return Some(leaf),
             }
         }
         None
@@ -78,100 +502,2113 @@ impl IntoIterator for FileNode {
     }
 }
 
-pub(crate) fn traverse_files_parallel(path: &PathBuf) -> Option<FileNode> {
-    tracing::debug!("Starting traverse with path `{}`", path.display());
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+/// The path typed as `input`, with a leading `~` expanded to the home directory, made absolute
+/// against the working directory and normalized lexically.
+pub(crate) fn resolve_input_path(input: &str) -> PathBuf {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    let cwd = std::env::current_dir().unwrap_or_default();
+    resolve_with(input, home.as_deref(), &cwd)
+}
 
-    let Ok(metadata) = std::fs::metadata(path) else {
-        return None;
+fn resolve_with(input: &str, home: Option<&Path>, cwd: &Path) -> PathBuf {
+    let expanded = match (input.strip_prefix('~'), home) {
+        (Some(rest), Some(home))
+            if rest.is_empty() || rest.starts_with(std::path::is_separator) =>
+        {
+            home.join(rest.trim_start_matches(std::path::is_separator))
+        }
+        _ => PathBuf::from(input),
     };
+    normalize_lexically(&cwd.join(expanded))
+}
 
-    if metadata.is_dir() {
-        if let Ok(entries) = std::fs::read_dir(path) {
-            let paths: Vec<_> = entries
-                .filter_map(|entry| entry.ok().map(|e| e.path()))
-                .collect();
+/// `path` without `.` components, trailing separators and `..` components that go back into
+/// the directory before them, so the same folder is always keyed by the same path.
+///
+/// Only looks at the text, so `link/..` is taken to be the directory containing the symlink
+/// rather than the parent of its target. A `..` at the root stays at the root.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                Some(Component::ParentDir | Component::CurDir) | None => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
 
-            let children: Vector<FileNode> = paths
-                .par_iter()
-                .filter_map(|path| {
-                    if path.is_file() {
-                        let metadata = std::fs::metadata(path).ok();
-                        let size = metadata.map(|md| md.len()).unwrap_or(0);
-
-                        Some(FileNode::File {
-                            path: path.clone(),
-                            size,
-                        })
-                    } else {
-                        traverse_files_parallel(path)
-                    }
-                })
-                .collect::<Vec<_>>()
-                .into();
+/// What size a scan counts files at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum SizeKind {
+    /// Their length, however much of it is stored
+    #[default]
+    Apparent,
+    /// What they take up on disk after transparent compression, where the filesystem tells,
+    /// or otherwise the blocks allocated for them
+    Compressed,
+}
 
-            tracing::debug!(
-                "Found directory `{}` with `{}` children",
-                path.display(),
-                children.len()
-            );
+impl SizeKind {
+    /// The size of the file `path` with `metadata`, read from `fs`.
+    fn of(self, fs: &impl MetadataProvider, path: &Path, metadata: &EntryMetadata) -> u64 {
+        match self {
+            SizeKind::Apparent => metadata.len,
+            SizeKind::Compressed => {
+                let compressed = fs.compressed_size(path).unwrap_or_else(|err| {
+                    tracing::debug!("No compressed size of `{}`: {err}", path.display());
+                    None
+                });
+                // Blocks are 0 where they aren't known either
+                let allocated = (metadata.blocks > 0).then_some(metadata.blocks * 512);
+                compressed.or(allocated).unwrap_or(metadata.len)
+            }
+        }
+    }
+}
+
+/// What a scan does with junctions and other reparse points on Windows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum ReparsePolicy {
+    /// Leave them out of the tree
+    Skip,
+    /// Keep them as symlinks to their target, without scanning it
+    #[default]
+    Flag,
+    /// Scan their target as if it was a directory at their path, flagging them instead if that
+    /// would loop
+    Follow,
+}
+
+/// How often a scan retries reading metadata or a directory when it fails, as it may on flaky
+/// network mounts.
+///
+/// Only errors that may go away are retried, like timeouts and dropped connections, not a
+/// missing file or one that may not be read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data, Lens)]
+pub(crate) struct RetryPolicy {
+    /// Tries after the first one, 0 to give up right away
+    pub(crate) retries: u32,
+    /// Milliseconds waited before the first retry, doubling with every further one
+    pub(crate) backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    /// The result of `attempt`, tried again as long as it fails with a transient error and
+    /// retries are left.
+    pub(crate) fn run<T>(
+        self,
+        mut attempt: impl FnMut() -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let mut backoff = Duration::from_millis(self.backoff_ms);
+        let mut retries = self.retries;
+        loop {
+            match attempt() {
+                Err(err) if retries > 0 && is_transient(&err) => {
+                    tracing::debug!("Retrying in {backoff:?} after {err}");
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    retries -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `err` may go away when trying again. Anything not known to, isn't retried.
+fn is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ResourceBusy
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::NetworkDown
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+    )
+}
+
+/// How a scan treats what it finds.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Data, Lens)]
+pub(crate) struct ScanOptions {
+    /// Keep special files (devices, sockets, fifos) in the tree as empty files, instead of only
+    /// counting them
+    pub(crate) include_special_files: bool,
+    /// Continue from the checkpoint of an interrupted scan of the same folder
+    pub(crate) resume: bool,
+    /// Subdirectories with fewer bytes are folded into one file standing in for their contents,
+    /// 0 expands everything
+    pub(crate) min_expand_size: u64,
+    /// Whether paths differing only in case are the same, when combining a resumed scan with
+    /// its checkpoint
+    pub(crate) path_case: PathCase,
+    pub(crate) reparse_points: ReparsePolicy,
+    /// Lowercase extensions, without the dot, of the only regular files kept in the tree.
+    /// The others are only added up in [`ScanResult::excluded_bytes`].
+    #[data(eq)]
+    pub(crate) only_extensions: Option<Vec<String>>,
+    /// Leave out mount points showing files the scan finds at another path, on Linux
+    pub(crate) skip_duplicate_mounts: bool,
+    pub(crate) retry: RetryPolicy,
+    /// Scan into zip and tar archives as if they were directories, if built with the
+    /// `archives` feature
+    pub(crate) scan_archives: bool,
+    pub(crate) size_kind: SizeKind,
+}
+
+/// What a scan found.
+#[derive(Debug)]
+pub(crate) struct ScanResult {
+    pub(crate) root: FileNode,
+    /// Files that are neither regular files nor directories, whether included or not
+    pub(crate) special_files: u64,
+    /// Bytes in regular files left out by [`ScanOptions::only_extensions`]
+    pub(crate) excluded_bytes: u64,
+    /// 512 byte blocks allocated for the files and directories scanned, 0 if unknown
+    pub(crate) total_blocks: u64,
+    /// What the files inside scanned archives take up in them, see
+    /// [`ScanOptions::scan_archives`]
+    pub(crate) compressed_sizes: HashMap<PathBuf, u64>,
+    /// The archives scanned into, which are directories in `root` but files on disk
+    pub(crate) archives: HashSet<PathBuf>,
+    /// Directories that couldn't be listed, so are left out
+    pub(crate) unreadable_directories: u64,
+    /// Files whose size couldn't be read, so count as empty
+    pub(crate) unknown_sizes: u64,
+    /// Files deleted between being listed and being read
+    pub(crate) vanished: u64,
+}
+
+impl ScanResult {
+    /// Whether the totals take in everything below the scanned folder.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.incomplete_reasons().is_empty()
+    }
+
+    /// Why the totals leave something out, each as a short phrase.
+    pub(crate) fn incomplete_reasons(&self) -> Vec<String> {
+        incomplete_reasons(
+            self.unreadable_directories,
+            self.unknown_sizes,
+            self.vanished,
+        )
+    }
+}
+
+/// Why a tree's totals leave something out, from the counts kept in [`ScanResult`].
+pub(crate) fn incomplete_reasons(
+    unreadable_directories: u64,
+    unknown_sizes: u64,
+    vanished: u64,
+) -> Vec<String> {
+    [
+        (
+            unreadable_directories,
+            "directory",
+            "directories",
+            "unreadable",
+        ),
+        (unknown_sizes, "file", "files", "of unknown size"),
+        (vanished, "file", "files", "vanished while scanning"),
+    ]
+    .into_iter()
+    .filter(|(count, ..)| *count > 0)
+    .map(|(count, one, many, reason)| format!("{count} {} {reason}", plural(count, one, many)))
+    .collect()
+}
+
+/// Why a scan failed.
+#[derive(Debug)]
+pub(crate) enum ScanError {
+    NotFound {
+        path: PathBuf,
+    },
+    /// Neither a directory nor a file, or below a file
+    NotADirectory {
+        path: PathBuf,
+    },
+    PermissionDenied {
+        path: PathBuf,
+    },
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Stopped early, because a newer scan replaced it
+    Cancelled,
+}
+
+impl ScanError {
+    fn from_io(path: &Path, source: std::io::Error) -> Self {
+        let path = path.to_path_buf();
+        match source.kind() {
+            std::io::ErrorKind::NotFound => ScanError::NotFound { path },
+            std::io::ErrorKind::NotADirectory => ScanError::NotADirectory { path },
+            std::io::ErrorKind::PermissionDenied => ScanError::PermissionDenied { path },
+            _ => ScanError::Io { path, source },
+        }
+    }
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::NotFound { path } => write!(f, "`{}` doesn't exist", path.display()),
+            ScanError::NotADirectory { path } => {
+                write!(f, "`{}` isn't a folder or file", path.display())
+            }
+            ScanError::PermissionDenied { path } => {
+                write!(f, "No permission to read `{}`", path.display())
+            }
+            ScanError::Io { path, source } => {
+                write!(f, "Failed reading `{}`: {source}", path.display())
+            }
+            ScanError::Cancelled => write!(f, "Scan cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Fails if scanning `path` couldn't even start.
+fn check_root(
+    fs: &impl MetadataProvider,
+    path: &Path,
+    options: &ScanOptions,
+) -> Result<(), ScanError> {
+    let metadata = options
+        .retry
+        .run(|| fs.metadata(path))
+        .map_err(|err| ScanError::from_io(path, err))?;
+    match metadata.kind {
+        EntryKind::Directory => {
+            options
+                .retry
+                .run(|| fs.read_dir(path))
+                .map_err(|err| ScanError::from_io(path, err))?;
+        }
+        EntryKind::File => {}
+        _ if options.include_special_files => {}
+        _ => {
+            return Err(ScanError::NotADirectory {
+                path: path.to_path_buf(),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Scans `path`, counting every file in `files_found` as it's found so progress can be reported.
+///
+/// Gives up with [`ScanError::Cancelled`] soon after `cancelled` is set.
+pub(crate) fn traverse(
+    path: &Path,
+    options: &ScanOptions,
+    files_found: &AtomicU64,
+    cancelled: &AtomicBool,
+) -> Result<ScanResult, ScanError> {
+    traverse_reporting(&RealFs, path, options, files_found, &|_| {}, cancelled)
+}
+
+/// Scans `path` like [`traverse`], without a way to cancel it or to know why it failed.
+///
+/// Kept for callers from before scans said why they failed, the app itself doesn't use it.
+#[allow(dead_code)]
+pub(crate) fn traverse_files_parallel(
+    path: &Path,
+    options: &ScanOptions,
+    files_found: &AtomicU64,
+) -> Option<FileNode> {
+    traverse(path, options, files_found, &AtomicBool::new(false))
+        .ok()
+        .map(|result| result.root)
+}
+
+/// Scans `path` in `fs` like [`traverse`], calling `found` with every file, symlink and special
+/// file as soon as it's found, from whichever thread found it.
+pub(crate) fn traverse_reporting(
+    fs: &impl MetadataProvider,
+    path: &Path,
+    options: &ScanOptions,
+    files_found: &AtomicU64,
+    found: &(dyn Fn(&FileNode) + Sync),
+    cancelled: &AtomicBool,
+) -> Result<ScanResult, ScanError> {
+    check_root(fs, path, options)?;
+    let mut traversal = Traversal::new(fs, options, files_found).with_duplicate_mounts(path);
+    traversal.found = found;
+    traversal.cancelled = cancelled;
+    let root = traversal.traverse(path);
+    // Entries are reported as they're found, but a lone file has no directory to be found in
+    if let Some(root @ FileNode::File { .. }) = &root {
+        found(root);
+    }
+    traversal.finish(path, root)
+}
+
+/// Re-reads the size of every file in `tree` in place, without listing any directory again,
+/// for when only sizes changed since it was scanned. Files and directories that are gone are
+/// removed, and the number of files removed returned. Sizes are read as `size_kind` says.
+pub(crate) fn refresh_sizes(tree: &mut FileNode, size_kind: SizeKind) -> u64 {
+    refresh_sizes_in(&RealFs, tree, size_kind)
+}
+
+/// Refreshes `tree` from `fs` like [`refresh_sizes`].
+fn refresh_sizes_in(fs: &impl MetadataProvider, tree: &mut FileNode, size_kind: SizeKind) -> u64 {
+    let vanished = AtomicU64::new(0);
+    match refreshed(fs, tree, size_kind, &vanished) {
+        Some(refreshed) => *tree = refreshed,
+        // The root stays as what's left of the scan, but emptied
+        None => match tree {
+            FileNode::Directory { children, .. } => children.clear(),
+            FileNode::File { size, .. } => *size = None,
+            FileNode::Symlink { .. } | FileNode::Aggregate { .. } => {}
+        },
+    }
+    vanished.into_inner()
+}
+
+/// `node` with the sizes its files have now, `None` if it's gone, counting the files gone in
+/// `vanished`.
+fn refreshed(
+    fs: &impl MetadataProvider,
+    node: &FileNode,
+    size_kind: SizeKind,
+    vanished: &AtomicU64,
+) -> Option<FileNode> {
+    use rayon::iter::{ParallelBridge, ParallelIterator};
+
+    // There's nothing on disk to refresh a stand-in from
+    if !node.is_selectable() {
+        return Some(node.clone());
+    }
+    // A symlink is kept as the scan found it for as long as it's there, even if it's broken
+    if let FileNode::Symlink { path, .. } = node {
+        return match fs.symlink_metadata(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            _ => Some(node.clone()),
+        };
+    }
+    let metadata = match fs.metadata(node.path()) {
+        Ok(metadata) => Some(metadata),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::debug!("`{}` vanished since it was scanned", node.path().display());
+            let files = node
+                .iter()
+                .filter(|node| matches!(node, FileNode::File { .. }));
+            vanished.fetch_add(files.count() as u64, Ordering::Relaxed);
+            return None;
+        }
+        Err(err) => {
+            tracing::warn!("Failed reading size of `{}`: {err}", node.path().display());
+            None
+        }
+    };
+    match node {
+        // Archives are directories that are files on disk, and their contents aren't
+        FileNode::Directory { children, path }
+            if metadata.is_none_or(|metadata| metadata.kind == EntryKind::Directory) =>
+        {
+            let mut children: Vec<FileNode> = children
+                .iter()
+                .par_bridge()
+                .filter_map(|child| refreshed(fs, child, size_kind, vanished))
+                .collect();
+            // Bridged children come back in any order
+            children.sort_by(canonical_order);
             Some(FileNode::Directory {
                 path: path.clone(),
-                children,
+                children: children.into(),
             })
-        } else {
-            tracing::debug!("Failed traverse with path `{}`", path.display());
-            None
         }
-    } else if metadata.is_file() {
-        tracing::debug!("Found file `{}`", path.display());
-        Some(FileNode::File {
+        FileNode::File { path, size } => Some(FileNode::File {
             path: path.clone(),
-            size: metadata.len(),
-        })
-    } else {
-        tracing::warn!("Failed traverse with path `{}`", path.display());
-        None
+            // Keeping the size from the scan if it can't be read now
+            size: metadata
+                .map(|metadata| size_kind.of(fs, path, &metadata))
+                .or(*size),
+        }),
+        node => Some(node.clone()),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use druid::im::vector;
+/// Scans `path` like [`traverse`], except for its subdirectories in `finished`,
+/// which were scanned before. Calls `save` with every finished subdirectory once in a while.
+///
+/// Files and special files in the skipped subdirectories aren't counted again. Subdirectories
+/// cut short by `cancelled` aren't saved.
+pub(crate) fn traverse_checkpointed(
+    path: &Path,
+    options: &ScanOptions,
+    files_found: &AtomicU64,
+    finished: Vec<FileNode>,
+    save: &(impl Fn(&[FileNode]) + Sync),
+    cancelled: &AtomicBool,
+) -> Result<ScanResult, ScanError> {
+    check_root(&RealFs, path, options)?;
+    let mut traversal = Traversal::new(&RealFs, options, files_found).with_duplicate_mounts(path);
+    traversal.cancelled = cancelled;
+    let root = match RealFs.read_dir(path) {
+        Ok(paths) => Some(traversal.traverse_checkpointed(path, paths, finished, save)),
+        // Not a directory, so there's nothing to checkpoint
+        Err(_) => traversal.traverse(path),
+    };
+    traversal.finish(path, root)
+}
 
-    use crate::file_system::FileNode;
+/// State of one scan of `fs`, shared by all threads working on it.
+///
+/// Files that can't be found after they were listed got deleted in between, so they're skipped
+/// and counted in `vanished`. Files whose size can't be read for other reasons are kept with an
+/// unknown size.
+struct Traversal<'a, P> {
+    fs: &'a P,
+    options: &'a ScanOptions,
+    files_found: &'a AtomicU64,
+    vanished: AtomicU64,
+    unreadable_directories: AtomicU64,
+    unknown_sizes: AtomicU64,
+    special_files: AtomicU64,
+    excluded_bytes: AtomicU64,
+    blocks: AtomicU64,
+    compressed_sizes: Mutex<HashMap<PathBuf, u64>>,
+    archives: Mutex<HashSet<PathBuf>>,
+    /// Called with every file, symlink and special file as soon as it's found
+    found: &'a (dyn Fn(&FileNode) + Sync),
+    /// Once set, nothing more is walked and the traversal fails
+    cancelled: &'a AtomicBool,
+    /// Mount points that are left out, see [`ScanOptions::skip_duplicate_mounts`]
+    duplicate_mounts: Vec<PathBuf>,
+}
 
-    #[test]
-    fn iterator() {
-        let root = FileNode::Directory {
-            path: "/".into(),
-            children: vector![
-                FileNode::File {
-                    path: "/1".into(),
-                    size: 1
-                },
-                FileNode::Directory {
-                    path: "/2".into(),
+/// What a traversal that can't be cancelled checks
+static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+impl<'a, P: MetadataProvider> Traversal<'a, P> {
+    fn new(fs: &'a P, options: &'a ScanOptions, files_found: &'a AtomicU64) -> Self {
+        Self {
+            fs,
+            options,
+            files_found,
+            vanished: AtomicU64::new(0),
+            unreadable_directories: AtomicU64::new(0),
+            unknown_sizes: AtomicU64::new(0),
+            special_files: AtomicU64::new(0),
+            excluded_bytes: AtomicU64::new(0),
+            blocks: AtomicU64::new(0),
+            compressed_sizes: Default::default(),
+            archives: Default::default(),
+            found: &|_| {},
+            cancelled: &NOT_CANCELLED,
+            duplicate_mounts: Vec::new(),
+        }
+    }
+
+    /// Finds the duplicate mounts below `root`, if the options ask for skipping them and the
+    /// mounts can be read.
+    fn with_duplicate_mounts(mut self, root: &Path) -> Self {
+        if self.options.skip_duplicate_mounts {
+            if let Some(mounts) = read_mounts() {
+                self.duplicate_mounts = duplicate_mount_points(&mounts, root);
+            }
+        }
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `read` of the filesystem, retried as the options say.
+    fn retried<T>(&self, read: impl Fn(&P) -> std::io::Result<T>) -> std::io::Result<T> {
+        self.options.retry.run(|| read(self.fs))
+    }
+
+    fn traverse(&self, path: &Path) -> Option<FileNode> {
+        tracing::debug!("Starting traverse with path `{}`", path.display());
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+        if self.is_cancelled() {
+            return None;
+        }
+        let Ok(metadata) = self.retried(|fs| fs.metadata(path)) else {
+            return None;
+        };
+        self.blocks.fetch_add(metadata.blocks, Ordering::Relaxed);
+
+        if metadata.kind == EntryKind::Directory {
+            if let Ok(paths) = self.retried(|fs| fs.read_dir(path)) {
+                let mut children: Vec<FileNode> = paths
+                    .par_iter()
+                    .filter_map(|path| self.entry(path))
+                    .collect();
+                // read_dir returns entries in no particular order, so sort to get the same tree
+                // on every scan
+                children.sort_by(canonical_order);
+
+                tracing::debug!(
+                    "Found directory `{}` with `{}` children",
+                    path.display(),
+                    children.len()
+                );
+                Some(FileNode::Directory {
+                    path: path.to_path_buf(),
+                    children: children.into(),
+                })
+            } else {
+                tracing::debug!("Failed traverse with path `{}`", path.display());
+                self.unreadable_directories.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        } else if metadata.kind == EntryKind::File {
+            tracing::debug!("Found file `{}`", path.display());
+            self.files_found.fetch_add(1, Ordering::Relaxed);
+            if let Some(archive) = self.archive(path) {
+                return Some(archive);
+            }
+            Some(FileNode::File {
+                path: path.to_path_buf(),
+                size: Some(self.options.size_kind.of(self.fs, path, &metadata)),
+            })
+        } else {
+            tracing::debug!("Found special file `{}`", path.display());
+            self.special_files.fetch_add(1, Ordering::Relaxed);
+            if !self.options.include_special_files {
+                return None;
+            }
+            self.files_found.fetch_add(1, Ordering::Relaxed);
+            Some(FileNode::File {
+                path: path.to_path_buf(),
+                size: Some(0),
+            })
+        }
+    }
+
+    /// The node of an entry found in a directory.
+    fn entry(&self, path: &Path) -> Option<FileNode> {
+        let kind = self.retried(|fs| fs.symlink_metadata(path));
+        let node = match kind.map(|metadata| metadata.kind) {
+            Ok(EntryKind::Symlink) => self.symlink(path),
+            Ok(EntryKind::ReparsePoint) => self.reparse_point(path),
+            Ok(EntryKind::File) => self.file(path),
+            _ if self.duplicate_mounts.iter().any(|mount| mount == path) => {
+                tracing::info!("Skipping `{}`, mounted elsewhere too", path.display());
+                None
+            }
+            _ => self.traverse(path),
+        }?;
+        match node {
+            FileNode::Directory { .. } if node.size() < self.options.min_expand_size => {
+                Some(node.folded())
+            }
+            FileNode::Directory { .. } => Some(node),
+            _ => {
+                (self.found)(&node);
+                Some(node)
+            }
+        }
+    }
+
+    /// A symlink found in a directory.
+    fn symlink(&self, path: &Path) -> Option<FileNode> {
+        let target = match self.retried(|fs| fs.read_link(path)) {
+            Ok(target) => target,
+            Err(err) => {
+                tracing::warn!("Failed reading link `{}`: {err}", path.display());
+                return None;
+            }
+        };
+        Some(FileNode::Symlink {
+            path: path.to_path_buf(),
+            target,
+            // Follows the link, unlike everything else here
+            broken: self.fs.metadata(path).is_err(),
+        })
+    }
+
+    /// A reparse point found in a directory, handled as the options say.
+    ///
+    /// One is only followed if its target isn't a folder the scan came through to it, so
+    /// following them can't loop. The folders are compared resolved, as targets can be relative
+    /// or verbatim `\\?\` paths, and the scan may have come through other reparse points.
+    fn reparse_point(&self, path: &Path) -> Option<FileNode> {
+        match self.options.reparse_points {
+            ReparsePolicy::Skip => {
+                tracing::debug!("Skipping reparse point `{}`", path.display());
+                None
+            }
+            ReparsePolicy::Flag => self.symlink(path),
+            ReparsePolicy::Follow => {
+                let target = self.fs.read_link(path).ok()?;
+                let target = match self
+                    .fs
+                    .canonicalize(&path.parent().unwrap_or(path).join(target))
+                {
+                    Ok(target) => target,
+                    Err(err) => {
+                        tracing::debug!("Not following `{}`: {err}", path.display());
+                        return self.symlink(path);
+                    }
+                };
+                let loops = path.ancestors().skip(1).any(|ancestor| {
+                    self.fs
+                        .canonicalize(ancestor)
+                        .is_ok_and(|ancestor| ancestor == target)
+                });
+                if loops {
+                    tracing::info!(
+                        "Not following `{}` into `{}`, which contains it",
+                        path.display(),
+                        target.display()
+                    );
+                    return self.symlink(path);
+                }
+                self.traverse(path)
+            }
+        }
+    }
+
+    /// A regular file found in a directory.
+    fn file(&self, path: &Path) -> Option<FileNode> {
+        let size = match self.retried(|fs| fs.metadata(path)) {
+            Ok(metadata) => {
+                self.blocks.fetch_add(metadata.blocks, Ordering::Relaxed);
+                Some(self.options.size_kind.of(self.fs, path, &metadata))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!("`{}` vanished while scanning", path.display());
+                self.vanished.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            Err(err) => {
+                tracing::warn!("Failed reading size of `{}`: {err}", path.display());
+                self.unknown_sizes.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        };
+        self.files_found.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(only) = &self.options.only_extensions {
+            if !only.contains(&extension(path)) {
+                self.excluded_bytes
+                    .fetch_add(size.unwrap_or(0), Ordering::Relaxed);
+                return None;
+            }
+        }
+        if let Some(archive) = self.archive(path) {
+            return Some(archive);
+        }
+        Some(FileNode::File {
+            path: path.to_path_buf(),
+            size,
+        })
+    }
+
+    /// The contents of the archive at `path` as a directory, if archives are scanned into and
+    /// it's one that can be read.
+    #[cfg(feature = "archives")]
+    fn archive(&self, path: &Path) -> Option<FileNode> {
+        if !self.options.scan_archives || !crate::archive::is_archive(path) {
+            return None;
+        }
+        match crate::archive::read_archive(path) {
+            Ok(archive) => {
+                self.compressed_sizes
+                    .lock()
+                    .unwrap()
+                    .extend(archive.compressed_sizes);
+                self.archives.lock().unwrap().insert(path.to_path_buf());
+                Some(archive.root)
+            }
+            Err(err) => {
+                tracing::warn!("Failed reading archive `{}`: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "archives"))]
+    fn archive(&self, _path: &Path) -> Option<FileNode> {
+        None
+    }
+
+    /// The result of this traversal having found `root` at `path`.
+    fn finish(&self, path: &Path, root: Option<FileNode>) -> Result<ScanResult, ScanError> {
+        if self.is_cancelled() {
+            tracing::info!("Cancelled scanning `{}`", path.display());
+            return Err(ScanError::Cancelled);
+        }
+        let vanished = self.vanished.load(Ordering::Relaxed);
+        if vanished > 0 {
+            tracing::info!(
+                "{vanished} files vanished while scanning `{}`",
+                path.display()
+            );
+        }
+        Ok(ScanResult {
+            // Checked before, so it vanished since
+            root: root.ok_or_else(|| ScanError::NotFound {
+                path: path.to_path_buf(),
+            })?,
+            special_files: self.special_files.load(Ordering::Relaxed),
+            excluded_bytes: self.excluded_bytes.load(Ordering::Relaxed),
+            total_blocks: self.blocks.load(Ordering::Relaxed),
+            compressed_sizes: std::mem::take(&mut *self.compressed_sizes.lock().unwrap()),
+            archives: std::mem::take(&mut *self.archives.lock().unwrap()),
+            unreadable_directories: self.unreadable_directories.load(Ordering::Relaxed),
+            unknown_sizes: self.unknown_sizes.load(Ordering::Relaxed),
+            vanished,
+        })
+    }
+
+    /// Traverses the directory `path` with the entries `paths`, taking the subdirectories in
+    /// `finished` as they are.
+    fn traverse_checkpointed(
+        &self,
+        path: &Path,
+        paths: Vec<PathBuf>,
+        finished: Vec<FileNode>,
+        save: &(impl Fn(&[FileNode]) + Sync),
+    ) -> FileNode {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let case = self.options.path_case;
+        let listed = |node: &FileNode| {
+            paths
+                .iter()
+                .any(|path| case.is_same_path(path, node.path()))
+        };
+        // Subdirectories deleted since the checkpoint are dropped
+        let finished: Vec<_> = finished.into_iter().filter(listed).collect();
+        for node in &finished {
+            let files = node
+                .iter()
+                .filter(|node| matches!(node, FileNode::File { .. }));
+            self.files_found
+                .fetch_add(files.count() as u64, Ordering::Relaxed);
+        }
+        let remaining: Vec<_> = paths
+            .into_iter()
+            .filter(|path| {
+                !finished
+                    .iter()
+                    .any(|node| case.is_same_path(node.path(), path))
+            })
+            .collect();
+
+        let checkpoint = Mutex::new((finished, Instant::now()));
+        let files: Vec<FileNode> = remaining
+            .into_par_iter()
+            .filter_map(|path| {
+                let node = self.entry(&path)?;
+                // Possibly missing entries, so it's not finished
+                if self.is_cancelled() {
+                    return None;
+                }
+                if !matches!(node, FileNode::Directory { .. }) {
+                    return Some(node);
+                }
+                let mut checkpoint = checkpoint.lock().unwrap();
+                let (finished, last_save) = &mut *checkpoint;
+                finished.push(node);
+                if last_save.elapsed() >= CHECKPOINT_INTERVAL {
+                    save(finished);
+                    *last_save = Instant::now();
+                }
+                None
+            })
+            .collect();
+
+        let (finished, _) = checkpoint.into_inner().unwrap();
+        let directory = |children: Vec<FileNode>| FileNode::Directory {
+            path: path.to_path_buf(),
+            children: children.into(),
+        };
+        directory(finished).merge(directory(files), case)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    use druid::im::vector;
+
+    use crate::file_system::{
+        bucket_of, is_transient, normalize_lexically, refresh_sizes_in, resolve_with, traverse,
+        traverse_files_parallel, traverse_reporting, BucketStat, FileNode, ReparsePolicy,
+        RetryPolicy, ScanError, ScanOptions, ScanResult, SizeKind, Traversal,
+    };
+    use crate::metadata::{EntryMetadata, MemoryFs, MetadataProvider};
+    use crate::path_case::PathCase;
+
+    /// A tree whose stored order is neither by size nor by path
+    fn unordered_tree() -> FileNode {
+        dir(
+            "/",
+            vector![
+                file("/z", 4),
+                dir("/b", vector![file("/b/small", 1), file("/b/big", 6)]),
+                FileNode::Symlink {
+                    path: "/link".into(),
+                    target: "/z".into(),
+                    broken: false,
+                },
+                dir("/a", vector![file("/a/tie", 4), dir("/a/empty", vector![])]),
+            ],
+        )
+    }
+
+    fn paths(nodes: impl Iterator<Item = FileNode>) -> Vec<String> {
+        nodes
+            .map(|node| node.path().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn iterates_in_stored_order() {
+        assert_eq!(
+            paths(unordered_tree().into_iter()),
+            ["/z", "/b/small", "/b/big", "/link", "/a/tie"]
+        );
+    }
+
+    #[test]
+    fn iterates_largest_first() {
+        assert_eq!(
+            paths(unordered_tree().largest_first()),
+            ["/b/big", "/a/tie", "/z", "/b/small", "/link"]
+        );
+        assert_eq!(paths(file("/lone", 1).largest_first()), ["/lone"]);
+    }
+
+    #[test]
+    fn iterator() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::File {
+                    path: "/1".into(),
+                    size: Some(1)
+                },
+                FileNode::Directory {
+                    path: "/2".into(),
                     children: vector![FileNode::File {
                         path: "/2/3".into(),
-                        size: 3,
+                        size: Some(3),
                     }]
                 }
             ],
         };
+        assert!(root.iter().eq(&root.clone().into_vector()));
         let all_children = root.into_iter().collect::<Vec<_>>();
         assert_eq!(
             all_children,
             vec![
                 FileNode::File {
                     path: "/1".into(),
-                    size: 1
+                    size: Some(1)
                 },
                 FileNode::File {
                     path: "/2/3".into(),
-                    size: 3
+                    size: Some(3)
                 }
             ]
         )
     }
+
+    #[test]
+    fn traversal_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["b", "a", "c/d"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        for (file, size) in [("z", 3), ("a/y", 1), ("c/x", 2), ("c/d/w", 4), ("0", 5)] {
+            std::fs::write(dir.path().join(file), vec![0u8; size]).unwrap();
+        }
+
+        let options = ScanOptions::default();
+        let files_found = AtomicU64::new(0);
+        let first = traverse(dir.path(), &options, &files_found, &Default::default())
+            .unwrap()
+            .root;
+        let second = traverse(
+            dir.path(),
+            &options,
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap()
+        .root;
+        assert_eq!(first, second);
+        assert_eq!(files_found.load(Ordering::Relaxed), 5);
+
+        let FileNode::Directory { children, .. } = first else {
+            panic!("expected a directory");
+        };
+        let names = children
+            .iter()
+            .map(|child| child.path().file_name().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "c", "0", "z"]);
+    }
+
+    #[test]
+    fn largest_directories() {
+        let file = |path: &str, size| FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        };
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                file("/1", 100),
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![
+                        FileNode::Directory {
+                            path: "/a/b".into(),
+                            children: vector![file("/a/b/2", 20)],
+                        },
+                        file("/a/3", 5),
+                        FileNode::Directory {
+                            path: "/a/empty".into(),
+                            children: vector![],
+                        },
+                    ],
+                },
+                FileNode::Directory {
+                    path: "/c".into(),
+                    children: vector![file("/c/4", 30)],
+                },
+            ],
+        };
+        let (a, b, c) = (
+            PathBuf::from("/a"),
+            PathBuf::from("/a/b"),
+            PathBuf::from("/c"),
+        );
+        assert_eq!(
+            root.largest_directories(3),
+            vec![(&c, 30), (&a, 25), (&b, 20)]
+        );
+        assert_eq!(root.largest_directories(10).len(), 4);
+        assert_eq!(root.largest_directories(0), vec![]);
+    }
+
+    #[test]
+    fn iterates_with_depth_in_pre_order() {
+        let root = dir(
+            "/",
+            vector![
+                dir(
+                    "/a",
+                    vector![dir("/a/b", vector![file("/a/b/1", 1)]), file("/a/2", 2)]
+                ),
+                file("/3", 3),
+                dir("/empty", vector![]),
+            ],
+        );
+        let visited: Vec<_> = root
+            .iter_with_depth()
+            .map(|(node, depth)| (node.path().to_str().unwrap(), depth))
+            .collect();
+        assert_eq!(
+            visited,
+            [
+                ("/", 0),
+                ("/a", 1),
+                ("/a/b", 2),
+                ("/a/b/1", 3),
+                ("/a/2", 2),
+                ("/3", 1),
+                ("/empty", 1)
+            ]
+        );
+        assert_eq!(file("/x", 1).iter_with_depth().count(), 1);
+    }
+
+    #[test]
+    fn deepest_paths() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::File {
+                    path: "/1".into(),
+                    size: Some(1)
+                },
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![
+                        FileNode::Directory {
+                            path: "/a/b".into(),
+                            children: vector![FileNode::File {
+                                path: "/a/b/2".into(),
+                                size: Some(2),
+                            }]
+                        },
+                        FileNode::Directory {
+                            path: "/a/empty".into(),
+                            children: vector![]
+                        }
+                    ]
+                }
+            ],
+        };
+        assert_eq!(
+            root.deepest_paths(2),
+            vec![("/a/b/2".into(), 3), ("/a/empty".into(), 2)]
+        );
+        assert_eq!(root.deepest_paths(10).len(), 3);
+        assert_eq!(root.deepest_paths(3)[2], ("/1".into(), 1));
+    }
+
+    #[test]
+    fn vanished_files_are_skipped() {
+        let fs = MemoryFs::default()
+            .dir("/dir")
+            .file("/dir/kept", 2)
+            .failing("/dir/vanished", std::io::ErrorKind::NotFound);
+
+        let options = ScanOptions::default();
+        let files_found = AtomicU64::new(0);
+        let traversal = Traversal::new(&fs, &options, &files_found);
+        let root = traversal.traverse(Path::new("/dir")).unwrap();
+
+        assert_eq!(
+            root.into_vector(),
+            vector![FileNode::File {
+                path: "/dir/kept".into(),
+                size: Some(2)
+            }]
+        );
+        assert_eq!(traversal.vanished.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn unreadable_sizes_are_unknown() {
+        let fs = MemoryFs::default()
+            .dir("/dir")
+            .failing("/dir/secret", std::io::ErrorKind::PermissionDenied);
+
+        let options = ScanOptions::default();
+        let files_found = AtomicU64::new(0);
+        let traversal = Traversal::new(&fs, &options, &files_found);
+        let root = traversal.traverse(Path::new("/dir")).unwrap();
+
+        assert_eq!(
+            root.into_vector(),
+            vector![FileNode::File {
+                path: "/dir/secret".into(),
+                size: None
+            }]
+        );
+    }
+
+    #[test]
+    fn empty_files_are_pruned() {
+        let mut root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![
+                        FileNode::File {
+                            path: "/a/empty".into(),
+                            size: Some(0),
+                        },
+                        FileNode::File {
+                            path: "/a/unknown".into(),
+                            size: None,
+                        },
+                    ],
+                },
+                FileNode::File {
+                    path: "/empty".into(),
+                    size: Some(0),
+                },
+                FileNode::File {
+                    path: "/full".into(),
+                    size: Some(1),
+                },
+            ],
+        };
+
+        assert_eq!(root.prune_empty_files(), 2);
+        assert_eq!(
+            root.into_vector(),
+            vector![
+                FileNode::File {
+                    path: "/a/unknown".into(),
+                    size: None,
+                },
+                FileNode::File {
+                    path: "/full".into(),
+                    size: Some(1),
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files_are_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("regular"), [0u8; 1]).unwrap();
+        let fifo = dir.path().join("fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let excluded = traverse(
+            dir.path(),
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(excluded.special_files, 1);
+        assert_eq!(
+            excluded.root.into_vector(),
+            vector![FileNode::File {
+                path: dir.path().join("regular"),
+                size: Some(1),
+            }]
+        );
+
+        let files_found = AtomicU64::new(0);
+        let included = traverse(
+            dir.path(),
+            &ScanOptions {
+                include_special_files: true,
+                ..Default::default()
+            },
+            &files_found,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(included.special_files, 1);
+        assert_eq!(files_found.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            included.root.into_vector(),
+            vector![
+                FileNode::File {
+                    path: fifo,
+                    size: Some(0),
+                },
+                FileNode::File {
+                    path: dir.path().join("regular"),
+                    size: Some(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn input_paths_are_resolved() {
+        let home = Path::new("/home/user");
+        let cwd = Path::new("/work");
+        let resolve = |input| resolve_with(input, Some(home), cwd);
+        assert_eq!(resolve("~"), Path::new("/home/user"));
+        assert_eq!(resolve("~/sub"), Path::new("/home/user/sub"));
+        assert_eq!(resolve("./rel"), Path::new("/work/rel"));
+        assert_eq!(resolve("rel/sub"), Path::new("/work/rel/sub"));
+        assert_eq!(resolve("/abs/path"), Path::new("/abs/path"));
+        // Other users' homes aren't looked up
+        assert_eq!(resolve("~other"), Path::new("/work/~other"));
+        assert_eq!(resolve_with("~/sub", None, cwd), Path::new("/work/~/sub"));
+        assert_eq!(resolve("a/./b"), Path::new("/work/a/b"));
+        assert_eq!(resolve("a/b/.."), Path::new("/work/a"));
+        assert_eq!(resolve("../.."), Path::new("/"));
+    }
+
+    #[test]
+    fn paths_are_normalized_lexically() {
+        let normalize = |path| normalize_lexically(Path::new(path));
+        assert_eq!(normalize("a/./b"), Path::new("a/b"));
+        assert_eq!(normalize("a/b/.."), Path::new("a"));
+        assert_eq!(normalize("/a/b/"), Path::new("/a/b"));
+        assert_eq!(normalize("/a/b/./"), Path::new("/a/b"));
+        assert_eq!(normalize("/a/../../b"), Path::new("/b"));
+        assert_eq!(normalize("../a/../.."), Path::new("../.."));
+        assert_eq!(normalize("."), Path::new(""));
+    }
+
+    #[test]
+    fn scan_errors_tell_why() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, [0u8; 1]).unwrap();
+        let scan = |path: PathBuf| {
+            traverse(
+                &path,
+                &ScanOptions::default(),
+                &AtomicU64::new(0),
+                &Default::default(),
+            )
+        };
+
+        assert!(matches!(
+            scan(dir.path().join("missing")),
+            Err(ScanError::NotFound { path }) if path == dir.path().join("missing")
+        ));
+        assert!(matches!(
+            scan(file.join("below")),
+            Err(ScanError::NotADirectory { path }) if path == file.join("below")
+        ));
+        assert!(scan(file.clone()).is_ok());
+
+        // The wrapper from before scans had errors only tells whether it worked
+        let found = AtomicU64::new(0);
+        let options = ScanOptions::default();
+        assert_eq!(
+            traverse_files_parallel(&dir.path().join("missing"), &options, &found),
+            None
+        );
+        assert_eq!(
+            traverse_files_parallel(&file, &options, &found).map(|root| root.size()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn nodes_are_found_by_path() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![FileNode::File {
+                        path: "/a/1".into(),
+                        size: Some(1),
+                    }],
+                },
+                FileNode::File {
+                    path: "/ab".into(),
+                    size: Some(2),
+                },
+            ],
+        };
+        assert_eq!(root.find(Path::new("/")), Some(&root));
+        assert_eq!(root.find(Path::new("/ab")).map(FileNode::size), Some(2));
+        assert_eq!(root.find(Path::new("/a/1")).map(FileNode::size), Some(1));
+        assert_eq!(root.find(Path::new("/a/2")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_classified() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("target"), [0u8; 4]).unwrap();
+        std::os::unix::fs::symlink("target", dir.path().join("valid")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("broken")).unwrap();
+
+        let root = traverse(
+            dir.path(),
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap()
+        .root;
+        // Links don't add the size of their targets
+        assert_eq!(root.size(), 4);
+        assert_eq!(
+            root.symlinks(),
+            [
+                &FileNode::Symlink {
+                    path: dir.path().join("broken"),
+                    target: "missing".into(),
+                    broken: true,
+                },
+                &FileNode::Symlink {
+                    path: dir.path().join("valid"),
+                    target: "target".into(),
+                    broken: false,
+                },
+            ]
+        );
+    }
+
+    fn dir(path: &str, children: druid::im::Vector<FileNode>) -> FileNode {
+        FileNode::Directory {
+            path: path.into(),
+            children,
+        }
+    }
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    fn aggregate(path: &str, size: u64, count: u64) -> FileNode {
+        FileNode::Aggregate {
+            path: path.into(),
+            size,
+            count,
+        }
+    }
+
+    #[test]
+    fn merge_disjoint_children() {
+        let merged = dir("/", vector![file("/b", 2)])
+            .merge(dir("/", vector![file("/a", 1)]), PathCase::Sensitive);
+        assert_eq!(merged, dir("/", vector![file("/a", 1), file("/b", 2)]));
+        assert_eq!(merged.size(), 3);
+    }
+
+    #[test]
+    fn merge_overlapping_file_takes_later_size() {
+        let merged = dir("/", vector![file("/a", 1), file("/b", 2)])
+            .merge(dir("/", vector![file("/a", 10)]), PathCase::Sensitive);
+        assert_eq!(merged, dir("/", vector![file("/a", 10), file("/b", 2)]));
+
+        // A file that became a directory is replaced too
+        let merged = dir("/", vector![file("/a", 1)]).merge(
+            dir("/", vector![dir("/a", vector![file("/a/1", 4)])]),
+            PathCase::Sensitive,
+        );
+        assert_eq!(
+            merged,
+            dir("/", vector![dir("/a", vector![file("/a/1", 4)])])
+        );
+    }
+
+    #[test]
+    fn merge_directory_with_new_children() {
+        let merged = dir(
+            "/",
+            vector![dir("/d", vector![file("/d/1", 1)]), file("/x", 8)],
+        )
+        .merge(
+            dir(
+                "/",
+                vector![dir("/d", vector![file("/d/2", 2), file("/d/1", 3)])],
+            ),
+            PathCase::Sensitive,
+        );
+        assert_eq!(
+            merged,
+            dir(
+                "/",
+                vector![
+                    dir("/d", vector![file("/d/1", 3), file("/d/2", 2)]),
+                    file("/x", 8)
+                ]
+            )
+        );
+        assert_eq!(merged.size(), 13);
+    }
+
+    #[test]
+    fn merge_keys_by_case() {
+        let old = dir("/", vector![dir("/Docs", vector![file("/Docs/a", 1)])]);
+        let new = dir("/", vector![dir("/docs", vector![file("/docs/b", 2)])]);
+
+        let sensitive = old.clone().merge(new.clone(), PathCase::Sensitive);
+        assert_eq!(
+            sensitive,
+            dir(
+                "/",
+                vector![
+                    dir("/Docs", vector![file("/Docs/a", 1)]),
+                    dir("/docs", vector![file("/docs/b", 2)])
+                ]
+            )
+        );
+
+        let insensitive = old.merge(new, PathCase::Insensitive);
+        assert_eq!(
+            insensitive,
+            dir(
+                "/",
+                vector![dir(
+                    "/Docs",
+                    vector![file("/Docs/a", 1), file("/docs/b", 2)]
+                )]
+            )
+        );
+        assert_eq!(insensitive.size(), 3);
+    }
+
+    #[test]
+    fn removing_nested_file_shrinks_ancestors() {
+        let mut root = dir(
+            "/",
+            vector![
+                dir(
+                    "/a",
+                    vector![
+                        dir("/a/b", vector![file("/a/b/1", 5), file("/a/b/2", 7)]),
+                        file("/a/3", 1)
+                    ]
+                ),
+                file("/4", 2),
+            ],
+        );
+        let sizes = |root: &FileNode| {
+            ["/", "/a", "/a/b"].map(|path| root.find(Path::new(path)).unwrap().size())
+        };
+        assert_eq!(sizes(&root), [15, 13, 12]);
+
+        assert_eq!(root.remove_path(Path::new("/a/b/2")), Some(7));
+        assert_eq!(sizes(&root), [8, 6, 5]);
+        assert_eq!(root.find(Path::new("/a/b/2")), None);
+        assert_eq!(root.find(Path::new("/4")).unwrap().size(), 2);
+
+        assert_eq!(root.remove_path(Path::new("/a/b/2")), None);
+        assert_eq!(root.remove_path(Path::new("/")), None);
+        assert_eq!(root.remove_path(Path::new("/a")), Some(6));
+        assert_eq!(root.size(), 2);
+    }
+
+    #[test]
+    fn scans_virtual_tree() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .file("/root/a.txt", 3)
+            .dir("/root/sub")
+            .file("/root/sub/b.bin", 40)
+            .symlink("/root/sub/link", "b.bin")
+            .symlink("/root/dangling", "/nowhere")
+            .special("/root/fifo")
+            .failing("/root/locked", std::io::ErrorKind::PermissionDenied);
+        let files_found = AtomicU64::new(0);
+        let result = traverse_reporting(
+            &fs,
+            Path::new("/root"),
+            &ScanOptions::default(),
+            &files_found,
+            &|_| {},
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.root,
+            dir(
+                "/root",
+                vector![
+                    dir(
+                        "/root/sub",
+                        vector![
+                            file("/root/sub/b.bin", 40),
+                            FileNode::Symlink {
+                                path: "/root/sub/link".into(),
+                                target: "b.bin".into(),
+                                broken: false,
+                            }
+                        ]
+                    ),
+                    file("/root/a.txt", 3),
+                    FileNode::Symlink {
+                        path: "/root/dangling".into(),
+                        target: "/nowhere".into(),
+                        broken: true,
+                    },
+                    FileNode::File {
+                        path: "/root/locked".into(),
+                        size: None,
+                    },
+                ]
+            )
+        );
+        assert_eq!(result.special_files, 1);
+        assert_eq!(files_found.load(Ordering::Relaxed), 3);
+
+        assert!(matches!(
+            traverse_reporting(
+                &fs,
+                Path::new("/missing"),
+                &ScanOptions::default(),
+                &files_found,
+                &|_| {},
+                &AtomicBool::new(false)
+            ),
+            Err(ScanError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn direct_file_size_leaves_out_subdirectories() {
+        let root = dir(
+            "/",
+            vector![
+                file("/1", 1),
+                dir(
+                    "/a",
+                    vector![
+                        file("/a/10", 10),
+                        dir("/a/b", vector![file("/a/b/100", 100)])
+                    ]
+                ),
+                file("/2", 2),
+                FileNode::File {
+                    path: "/unknown".into(),
+                    size: None,
+                },
+            ],
+        );
+        assert_eq!(root.direct_file_size(), 3);
+        assert_eq!(root.find(Path::new("/a")).unwrap().direct_file_size(), 10);
+        assert_eq!(file("/lone", 4).direct_file_size(), 4);
+        assert_eq!(dir("/empty", vector![]).direct_file_size(), 0);
+    }
+
+    #[test]
+    fn small_subdirectories_stay_folded() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .dir("/root/small")
+            .file("/root/small/1", 10)
+            .dir("/root/small/nested")
+            .file("/root/small/nested/2", 20)
+            .file("/root/small/nested/3", 5)
+            .dir("/root/big")
+            .file("/root/big/1", 100)
+            .dir("/root/big/sub")
+            .file("/root/big/sub/2", 60)
+            .dir("/root/big/tiny")
+            .file("/root/big/tiny/3", 1)
+            .file("/root/lone", 1);
+        let options = ScanOptions {
+            min_expand_size: 50,
+            ..Default::default()
+        };
+        let root = Traversal::new(&fs, &options, &AtomicU64::new(0))
+            .traverse(Path::new("/root"))
+            .unwrap();
+
+        assert_eq!(
+            root,
+            dir(
+                "/root",
+                vector![
+                    dir(
+                        "/root/big",
+                        vector![
+                            dir("/root/big/sub", vector![file("/root/big/sub/2", 60)]),
+                            dir(
+                                "/root/big/tiny",
+                                vector![aggregate("/root/big/tiny/1 item", 1, 1)]
+                            ),
+                            file("/root/big/1", 100),
+                        ]
+                    ),
+                    dir(
+                        "/root/small",
+                        vector![aggregate("/root/small/3 items", 35, 3)]
+                    ),
+                    file("/root/lone", 1),
+                ]
+            )
+        );
+        assert_eq!(root.size(), 197);
+    }
+
+    #[test]
+    fn files_are_bucketed_by_size() {
+        let root = dir(
+            "/",
+            vector![
+                file("/0", 0),
+                file("/5", 5),
+                dir("/a", vector![file("/a/10", 10), file("/a/50", 50)]),
+                file("/100", 100),
+                file("/1000", 1000),
+                FileNode::File {
+                    path: "/unknown".into(),
+                    size: None,
+                },
+            ],
+        );
+        let stat = |count, bytes| BucketStat { count, bytes };
+        assert_eq!(
+            root.size_histogram(&[10, 100]),
+            [stat(2, 5), stat(2, 60), stat(2, 1100)]
+        );
+        assert_eq!(root.size_histogram(&[]), [stat(6, 1165)]);
+        assert_eq!(bucket_of(&[10, 100], 9), 0);
+        assert_eq!(bucket_of(&[10, 100], 10), 1);
+        assert_eq!(bucket_of(&[10, 100], u64::MAX), 2);
+    }
+
+    #[test]
+    fn cancelled_scan_fails() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .dir("/root/a")
+            .file("/root/a/x", 1);
+        let result = traverse_reporting(
+            &fs,
+            Path::new("/root"),
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &|_| {},
+            &AtomicBool::new(true),
+        );
+        assert!(matches!(result, Err(ScanError::Cancelled)));
+    }
+
+    #[test]
+    fn reparse_points_follow_policy() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .dir("/root/data")
+            .file("/root/data/x", 4)
+            .reparse_point("/root/junction", "data")
+            .reparse_point("/root/data/up", "/root")
+            .reparse_point("/root/data/back", "..");
+        let scan = |reparse_points| {
+            let options = ScanOptions {
+                reparse_points,
+                ..Default::default()
+            };
+            Traversal::new(&fs, &options, &AtomicU64::new(0))
+                .traverse(Path::new("/root"))
+                .unwrap()
+        };
+        let link = |path: &str, target: &str| FileNode::Symlink {
+            path: path.into(),
+            target: target.into(),
+            broken: false,
+        };
+
+        assert_eq!(
+            scan(ReparsePolicy::Skip),
+            dir(
+                "/root",
+                vector![dir("/root/data", vector![file("/root/data/x", 4)])]
+            )
+        );
+        assert_eq!(
+            scan(ReparsePolicy::Flag),
+            dir(
+                "/root",
+                vector![
+                    dir(
+                        "/root/data",
+                        vector![
+                            link("/root/data/back", ".."),
+                            link("/root/data/up", "/root"),
+                            file("/root/data/x", 4)
+                        ]
+                    ),
+                    link("/root/junction", "data")
+                ]
+            )
+        );
+        // Going up would loop, however the target is spelled, so only the junction is followed
+        assert_eq!(
+            scan(ReparsePolicy::Follow),
+            dir(
+                "/root",
+                vector![
+                    dir(
+                        "/root/data",
+                        vector![
+                            link("/root/data/back", ".."),
+                            link("/root/data/up", "/root"),
+                            file("/root/data/x", 4)
+                        ]
+                    ),
+                    dir(
+                        "/root/junction",
+                        vector![
+                            link("/root/junction/back", ".."),
+                            link("/root/junction/up", "/root"),
+                            file("/root/junction/x", 4)
+                        ]
+                    )
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn only_listed_extensions_are_kept() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .file("/root/movie.MKV", 100)
+            .file("/root/notes.txt", 3)
+            .dir("/root/sub")
+            .file("/root/sub/clip.mp4", 50)
+            .file("/root/sub/Makefile", 7);
+        let options = ScanOptions {
+            only_extensions: Some(vec![String::from("mkv"), String::from("mp4")]),
+            ..Default::default()
+        };
+        let result = traverse_reporting(
+            &fs,
+            Path::new("/root"),
+            &options,
+            &AtomicU64::new(0),
+            &|_| {},
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(
+            result.root,
+            dir(
+                "/root",
+                vector![
+                    dir("/root/sub", vector![file("/root/sub/clip.mp4", 50)]),
+                    file("/root/movie.MKV", 100)
+                ]
+            )
+        );
+        assert_eq!(result.excluded_bytes, 10);
+    }
+
+    #[test]
+    fn flattens_sorted_by_path() {
+        let root = dir(
+            "/",
+            vector![
+                dir(
+                    "/b",
+                    vector![dir("/b/y", vector![file("/b/y/deep", 4)]), file("/b/x", 3)]
+                ),
+                dir("/a", vector![file("/a/2", 2), file("/a/1", 1)]),
+                dir("/empty", vector![]),
+                file("/c", 5),
+                FileNode::Symlink {
+                    path: "/a.link".into(),
+                    target: "/a".into(),
+                    broken: false,
+                },
+            ],
+        );
+        assert_eq!(
+            root.flatten_sorted(),
+            [
+                ("/a/1".into(), 1),
+                ("/a/2".into(), 2),
+                ("/a.link".into(), 0),
+                ("/b/x".into(), 3),
+                ("/b/y/deep".into(), 4),
+                ("/c".into(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_mounts_are_left_out() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .dir("/root/data")
+            .file("/root/data/x", 4)
+            .dir("/root/bind")
+            .file("/root/bind/x", 4);
+        let (options, files_found) = (ScanOptions::default(), AtomicU64::new(0));
+        let mut traversal = Traversal::new(&fs, &options, &files_found);
+        traversal.duplicate_mounts = vec!["/root/bind".into()];
+        assert_eq!(
+            traversal.traverse(Path::new("/root")),
+            Some(dir(
+                "/root",
+                vector![dir("/root/data", vector![file("/root/data/x", 4)])]
+            ))
+        );
+    }
+
+    /// Fails reading the metadata of `path` a number of times before passing it on to `fs`.
+    struct Flaky {
+        fs: MemoryFs,
+        path: PathBuf,
+        failures: AtomicU64,
+    }
+
+    impl MetadataProvider for Flaky {
+        fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+            self.fs.read_dir(path)
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<EntryMetadata> {
+            let failing = path == self.path
+                && self
+                    .failures
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    .is_ok();
+            if failing {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            self.fs.metadata(path)
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> std::io::Result<EntryMetadata> {
+            self.fs.symlink_metadata(path)
+        }
+
+        fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.fs.read_link(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.fs.canonicalize(path)
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let scan = |failures, retries| {
+            let fs = Flaky {
+                fs: MemoryFs::default()
+                    .dir("/root")
+                    .file("/root/flaky", 5)
+                    .failing("/root/denied", std::io::ErrorKind::PermissionDenied),
+                path: "/root/flaky".into(),
+                failures: AtomicU64::new(failures),
+            };
+            let options = ScanOptions {
+                retry: RetryPolicy {
+                    retries,
+                    backoff_ms: 0,
+                },
+                ..Default::default()
+            };
+            Traversal::new(&fs, &options, &AtomicU64::new(0))
+                .traverse(Path::new("/root"))
+                .unwrap()
+        };
+        let flaky = |size| FileNode::File {
+            path: "/root/flaky".into(),
+            size,
+        };
+        let denied = FileNode::File {
+            path: "/root/denied".into(),
+            size: None,
+        };
+
+        assert_eq!(
+            scan(2, 2),
+            dir("/root", vector![denied.clone(), flaky(Some(5))])
+        );
+        assert_eq!(scan(3, 2), dir("/root", vector![denied, flaky(None)]));
+
+        let attempts = AtomicU64::new(0);
+        let denied = RetryPolicy {
+            retries: 3,
+            backoff_ms: 0,
+        }
+        .run(|| -> std::io::Result<()> {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(std::io::ErrorKind::PermissionDenied.into())
+        });
+        assert!(denied.is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+
+        // Neither are errors not known to be transient
+        assert!(!is_transient(&std::io::ErrorKind::InvalidData.into()));
+        assert!(!is_transient(&std::io::Error::other("unknown")));
+        assert!(is_transient(&std::io::ErrorKind::ConnectionReset.into()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allocated_blocks_are_summed() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a"), vec![1u8; 10_000]).unwrap();
+        std::fs::write(dir.path().join("sub/b"), vec![1u8; 100]).unwrap();
+        let blocks: u64 = [".", "a", "sub", "sub/b"]
+            .into_iter()
+            .map(|path| std::fs::metadata(dir.path().join(path)).unwrap().blocks())
+            .sum();
+        let result = traverse(
+            dir.path(),
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(result.total_blocks, blocks);
+        assert!(blocks > 0);
+    }
+
+    /// Fails listing any directory, to tell a scan didn't list one.
+    struct Unlisted(MemoryFs);
+
+    impl MetadataProvider for Unlisted {
+        fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+            panic!("listed `{}`", path.display())
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<EntryMetadata> {
+            self.0.metadata(path)
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> std::io::Result<EntryMetadata> {
+            self.0.symlink_metadata(path)
+        }
+
+        fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.0.read_link(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+            self.0.canonicalize(path)
+        }
+    }
+
+    #[test]
+    fn refreshes_sizes_without_listing() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .dir("/root/sub")
+            .file("/root/sub/grows", 1)
+            .file("/root/sub/goes", 2)
+            .dir("/root/gone")
+            .file("/root/gone/a", 3)
+            .file("/root/gone/b", 4)
+            .symlink("/root/broken", "/nowhere")
+            .file("/root/same", 5);
+        let options = ScanOptions::default();
+        let files_found = AtomicU64::new(0);
+        let mut tree = Traversal::new(&fs, &options, &files_found)
+            .traverse(Path::new("/root"))
+            .unwrap();
+        assert_eq!(tree.size(), 15);
+
+        // Files added since aren't found, as no directory is listed again
+        let changed = Unlisted(
+            MemoryFs::default()
+                .dir("/root")
+                .dir("/root/sub")
+                .file("/root/sub/grows", 100)
+                .file("/root/sub/new", 1000)
+                .symlink("/root/broken", "/nowhere")
+                .file("/root/same", 5),
+        );
+        assert_eq!(refresh_sizes_in(&changed, &mut tree, SizeKind::Apparent), 3);
+        assert_eq!(
+            tree,
+            dir(
+                "/root",
+                vector![
+                    dir("/root/sub", vector![file("/root/sub/grows", 100)]),
+                    FileNode::Symlink {
+                        path: "/root/broken".into(),
+                        target: "/nowhere".into(),
+                        broken: true,
+                    },
+                    file("/root/same", 5)
+                ]
+            )
+        );
+        assert_eq!(tree.size(), 105);
+    }
+
+    #[test]
+    fn incomplete_scans_say_why() {
+        let result = |unreadable_directories, unknown_sizes, vanished| ScanResult {
+            root: file("/root", 1),
+            special_files: 3,
+            excluded_bytes: 100,
+            total_blocks: 0,
+            compressed_sizes: Default::default(),
+            archives: Default::default(),
+            unreadable_directories,
+            unknown_sizes,
+            vanished,
+        };
+        // Special and excluded files are left out on purpose
+        assert!(result(0, 0, 0).is_complete());
+        assert_eq!(result(0, 0, 0).incomplete_reasons(), Vec::<String>::new());
+
+        let incomplete = result(2, 0, 5);
+        assert!(!incomplete.is_complete());
+        assert_eq!(
+            incomplete.incomplete_reasons(),
+            [
+                "2 directories unreadable",
+                "5 files vanished while scanning"
+            ]
+        );
+        assert_eq!(
+            result(1, 4, 0).incomplete_reasons(),
+            ["1 directory unreadable", "4 files of unknown size"]
+        );
+
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .failing("/root/secret", std::io::ErrorKind::PermissionDenied)
+            .failing("/root/gone", std::io::ErrorKind::NotFound);
+        let options = ScanOptions::default();
+        let files_found = AtomicU64::new(0);
+        let traversal = Traversal::new(&fs, &options, &files_found);
+        let root = traversal.traverse(Path::new("/root"));
+        let scanned = traversal.finish(Path::new("/root"), root).unwrap();
+        assert_eq!(
+            scanned.incomplete_reasons(),
+            ["1 file of unknown size", "1 file vanished while scanning"]
+        );
+    }
+
+    #[test]
+    fn compressed_sizes_fall_back_to_blocks() {
+        let fs = MemoryFs::default()
+            .dir("/root")
+            .stored("/root/compressed", 10_000, 8, Some(1_500))
+            .stored("/root/allocated", 10_000, 24, None)
+            .file("/root/unknown", 700);
+        let scan = |size_kind| {
+            let options = ScanOptions {
+                size_kind,
+                ..Default::default()
+            };
+            let files_found = AtomicU64::new(0);
+            Traversal::new(&fs, &options, &files_found)
+                .traverse(Path::new("/root"))
+                .unwrap()
+                .into_vector()
+        };
+        assert_eq!(
+            scan(SizeKind::Apparent),
+            vector![
+                file("/root/allocated", 10_000),
+                file("/root/compressed", 10_000),
+                file("/root/unknown", 700)
+            ]
+        );
+        // Neither compressed nor allocated sizes are known for the last one
+        assert_eq!(
+            scan(SizeKind::Compressed),
+            vector![
+                file("/root/allocated", 24 * 512),
+                file("/root/compressed", 1_500),
+                file("/root/unknown", 700)
+            ]
+        );
+    }
 }
@@ -0,0 +1,257 @@
+use std::f64::consts::TAU;
+use std::path::PathBuf;
+
+use druid::kurbo::{Arc, BezPath, Circle};
+use druid::{Color, Data, Event, LifeCycle, MouseButton, Point, RenderContext, Size, Vec2, Widget};
+
+use crate::file_system::FileNode;
+use crate::AppState;
+
+/// Width and height of the pie in logical pixels
+const PIE_SIZE: f64 = 160.0;
+/// Color of the slice for the files directly in the shown folder
+const DIRECT_FILES_COLOR: Color = Color::grey8(0x80);
+
+/// One slice of the pie by folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FolderShare {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    /// The folder clicking the slice drills into, `None` for the direct files
+    pub(crate) directory: Option<PathBuf>,
+}
+
+/// Every direct subdirectory of `root` by its total size, largest first, and then all the
+/// files directly in it as one share, if there are any.
+pub(crate) fn folder_shares(root: &FileNode) -> Vec<FolderShare> {
+    let FileNode::Directory { children, .. } = root else {
+        return Vec::new();
+    };
+    let mut shares: Vec<_> = children
+        .iter()
+        .filter(|child| matches!(child, FileNode::Directory { .. }))
+        .map(|child| FolderShare {
+            name: child
+                .path()
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: child.size(),
+            directory: Some(child.path().clone()),
+        })
+        .collect();
+    shares.sort_by_key(|share| std::cmp::Reverse(share.size));
+    if children
+        .iter()
+        .any(|child| !matches!(child, FileNode::Directory { .. }))
+    {
+        shares.push(FolderShare {
+            name: String::from("direct files"),
+            size: root.direct_file_size(),
+            directory: None,
+        });
+    }
+    shares
+}
+
+/// The index of the share covering `fraction` of the way around the pie.
+fn share_at(shares: &[FolderShare], fraction: f64) -> Option<usize> {
+    let total: u64 = shares.iter().map(|share| share.size).sum();
+    let target = fraction * total as f64;
+    let mut end = 0;
+    shares.iter().position(|share| {
+        end += share.size;
+        share.size > 0 && target < end as f64
+    })
+}
+
+/// The shown folder as a pie with a slice per subdirectory, that clicking drills into.
+#[derive(Debug, Default)]
+pub(crate) struct FolderPie;
+
+impl FolderPie {
+    /// How far around the pie `point` is, starting at the top going clockwise, if it's on it.
+    fn fraction_at(size: Size, point: Point) -> Option<f64> {
+        let offset = point - size.to_rect().center();
+        if offset.hypot() > size.min_side() / 2.0 {
+            return None;
+        }
+        Some((offset.atan2() + TAU / 4.0).rem_euclid(TAU) / TAU)
+    }
+}
+
+impl Widget<AppState> for FolderPie {
+    fn event(
+        &mut self,
+        ctx: &mut druid::EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        _env: &druid::Env,
+    ) {
+        let Event::MouseDown(mouse) = event else {
+            return;
+        };
+        if mouse.button != MouseButton::Left {
+            return;
+        }
+        let Some(fraction) = Self::fraction_at(ctx.size(), mouse.pos) else {
+            return;
+        };
+        let shares = &data.folder_shares;
+        if let Some(directory) =
+            share_at(shares, fraction).and_then(|i| shares[i].directory.clone())
+        {
+            data.set_view_root(Some(directory));
+            data.select_only(None);
+            ctx.set_handled();
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &AppState,
+        data: &AppState,
+        _env: &druid::Env,
+    ) {
+        if !old_data.folder_shares.same(&data.folder_shares) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &druid::BoxConstraints,
+        _data: &AppState,
+        _env: &druid::Env,
+    ) -> Size {
+        bc.constrain(Size::new(PIE_SIZE, PIE_SIZE))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &AppState, _env: &druid::Env) {
+        let size = ctx.size();
+        let (center, radius) = (size.to_rect().center(), size.min_side() / 2.0);
+        let shares = &data.folder_shares;
+        let total: u64 = shares.iter().map(|share| share.size).sum();
+        if total == 0 {
+            ctx.fill(Circle::new(center, radius), &Color::grey(0.2));
+            return;
+        }
+        let mut start = -TAU / 4.0;
+        for share in shares.iter() {
+            let sweep = share.size as f64 / total as f64 * TAU;
+            let mut slice = BezPath::new();
+            slice.move_to(center);
+            slice.line_to(center + Vec2::from_angle(start) * radius);
+            let arc = Arc {
+                center,
+                radii: Vec2::new(radius, radius),
+                start_angle: start,
+                sweep_angle: sweep,
+                x_rotation: 0.0,
+            };
+            slice.extend(arc.append_iter(0.1));
+            slice.close_path();
+            let color = match share.directory {
+                Some(_) => data.palette.name_color(&share.name.to_lowercase()),
+                None => DIRECT_FILES_COLOR,
+            };
+            ctx.fill(&slice, &color);
+            start += sweep;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+    use druid::{Point, Size};
+
+    use super::{folder_shares, share_at, FolderPie, FolderShare};
+    use crate::file_system::FileNode;
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn shares_by_top_level_folder() {
+        let root = FileNode::Directory {
+            path: "/root".into(),
+            children: vector![
+                file("/root/1", 1),
+                FileNode::Directory {
+                    path: "/root/small".into(),
+                    children: vector![file("/root/small/2", 2)],
+                },
+                FileNode::Directory {
+                    path: "/root/big".into(),
+                    children: vector![
+                        file("/root/big/10", 10),
+                        FileNode::Directory {
+                            path: "/root/big/deep".into(),
+                            children: vector![file("/root/big/deep/20", 20)],
+                        },
+                    ],
+                },
+                file("/root/4", 4),
+            ],
+        };
+        let share = |name: &str, size, directory: Option<&str>| FolderShare {
+            name: name.into(),
+            size,
+            directory: directory.map(Into::into),
+        };
+        let shares = folder_shares(&root);
+        assert_eq!(
+            shares,
+            [
+                share("big", 30, Some("/root/big")),
+                share("small", 2, Some("/root/small")),
+                share("direct files", 5, None),
+            ]
+        );
+
+        assert_eq!(share_at(&shares, 0.0), Some(0));
+        assert_eq!(share_at(&shares, 0.8), Some(0));
+        assert_eq!(share_at(&shares, 0.82), Some(1));
+        assert_eq!(share_at(&shares, 0.99), Some(2));
+
+        // Without files directly in it there's no share for them
+        let FileNode::Directory { children, .. } = &root else {
+            unreachable!()
+        };
+        assert_eq!(
+            folder_shares(&children[2]),
+            [
+                share("deep", 20, Some("/root/big/deep")),
+                share("direct files", 10, None)
+            ]
+        );
+        assert_eq!(folder_shares(&children[1])[0].name, "direct files");
+        assert!(folder_shares(&file("/x", 1)).is_empty());
+    }
+
+    #[test]
+    fn fraction_goes_clockwise_from_the_top() {
+        let size = Size::new(100.0, 100.0);
+        let at = |x, y| FolderPie::fraction_at(size, Point::new(x, y));
+        assert_eq!(at(50.0, 10.0), Some(0.0));
+        assert_eq!(at(90.0, 50.0), Some(0.25));
+        assert_eq!(at(50.0, 90.0), Some(0.5));
+        assert_eq!(at(0.0, 0.0), None);
+    }
+}
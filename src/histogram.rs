@@ -0,0 +1,101 @@
+use crate::file_system::{bucket_of, FileNode};
+use crate::units::format_size;
+
+/// Boundaries between the buckets of the size histogram
+pub(crate) const SIZE_BUCKETS: [u64; 3] = [1024, 1024 * 1024, 100 * 1024 * 1024];
+/// Characters in the bar of the bucket with the most files
+const BAR_LENGTH: u64 = 20;
+
+/// The range of sizes in bucket `bucket` of [`SIZE_BUCKETS`], like `1.0 KiB – 1.0 MiB`.
+pub(crate) fn bucket_label(bucket: usize) -> String {
+    match (bucket.checked_sub(1), SIZE_BUCKETS.get(bucket)) {
+        (None, Some(upper)) => format!("< {}", format_size(*upper)),
+        (Some(lower), Some(upper)) => format!(
+            "{} – {}",
+            format_size(SIZE_BUCKETS[lower]),
+            format_size(*upper)
+        ),
+        (Some(lower), None) => format!("≥ {}", format_size(SIZE_BUCKETS[lower])),
+        (None, None) => String::from("All sizes"),
+    }
+}
+
+/// A bar of `count` out of `max` files.
+pub(crate) fn bar(count: u64, max: u64) -> String {
+    let length = if max > 0 {
+        (count * BAR_LENGTH).div_ceil(max)
+    } else {
+        0
+    };
+    "█".repeat(length as usize)
+}
+
+/// `root` without the files below it outside bucket `bucket` of [`SIZE_BUCKETS`]. Files of
+/// unknown size aren't in any bucket, as in [`FileNode::size_histogram`].
+pub(crate) fn in_size_bucket(root: &FileNode, bucket: usize) -> FileNode {
+    match root {
+        FileNode::Directory { path, children } => FileNode::Directory {
+            path: path.clone(),
+            children: children
+                .iter()
+                .filter(|child| match child {
+                    FileNode::File { size, .. } => {
+                        size.is_some_and(|size| bucket_of(&SIZE_BUCKETS, size) == bucket)
+                    }
+                    FileNode::Directory { .. }
+                    | FileNode::Symlink { .. }
+                    | FileNode::Aggregate { .. } => true,
+                })
+                .map(|child| in_size_bucket(child, bucket))
+                .collect(),
+        },
+        FileNode::File { .. } | FileNode::Symlink { .. } | FileNode::Aggregate { .. } => {
+            root.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+
+    use super::{bar, bucket_label, in_size_bucket};
+    use crate::file_system::FileNode;
+
+    #[test]
+    fn bucket_labels() {
+        assert_eq!(bucket_label(0), "< 1.0 KiB");
+        assert_eq!(bucket_label(1), "1.0 KiB – 1.0 MiB");
+        assert_eq!(bucket_label(3), "≥ 100.0 MiB");
+        assert_eq!(bar(1, 4), "█████");
+        assert_eq!(bar(0, 0), "");
+    }
+
+    #[test]
+    fn filtering_keeps_one_bucket() {
+        let file = |path: &str, size| FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        };
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                file("/small", 10),
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![file("/a/medium", 4096), file("/a/tiny", 1)],
+                },
+                FileNode::File {
+                    path: "/unknown".into(),
+                    size: None,
+                },
+            ],
+        };
+        assert_eq!(
+            in_size_bucket(&root, 1).into_vector(),
+            vector![file("/a/medium", 4096)]
+        );
+        assert_eq!(in_size_bucket(&root, 0).size(), 11);
+        assert_eq!(in_size_bucket(&root, 0).descendant_file_count(), 2);
+    }
+}
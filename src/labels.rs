@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+/// Marks where a label was cut short
+const ELLIPSIS: char = '…';
+/// Where a path is preferably wrapped, after the separator
+const SEPARATORS: [char; 2] = ['/', '\\'];
+
+/// The longest start of `text` of at most `max_chars` characters, 0 for any, that's no wider
+/// than `max_width` as told by `measure`, ending in an ellipsis if it's cut. `None` if not even
+/// the ellipsis fits.
+///
+/// Cuts between characters, never inside one.
+pub(crate) fn truncate_to_width(
+    text: &str,
+    max_chars: usize,
+    max_width: f64,
+    mut measure: impl FnMut(&str) -> f64,
+) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let cut = |n: usize| -> String {
+        if n >= chars.len() {
+            text.to_owned()
+        } else {
+            chars[..n].iter().chain([&ELLIPSIS]).collect()
+        }
+    };
+    let limit = match max_chars {
+        0 => chars.len(),
+        max_chars => max_chars.min(chars.len()),
+    };
+    if measure(&cut(limit)) <= max_width {
+        return Some(cut(limit));
+    }
+    // Searching the most characters that fit, with `limit` known to be too wide
+    let (mut low, mut high, mut longest) = (0, limit, None);
+    while low < high {
+        let n = (low + high) / 2;
+        if measure(&cut(n)) <= max_width {
+            longest = Some(n);
+            low = n + 1;
+        } else {
+            high = n;
+        }
+    }
+    longest.map(cut)
+}
+
+/// `text` broken into lines no wider than `max_width` as told by `measure`, after path
+/// separators where possible and between any characters where a part is too wide by itself.
+pub(crate) fn wrap_to_width(
+    text: &str,
+    max_width: f64,
+    mut measure: impl FnMut(&str) -> f64,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for part in text.split_inclusive(SEPARATORS) {
+        let joined = format!("{line}{part}");
+        if measure(&joined) <= max_width {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in part.chars() {
+            line.push(c);
+            // A single character too wide for a line still gets one
+            if line.chars().count() > 1 && measure(&line) > max_width {
+                line.pop();
+                lines.push(std::mem::take(&mut line));
+                line.push(c);
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `path` relative to `root`, to show paths under a deep scanned folder without repeating it.
+///
+/// Paths that aren't below `root`, and `root` itself, are kept whole.
+pub(crate) fn strip_root_prefix(path: &Path, root: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{strip_root_prefix, truncate_to_width, wrap_to_width};
+
+    /// Every character 10 wide
+    fn measure(text: &str) -> f64 {
+        text.chars().count() as f64 * 10.0
+    }
+
+    #[test]
+    fn truncates_to_fit_width() {
+        assert_eq!(
+            truncate_to_width("movie.mkv", 0, 90.0, measure).as_deref(),
+            Some("movie.mkv")
+        );
+        assert_eq!(
+            truncate_to_width("movie.mkv", 0, 89.0, measure).as_deref(),
+            Some("movie.m…")
+        );
+        assert_eq!(
+            truncate_to_width("movie.mkv", 4, 1000.0, measure).as_deref(),
+            Some("movi…")
+        );
+        assert_eq!(
+            truncate_to_width("ünïcødé", 0, 30.0, measure).as_deref(),
+            Some("ün…")
+        );
+        assert_eq!(
+            truncate_to_width("movie.mkv", 0, 10.0, measure).as_deref(),
+            Some("…")
+        );
+        assert_eq!(truncate_to_width("movie.mkv", 0, 9.0, measure), None);
+    }
+
+    #[test]
+    fn wraps_paths_after_separators() {
+        assert_eq!(
+            wrap_to_width("/home/user/file.txt", 120.0, measure),
+            ["/home/user/", "file.txt"]
+        );
+        assert_eq!(
+            wrap_to_width("/home/averyveryverylongname/x", 100.0, measure),
+            ["/home/", "averyveryv", "erylongnam", "e/x"]
+        );
+        assert_eq!(wrap_to_width("short", 100.0, measure), ["short"]);
+        assert_eq!(wrap_to_width("ab", 5.0, measure), ["a", "b"]);
+    }
+
+    #[test]
+    fn strips_the_scanned_root() {
+        let root = Path::new("/home/user/projects");
+        let strip = |path| strip_root_prefix(Path::new(path), root);
+        assert_eq!(strip("/home/user/projects/a/b.rs"), Path::new("a/b.rs"));
+        assert_eq!(strip("/home/user/projects/c"), Path::new("c"));
+        assert_eq!(strip("/home/user/projects"), root);
+        // Only whole components count, and paths elsewhere are shown in full
+        assert_eq!(
+            strip("/home/user/projects2/d"),
+            Path::new("/home/user/projects2/d")
+        );
+        assert_eq!(strip("/etc/hosts"), Path::new("/etc/hosts"));
+    }
+}
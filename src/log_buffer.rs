@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use druid::im::Vector;
+use druid::ExtEventSink;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// Most log lines kept for the log panel
+pub(crate) const LOG_CAPACITY: usize = 200;
+/// How often new log lines are handed to the app
+const FORWARD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Recent log lines, shared between the tracing layer recording them and the app showing them.
+///
+/// Once full, each new line pushes out the oldest one.
+#[derive(Debug, Clone)]
+pub(crate) struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+    /// Bumped on every recorded line, to tell when there's something new to forward
+    generation: Arc<AtomicU64>,
+}
+
+impl LogBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            generation: Default::default(),
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The recorded lines, oldest first.
+    pub(crate) fn lines(&self) -> Vector<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Keeps `AppState::log_lines` up to date from a background thread.
+    pub(crate) fn forward_to(&self, sink: ExtEventSink) {
+        let buffer = self.clone();
+        std::thread::spawn(move || {
+            let mut forwarded = 0;
+            loop {
+                std::thread::sleep(FORWARD_INTERVAL);
+                let generation = buffer.generation.load(Ordering::Relaxed);
+                if generation == forwarded {
+                    continue;
+                }
+                forwarded = generation;
+                let lines = buffer.lines();
+                sink.add_idle_callback(move |state: &mut crate::AppState| {
+                    state.log_lines = lines;
+                });
+            }
+        });
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogBuffer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = format!("{} {}:", metadata.level(), metadata.target());
+        event.record(&mut LineVisitor(&mut line));
+        self.push(line);
+    }
+}
+
+/// Appends the fields of an event to a log line, the message first without its name.
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::LogBuffer;
+
+    #[test]
+    fn events_land_in_bounded_buffer() {
+        let buffer = LogBuffer::new(3);
+        let subscriber = tracing_subscriber::registry().with(buffer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(files = 2, "Failed scanning");
+            assert_eq!(
+                buffer.lines(),
+                druid::im::vector![format!("WARN {}: Failed scanning files=2", module_path!())]
+            );
+
+            for i in 0..5 {
+                tracing::info!("Line {i}");
+            }
+        });
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("Line 2"));
+        assert!(lines[2].ends_with("Line 4"));
+    }
+}
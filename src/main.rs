@@ -1,20 +1,677 @@
 #![windows_subsystem = "windows"]
 
+#[cfg(feature = "archives")]
+mod archive;
+mod auto_rescan;
+mod bench;
+mod breadcrumbs;
+mod changes;
+mod checkpoint;
+mod cli;
+mod color;
+mod delete;
+mod export;
+mod extensions;
+mod file_list;
 mod file_system;
+mod folder_pie;
+mod histogram;
+mod labels;
+mod log_buffer;
+mod metadata;
+mod mounts;
+mod open;
+mod overview;
+mod path_case;
+mod path_list;
+mod persistence;
+mod progress_widget;
+mod report;
+mod settings;
+mod sparkline;
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
+mod treemap;
+mod units;
+mod viewport;
 mod visualization_widget;
+mod volume;
+mod volume_widget;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use druid::widget::{Button, CrossAxisAlignment, Flex, FlexParams, Label, TextBox};
-use druid::{AppLauncher, Data, Lens, UnitPoint, Widget, WidgetExt, WindowDesc};
-use file_system::{traverse_files_parallel, FileNode};
-use visualization_widget::VisualizationWidget;
+use auto_rescan::AutoRescan;
+use breadcrumbs::{breadcrumb_bar, breadcrumbs, Crumb};
+use changes::{ChangesNotice, ScanChanges};
+use color::{ColorMode, Gradient, HexColorFormatter, Palette, MIN_CONTRAST_RATIO};
+use delete::{DeleteMode, Deleter, SystemDeleter};
+use druid::commands::{SAVE_FILE_AS, SHOW_SAVE_PANEL};
+use druid::im::{HashMap, HashSet, Vector};
+use druid::text::{Formatter, ParseFormatter};
+use druid::widget::{
+    Button, Checkbox, CrossAxisAlignment, Either, Flex, FlexParams, Label, RadioGroup, Scroll,
+    SizedBox, TextBox, ViewSwitcher,
+};
+use druid::{
+    lens, AppDelegate, AppLauncher, Application, Color, Command, Data, DelegateCtx, Env,
+    ExtEventSink, FileDialogOptions, FileSpec, Handled, Lens, LensExt, Size, Target, UnitPoint,
+    Widget, WidgetExt, WindowDesc,
+};
+use export::{to_clipboard_text, write_csv, write_json};
+use file_list::{
+    largest_files, FileSort, ListedFile, SortKey, LARGEST_DIRECTORIES_COUNT, LARGEST_FILES_COUNT,
+};
+use file_system::{
+    refresh_sizes, resolve_input_path, traverse, BucketStat, FileNode, Metric, ScanError,
+    ScanOptions,
+};
+use folder_pie::{folder_shares, FolderPie, FolderShare};
+use histogram::{bucket_label, SIZE_BUCKETS};
+use labels::strip_root_prefix;
+use log_buffer::{LogBuffer, LOG_CAPACITY};
+use open::{Launcher, OpenAction, SystemLauncher};
+use overview::{filtered_tree, OverviewGranularity, ViewFilter, ViewOptions};
+use persistence::AppDirs;
+use progress_widget::ScanProgressBar;
+use report::render_tree_text;
+use settings::{settings_panel, Settings};
+use sparkline::Sparkline;
+use stats::{stats_markdown, TreeStats, QUERY_STATS};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use treemap::{TreemapAlgo, TreemapOrder};
+use units::{format_int, format_size, plural};
+use visualization_widget::{LayoutMode, SelectionStyle, VisualizationWidget};
+use volume::VolumeStats;
+use volume_widget::VolumeBar;
 
 #[derive(Debug, Clone, Lens)]
 struct AppState {
     folder: String,
+    /// The most recently selected file, one of `selected_files`
     selected_file: Option<FileNode>,
+    selected_files: Vector<FileNode>,
+    /// Whether the visualization draws `selected_file` as it is, which it notes here itself
+    selection_drawn: bool,
+    /// Whether the visualization folds the smallest nodes into one, as there are too many
+    nodes_folded: bool,
     all_files: Option<FileNode>,
+    /// The directory drilled into, the scanned folder if `None`
+    view_root: Option<PathBuf>,
+    /// The shown directory's subdirectories for the pie by folder, kept with the tree
+    folder_shares: Arc<[FolderShare]>,
+    /// The way from the scanned folder to the shown directory, kept with the tree
+    breadcrumbs: Vector<Crumb>,
+    /// Total size of the files with each extension, largest first
+    extensions: Vector<(String, u64)>,
+    /// Extensions left out of the visualization
+    hidden_extensions: HashSet<String>,
+    /// Files in each bucket of [`histogram::SIZE_BUCKETS`]
+    size_histogram: Vector<BucketStat>,
+    /// Only files in this bucket of the histogram are shown
+    size_bucket: Option<usize>,
+    show_histogram: bool,
+    /// The largest files of the last scan
+    largest_files: Vector<ListedFile>,
+    /// The directories with the most in them, kept with the tree
+    largest_directories: Vector<Crumb>,
+    /// The deepest files and empty directories with their depth, kept with the tree
+    deepest_paths: Vector<(PathBuf, usize)>,
+    /// Every symlink in the tree, kept with it
+    symlinks: Vector<FileNode>,
+    /// Summary numbers of the tree, kept with it
+    tree_stats: Option<TreeStats>,
+    file_sort: FileSort,
+    /// Space on the volume of the last scan
+    volume: Option<VolumeStats>,
+    view_filter: ViewFilter,
+    overview_granularity: OverviewGranularity,
+    /// Show the files directly in the shown directory as one node next to its subdirectories
+    group_root_files: bool,
+    metric: Metric,
+    layout_mode: LayoutMode,
+    treemap_algo: TreemapAlgo,
+    treemap_order: TreemapOrder,
+    /// Space around the children of top-level directories in a nested treemap
+    treemap_padding: f64,
+    selection_style: SelectionStyle,
+    /// Outline the selection in black or white rather than an inverted color
+    black_or_white_outlines: bool,
+    /// Contrast ratio an inverted outline color needs, below which black or white is used
+    min_outline_contrast: f64,
+    color_mode: ColorMode,
+    /// What names are colored from, and what the gradient is reset to
+    palette: Palette,
+    gradient: Gradient,
+    /// Most rectangles to draw, smaller nodes are folded into one
+    max_rects: usize,
+    /// How far in pixels a click may miss a rectangle and still select it
+    click_tolerance_px: f64,
+    /// Write the name of each file into its rectangle, cut to fit
+    show_labels: bool,
+    /// Most characters of a label, 0 for as many as fit
+    max_label_chars: usize,
+    /// Show the path of the hovered node
+    show_tooltips: bool,
+    /// Show paths relative to the scanned folder in lists and tooltips
+    relative_paths: bool,
+    /// Width in pixels tooltips wrap at
+    tooltip_width: f64,
+    /// Shade every rectangle like a cushion
+    cushioned: bool,
+    /// Outline directories in a treemap to group their files
+    directory_borders: bool,
+    /// How much directories drawn next to files are muted towards grey, 0.0 for not at all
+    directory_tint: f64,
+    /// Size in pixels of exported images
+    export_width: u32,
+    export_height: u32,
+    /// Rescan the folder this often, if set
+    auto_rescan_secs: Option<u64>,
+    /// Grow and shrink the files of a rescan from their old sizes instead of snapping to the new
+    animate_rescans: bool,
+    /// Subtrees with fewer bytes are collapsed in the text report
+    report_min_size: u64,
+    scan_options: ScanOptions,
+    /// Why the last scan failed
+    scan_error: Option<String>,
+    /// Special files found by the last scan, whether included or not
+    special_files: u64,
+    /// Asking whether to really delete the selected files
+    confirming_delete: bool,
+    delete_mode: DeleteMode,
+    /// Drop empty files from the tree after scanning
+    prune_empty_files: bool,
+    /// How many empty files were dropped from the last scan
+    pruned_files: u64,
+    /// Extensions of the only files to scan, separated by commas or spaces, empty for all
+    only_extensions: String,
+    /// Bytes the last scan left out because of their extension
+    excluded_bytes: u64,
+    /// 512 byte blocks the last scan found allocated, 0 where that isn't known
+    total_blocks: u64,
+    /// What the files in scanned archives take up in them, by path
+    compressed_sizes: HashMap<PathBuf, u64>,
+    /// The archives scanned into, so what's in them is only there virtually
+    archives: HashSet<PathBuf>,
+    scanning: bool,
+    files_scanned: u64,
+    /// What the last rescan found changed, shown for a while
+    scan_changes: Option<ScanChanges>,
+    /// Why the last scan's totals leave something out, empty if they don't
+    incomplete_reasons: Vector<String>,
+    /// Directories the last scan couldn't list, which refreshing sizes doesn't list either
+    unreadable_directories: u64,
+    /// Bumped by every scan, so results of a scan that got replaced are dropped
+    scan_generation: u64,
+    /// Set to stop the running scan
+    scan_cancel: Arc<AtomicBool>,
+    /// File counts of previous scans, by folder, to estimate progress of a rescan
+    scan_estimates: HashMap<String, u64>,
+    /// Recent log lines, oldest first
+    log_lines: Vector<String>,
+    show_log: bool,
+    show_legend: bool,
+    show_folder_pie: bool,
+    /// The settings being edited in the settings panel, `None` while it's closed
+    settings: Option<Settings>,
+    /// Groups the digits of counts and exact byte totals
+    thousands_separator: char,
+}
+
+impl AppState {
+    /// Applies the outcome of the scan started as `generation`, unless a newer scan replaced it.
+    /// Returns whether it was applied.
+    fn finish_scan(&mut self, generation: u64, outcome: ScanOutcome) -> bool {
+        if generation != self.scan_generation {
+            tracing::debug!("Dropping results of replaced scan of `{}`", outcome.folder);
+            return false;
+        }
+        let ScanOutcome {
+            files,
+            special_files,
+            scan_error,
+            pruned_files,
+            excluded_bytes,
+            total_blocks,
+            compressed_sizes,
+            archives,
+            incomplete_reasons,
+            unreadable_directories,
+            largest_files,
+            changes,
+            volume,
+            files_scanned,
+            folder,
+        } = outcome;
+        match &files {
+            Some(files) => tracing::debug!("Found these files: {:?}", files),
+            None => tracing::debug!("Found no files"),
+        }
+        self.extensions = files
+            .as_ref()
+            .map(extensions::extension_sizes)
+            .unwrap_or_default();
+        self.size_histogram = files
+            .as_ref()
+            .map(|files| files.size_histogram(&SIZE_BUCKETS).into())
+            .unwrap_or_default();
+        self.largest_directories = files.as_ref().map(largest_directories).unwrap_or_default();
+        self.deepest_paths = files
+            .as_ref()
+            .map(|files| files.deepest_paths(5).into())
+            .unwrap_or_default();
+        self.symlinks = files.as_ref().map(symlinks).unwrap_or_default();
+        self.scan_changes = changes;
+        self.all_files = files;
+        self.view_root = None;
+        self.special_files = special_files;
+        self.scan_error = scan_error;
+        self.pruned_files = pruned_files;
+        self.excluded_bytes = excluded_bytes;
+        self.total_blocks = total_blocks;
+        self.compressed_sizes = compressed_sizes;
+        self.archives = archives;
+        self.incomplete_reasons = incomplete_reasons;
+        self.unreadable_directories = unreadable_directories;
+        self.largest_files = largest_files;
+        self.volume = volume;
+        self.scanning = false;
+        self.files_scanned = files_scanned;
+        self.scan_estimates.insert(folder, files_scanned);
+        self.tree_stats = self.stats();
+        self.refresh_shown();
+        true
+    }
+
+    /// Replaces the tree with `files`, the same tree with refreshed sizes, keeping what else the
+    /// scan found. `vanished` files were gone, `unknown_sizes` files couldn't be sized and
+    /// `changes` are what the refresh changed.
+    fn finish_refresh(
+        &mut self,
+        generation: u64,
+        files: FileNode,
+        vanished: u64,
+        unknown_sizes: u64,
+        changes: Option<ScanChanges>,
+    ) -> bool {
+        let outcome = ScanOutcome {
+            largest_files: largest_files(&files, LARGEST_FILES_COUNT),
+            changes,
+            files: Some(files),
+            special_files: self.special_files,
+            scan_error: None,
+            pruned_files: self.pruned_files,
+            excluded_bytes: self.excluded_bytes,
+            // The refreshed sizes leave the old block count behind.
+            total_blocks: 0,
+            compressed_sizes: self.compressed_sizes.clone(),
+            archives: self.archives.clone(),
+            incomplete_reasons: file_system::incomplete_reasons(
+                self.unreadable_directories,
+                unknown_sizes,
+                vanished,
+            )
+            .into(),
+            unreadable_directories: self.unreadable_directories,
+            volume: self.volume,
+            files_scanned: self.files_scanned,
+            folder: self.folder.clone(),
+        };
+        // The directories are the same, so the one drilled into is still there
+        let view_root = self.view_root.take();
+        let finished = self.finish_scan(generation, outcome);
+        if finished {
+            self.set_view_root(view_root);
+        }
+        finished
+    }
+
+    /// Fraction of the running scan that's done, if the folder was scanned before.
+    pub(crate) fn scan_fraction(&self) -> Option<f64> {
+        let estimate = *self.scan_estimates.get(&self.folder)?;
+        (estimate > 0).then(|| (self.files_scanned as f64 / estimate as f64).min(1.0))
+    }
+
+    /// Selects just `file`, or nothing. Stand-ins for several files aren't selected.
+    pub(crate) fn select_only(&mut self, file: Option<FileNode>) {
+        let file = file.filter(FileNode::is_selectable);
+        self.selected_files = file.iter().cloned().collect();
+        self.selected_file = file;
+    }
+
+    /// Adds `file` to the selection, or removes it if it's already selected. Stand-ins for
+    /// several files are left alone.
+    pub(crate) fn toggle_selected(&mut self, file: FileNode) {
+        if !file.is_selectable() {
+            return;
+        }
+        match self.selected_files.index_of(&file) {
+            Some(index) => {
+                self.selected_files.remove(index);
+                if self.selected_file.as_ref() == Some(&file) {
+                    self.selected_file = self.selected_files.last().cloned();
+                }
+            }
+            None => {
+                self.selected_files.push_back(file.clone());
+                self.selected_file = Some(file);
+            }
+        }
+    }
+
+    /// What each visual encoding that's currently enabled means, for the key.
+    fn legend_entries(&self) -> Vec<String> {
+        let mut entries = vec![match self.color_mode {
+            ColorMode::Position => format!(
+                "Color: position, from {} to {}",
+                HexColorFormatter.format(&self.gradient.start),
+                HexColorFormatter.format(&self.gradient.end)
+            ),
+            ColorMode::ByNameHash => String::from("Color: extension, or name for folders"),
+        }];
+        if self.selection_style.outlines() {
+            let color = if self.black_or_white_outlines {
+                "black or white"
+            } else {
+                "a contrasting color"
+            };
+            entries.push(format!("Outlined in {color}: selected"));
+        }
+        if self.selection_style.dims_others() {
+            entries.push(String::from("Darkened: not selected"));
+        }
+        if self.cushioned {
+            entries.push(String::from("Shading: one cushion per rectangle"));
+        }
+        if self.directory_borders && self.layout_mode == LayoutMode::Treemap {
+            entries.push(String::from("Thin colored borders: folders"));
+        }
+        if self.directory_tint > 0.0 {
+            entries.push(String::from("Muted colors: folders"));
+        }
+        if self.group_root_files {
+            entries.push(String::from(
+                "\"N files\": files directly in the shown folder",
+            ));
+        }
+        if self.scan_options.min_expand_size > 0 {
+            entries.push(format!(
+                "\"N items\" in a folder: folded, under {}",
+                format_size(self.scan_options.min_expand_size)
+            ));
+        }
+        if let Some(bucket) = self.size_bucket {
+            entries.push(format!("Only files of {}", bucket_label(bucket)));
+        }
+        if self.nodes_folded {
+            entries.push(String::from("\"N others\": too small to draw on their own"));
+        }
+        entries
+    }
+
+    /// Combined size of every selected file.
+    fn selected_size(&self) -> u64 {
+        self.selected_files.iter().map(FileNode::size).sum()
+    }
+
+    /// The selected files, directories and symlinks that are on disk, leaving out stand-ins.
+    fn selected_on_disk(&self) -> impl Iterator<Item = &FileNode> {
+        self.selected_files
+            .iter()
+            .filter(|file| file.is_selectable())
+    }
+
+    /// `n` with its digits grouped by the chosen separator.
+    fn format_count(&self, n: u64) -> String {
+        format_int(n, self.thousands_separator)
+    }
+
+    /// What's selected, for the label next to the folder. A selection that filters changed to
+    /// no longer be drawn stays selected, but is marked as hidden.
+    fn selection_summary(&self) -> String {
+        if self.selected_files.len() > 1 {
+            return format!(
+                "{} files selected, {} B",
+                self.format_count(self.selected_files.len() as u64),
+                self.format_count(self.selected_size())
+            );
+        }
+        match &self.selected_file {
+            Some(file) if !self.selection_drawn => {
+                format!(
+                    "{} (hidden by the current view)",
+                    self.display_path(file.path())
+                )
+            }
+            Some(file) => self.display_path(file.path()),
+            None => String::default(),
+        }
+    }
+
+    /// `path` as lists and tooltips show it, relative to the scanned folder if that's chosen.
+    pub(crate) fn display_path(&self, path: &Path) -> String {
+        match &self.all_files {
+            Some(root) if self.relative_paths => strip_root_prefix(path, root.path()),
+            _ => path.to_path_buf(),
+        }
+        .display()
+        .to_string()
+    }
+
+    /// Deletes the selected files from disk and from the scanned tree, without a rescan.
+    fn delete_selected(&mut self) {
+        self.delete_selected_with(&SystemDeleter)
+    }
+
+    /// Deletes the selected files through `deleter` as the delete mode says.
+    ///
+    /// Files that fail to delete are logged and stay. The tree is updated the same whether
+    /// they went to the trash or not.
+    fn delete_selected_with(&mut self, deleter: &impl Deleter) {
+        self.confirming_delete = false;
+        let Some(root) = &mut self.all_files else {
+            return;
+        };
+        for file in self
+            .selected_files
+            .iter()
+            .filter(|file| file.is_selectable())
+        {
+            let path = file.path();
+            if let Err(err) = self.delete_mode.delete(file, deleter) {
+                tracing::error!("Failed deleting `{}`: {err}", path.display());
+                continue;
+            }
+            // Blocks were only counted for the whole tree, so what's left of
+            // them isn't known until the next scan.
+            self.total_blocks = 0;
+            match root.remove_path(path) {
+                Some(size) => tracing::info!("Deleted `{}`, freeing {size} B", path.display()),
+                None => tracing::warn!("Deleted `{}`, which wasn't in the tree", path.display()),
+            }
+            self.largest_files
+                .retain(|listed| !listed.path.starts_with(path));
+        }
+        self.extensions = extensions::extension_sizes(root);
+        self.size_histogram = root.size_histogram(&SIZE_BUCKETS).into();
+        self.largest_directories = largest_directories(root);
+        self.deepest_paths = root.deepest_paths(5).into();
+        self.symlinks = symlinks(root);
+        if self
+            .view_root
+            .as_ref()
+            .is_some_and(|view_root| root.find(view_root).is_none())
+        {
+            self.view_root = None;
+        }
+        self.tree_stats = self.stats();
+        self.refresh_shown();
+        self.select_only(None);
+    }
+
+    /// Opens every selected file as `action` says through `launcher`, each containing folder
+    /// only once.
+    fn open_selected(&self, action: OpenAction, launcher: &impl Launcher) {
+        let mut opened = Vec::new();
+        for file in self.selected_on_disk() {
+            let (program, args) = action.command(file.path());
+            if opened.contains(&(program, args.clone())) {
+                continue;
+            }
+            action.open(file.path(), launcher);
+            opened.push((program, args));
+        }
+    }
+
+    /// Whether any selected file is inside a scanned archive, so only there virtually.
+    fn selection_in_archive(&self) -> bool {
+        self.selected_files.iter().any(|file| {
+            file.path()
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| self.archives.contains(ancestor))
+        })
+    }
+
+    /// The options the overview is shown with.
+    pub(crate) fn view_options(&self) -> ViewOptions {
+        ViewOptions {
+            view_filter: self.view_filter,
+            granularity: self.overview_granularity,
+            group_root_files: self.group_root_files,
+            metric: self.metric,
+            max_rects: self.max_rects,
+            hidden_extensions: self.hidden_extensions.clone(),
+            size_bucket: self.size_bucket,
+        }
+    }
+
+    /// The directory the visualization shows.
+    pub(crate) fn shown_root(&self) -> Option<&FileNode> {
+        let root = self.all_files.as_ref()?;
+        match &self.view_root {
+            Some(path) => root.find(path).or(Some(root)),
+            None => Some(root),
+        }
+    }
+
+    /// Drills into `directory`, or back out to the scanned folder if `None`.
+    pub(crate) fn set_view_root(&mut self, directory: Option<PathBuf>) {
+        self.view_root = directory;
+        self.refresh_shown();
+    }
+
+    /// Recomputes what's kept about the shown directory, after it or the tree changed.
+    fn refresh_shown(&mut self) {
+        self.folder_shares = self
+            .shown_root()
+            .map(|root| folder_shares(root).into())
+            .unwrap_or_default();
+        self.breadcrumbs = self
+            .all_files
+            .as_ref()
+            .map(|files| breadcrumbs(files, self.view_root.as_deref()).into())
+            .unwrap_or_default();
+    }
+
+    /// Goes back to the parent of the directory drilled into.
+    fn drill_up(&mut self) {
+        let (Some(root), Some(view_root)) = (&self.all_files, &self.view_root) else {
+            return;
+        };
+        let parent = view_root
+            .parent()
+            .filter(|parent| parent.starts_with(root.path()) && *parent != root.path())
+            .map(Path::to_path_buf);
+        self.set_view_root(parent);
+        self.select_only(None);
+    }
+
+    /// Size of the files with extensions that aren't hidden.
+    fn visible_size(&self) -> u64 {
+        self.extensions
+            .iter()
+            .filter(|(extension, _)| !self.hidden_extensions.contains(extension))
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    /// The summary numbers of the tree, counted anew.
+    fn stats(&self) -> Option<TreeStats> {
+        let stats = TreeStats::of(self.all_files.as_ref()?);
+        Some(TreeStats {
+            total_blocks: self.total_blocks,
+            ..stats
+        })
+    }
+
+    /// Title of the window, naming the scanned folder and its size.
+    fn window_title(&self) -> String {
+        // The title is built on every update, so the size is the one kept with the tree
+        match (&self.all_files, self.tree_stats) {
+            (Some(root), Some(stats)) => format!(
+                "rs-dir-stat — {} ({})",
+                root.path().display(),
+                format_size(stats.total_size)
+            ),
+            _ => String::from("rs-dir-stat"),
+        }
+    }
+
+    /// The default state with what was saved by previous runs.
+    fn load() -> Self {
+        let mut state = Self::default();
+        let Some(dirs) = AppDirs::new() else {
+            return state;
+        };
+        match dirs.last_folder() {
+            Ok(Some(folder)) => state.folder = folder,
+            Ok(None) => {}
+            Err(err) => tracing::warn!("Failed loading last folder: {err}"),
+        }
+        match dirs.scan_estimates() {
+            Ok(estimates) => state.scan_estimates = estimates,
+            Err(err) => tracing::warn!("Failed loading scan estimates: {err}"),
+        }
+        state
+    }
+
+    /// Every file shown, in the directory drilled into and left in by the filters, by path.
+    pub(crate) fn visible_files(&self) -> Vec<(PathBuf, u64)> {
+        let Some(root) = self.shown_root() else {
+            return Vec::new();
+        };
+        filtered_tree(root, &self.view_options())
+            .as_ref()
+            .unwrap_or(root)
+            .flatten_sorted()
+    }
+
+    /// Sets the options to the settings edited in the settings panel.
+    fn apply_settings(&mut self) {
+        if let Some(settings) = self.settings.clone() {
+            settings.apply_to(self);
+        }
+    }
+
+    /// Saves what should be remembered by the next run.
+    fn save(&self, scanned_folder: &str) {
+        let Some(dirs) = AppDirs::new() else {
+            return;
+        };
+        if let Err(err) = dirs.save_last_folder(scanned_folder) {
+            tracing::warn!("Failed saving last folder: {err}");
+        }
+        if let Err(err) = dirs.save_scan_estimates(&self.scan_estimates) {
+            tracing::warn!("Failed saving scan estimates: {err}");
+        }
+    }
 }
 
 impl Default for AppState {
@@ -22,7 +679,80 @@ impl Default for AppState {
         Self {
             folder: "/home/toby/repos/chris/public".into(),
             selected_file: Default::default(),
+            selected_files: Default::default(),
+            selection_drawn: true,
+            nodes_folded: false,
             all_files: Default::default(),
+            view_root: None,
+            folder_shares: Default::default(),
+            breadcrumbs: Vector::new(),
+            extensions: Default::default(),
+            hidden_extensions: Default::default(),
+            size_histogram: Default::default(),
+            size_bucket: None,
+            show_histogram: false,
+            largest_files: Default::default(),
+            largest_directories: Vector::new(),
+            deepest_paths: Vector::new(),
+            symlinks: Vector::new(),
+            tree_stats: None,
+            file_sort: Default::default(),
+            volume: Default::default(),
+            view_filter: Default::default(),
+            overview_granularity: Default::default(),
+            group_root_files: false,
+            metric: Default::default(),
+            layout_mode: Default::default(),
+            treemap_algo: Default::default(),
+            treemap_order: Default::default(),
+            treemap_padding: 0.0,
+            selection_style: Default::default(),
+            black_or_white_outlines: false,
+            min_outline_contrast: MIN_CONTRAST_RATIO,
+            color_mode: Default::default(),
+            palette: Default::default(),
+            gradient: Default::default(),
+            max_rects: 10_000,
+            click_tolerance_px: 4.0,
+            show_labels: false,
+            max_label_chars: 40,
+            show_tooltips: false,
+            tooltip_width: 300.0,
+            relative_paths: false,
+            cushioned: false,
+            directory_borders: false,
+            directory_tint: 0.0,
+            export_width: 1920,
+            export_height: 1080,
+            auto_rescan_secs: None,
+            animate_rescans: false,
+            report_min_size: 0,
+            scan_options: Default::default(),
+            scan_error: None,
+            special_files: 0,
+            confirming_delete: false,
+            delete_mode: DeleteMode::default(),
+            prune_empty_files: false,
+            pruned_files: 0,
+            only_extensions: String::new(),
+            excluded_bytes: 0,
+            total_blocks: 0,
+            compressed_sizes: Default::default(),
+            archives: Default::default(),
+            scanning: false,
+            files_scanned: 0,
+            scan_changes: None,
+            incomplete_reasons: Vector::new(),
+            unreadable_directories: 0,
+            scan_generation: 0,
+            scan_cancel: Default::default(),
+            scan_estimates: Default::default(),
+            log_lines: Default::default(),
+            show_log: false,
+            show_legend: false,
+            show_folder_pie: false,
+            settings: None,
+            thousands_separator: ',',
         }
     }
 }
@@ -31,7 +761,110 @@ impl Data for AppState {
     fn same(&self, other: &Self) -> bool {
         self.folder == other.folder
             && self.selected_file == other.selected_file
+            && self.selected_files == other.selected_files
+            && self.selection_drawn == other.selection_drawn
+            && self.nodes_folded == other.nodes_folded
             && self.all_files == other.all_files
+            && self.view_root == other.view_root
+            && Arc::ptr_eq(&self.folder_shares, &other.folder_shares)
+            && self.breadcrumbs == other.breadcrumbs
+            && self.extensions == other.extensions
+            && self.hidden_extensions == other.hidden_extensions
+            && self.size_histogram == other.size_histogram
+            && self.size_bucket == other.size_bucket
+            && self.show_histogram == other.show_histogram
+            && self.largest_files == other.largest_files
+            && self.largest_directories == other.largest_directories
+            && self.deepest_paths == other.deepest_paths
+            && self.symlinks == other.symlinks
+            && self.tree_stats == other.tree_stats
+            && self.file_sort == other.file_sort
+            && self.volume == other.volume
+            && self.view_filter == other.view_filter
+            && self.overview_granularity == other.overview_granularity
+            && self.group_root_files == other.group_root_files
+            && self.metric == other.metric
+            && self.layout_mode == other.layout_mode
+            && self.treemap_algo == other.treemap_algo
+            && self.treemap_order == other.treemap_order
+            && self.treemap_padding == other.treemap_padding
+            && self.selection_style == other.selection_style
+            && self.black_or_white_outlines == other.black_or_white_outlines
+            && self.min_outline_contrast == other.min_outline_contrast
+            && self.color_mode == other.color_mode
+            && self.palette == other.palette
+            && self.gradient.same(&other.gradient)
+            && self.max_rects == other.max_rects
+            && self.click_tolerance_px == other.click_tolerance_px
+            && self.show_labels == other.show_labels
+            && self.max_label_chars == other.max_label_chars
+            && self.show_tooltips == other.show_tooltips
+            && self.relative_paths == other.relative_paths
+            && self.tooltip_width == other.tooltip_width
+            && self.cushioned == other.cushioned
+            && self.directory_borders == other.directory_borders
+            && self.directory_tint == other.directory_tint
+            && self.export_width == other.export_width
+            && self.export_height == other.export_height
+            && self.auto_rescan_secs == other.auto_rescan_secs
+            && self.animate_rescans == other.animate_rescans
+            && self.report_min_size == other.report_min_size
+            && self.scan_options == other.scan_options
+            && self.scan_error == other.scan_error
+            && self.special_files == other.special_files
+            && self.confirming_delete == other.confirming_delete
+            && self.delete_mode == other.delete_mode
+            && self.prune_empty_files == other.prune_empty_files
+            && self.pruned_files == other.pruned_files
+            && self.only_extensions == other.only_extensions
+            && self.excluded_bytes == other.excluded_bytes
+            && self.total_blocks == other.total_blocks
+            && self.compressed_sizes == other.compressed_sizes
+            && self.archives == other.archives
+            && self.scanning == other.scanning
+            && self.files_scanned == other.files_scanned
+            && self.scan_changes == other.scan_changes
+            && self.incomplete_reasons == other.incomplete_reasons
+            && self.unreadable_directories == other.unreadable_directories
+            && self.scan_generation == other.scan_generation
+            && Arc::ptr_eq(&self.scan_cancel, &other.scan_cancel)
+            && self.scan_estimates == other.scan_estimates
+            && self.log_lines == other.log_lines
+            && self.show_log == other.show_log
+            && self.show_legend == other.show_legend
+            && self.show_folder_pie == other.show_folder_pie
+            && self.settings.same(&other.settings)
+            && self.thousands_separator == other.thousands_separator
+    }
+}
+
+struct Delegate;
+
+impl AppDelegate<AppState> for Delegate {
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Handled {
+        if cmd.is(QUERY_STATS) {
+            match data.tree_stats {
+                Some(stats) => tracing::info!("Stats of `{}`: {:?}", data.folder, stats),
+                None => tracing::info!("No stats, nothing scanned yet"),
+            }
+            return Handled::Yes;
+        }
+        if let Some(file_info) = cmd.get(SAVE_FILE_AS) {
+            let size = Size::new(data.export_width.into(), data.export_height.into());
+            match export::export_png(data, size, file_info.path()) {
+                Ok(()) => tracing::info!("Exported `{}`", file_info.path().display()),
+                Err(err) => tracing::error!("Failed exporting PNG: {err}"),
+            }
+            return Handled::Yes;
+        }
+        Handled::No
     }
 }
 
@@ -45,39 +878,1330 @@ fn main_widget() -> impl Widget<AppState> {
                         .lens(AppState::folder)
                         .fix_width(200.0),
                 )
+                .with_child(Sparkline.fix_width(120.0))
                 .with_flex_child(
-                    Label::dynamic(|state: &AppState, _env| match &state.selected_file {
-                        Some(file) => file.path().display().to_string(),
-                        None => String::default(),
-                    })
-                    .expand_width(),
+                    Label::dynamic(|state: &AppState, _env| state.selection_summary())
+                        .expand_width(),
                     1.0,
                 )
+                .with_child(Either::new(
+                    |state: &AppState, _env| state.confirming_delete,
+                    Flex::row()
+                        .with_child(Label::dynamic(|state: &AppState, _env| {
+                            // Folders go with everything in them, so these aren't all files
+                            let count = state.selected_on_disk().count() as u64;
+                            let items = format!(
+                                "{} {}",
+                                state.format_count(count),
+                                plural(count, "item", "items")
+                            );
+                            let size = format_size(state.selected_size());
+                            match state.delete_mode {
+                                DeleteMode::Trash => {
+                                    format!("Move {items} ({size}) to the trash?")
+                                }
+                                DeleteMode::Permanent => {
+                                    format!("Delete {items} ({size}) from disk for good?")
+                                }
+                            }
+                        }))
+                        .with_child(
+                            RadioGroup::row(vec![
+                                ("To the trash", DeleteMode::Trash),
+                                ("Permanently", DeleteMode::Permanent),
+                            ])
+                            .lens(AppState::delete_mode),
+                        )
+                        .with_child(
+                            Button::new("Delete").on_click(|_ctx, state: &mut AppState, _env| {
+                                state.delete_selected()
+                            }),
+                        )
+                        .with_child(Button::new("Cancel").on_click(
+                            |_ctx, state: &mut AppState, _env| state.confirming_delete = false,
+                        )),
+                    Button::new("Delete selected")
+                        .on_click(|_ctx, state: &mut AppState, _env| state.confirming_delete = true)
+                        .disabled_if(|state: &AppState, _env| {
+                            state.selected_on_disk().next().is_none()
+                                || state.scanning
+                                || state.selection_in_archive()
+                        }),
+                ))
                 .with_child(
-                    Button::new("Traverse folder")
+                    Button::new("Open file")
                         .on_click(|_ctx, state: &mut AppState, _env| {
-                            tracing::debug!("Clicky clicky! {}", &state.folder);
-                            state.all_files =
-                                traverse_files_parallel(&PathBuf::from(&state.folder));
-                            match &state.all_files {
-                                Some(files) => tracing::debug!("Found these files: {:?}", files),
-                                None => tracing::debug!("Found no files"),
-                            }
+                            state.open_selected(OpenAction::File, &SystemLauncher)
+                        })
+                        .disabled_if(|state: &AppState, _env| {
+                            state.selected_on_disk().next().is_none()
+                                || state.selection_in_archive()
+                        }),
+                )
+                .with_child(
+                    Button::new("Open containing folder")
+                        .on_click(|_ctx, state: &mut AppState, _env| {
+                            state.open_selected(OpenAction::ContainingFolder, &SystemLauncher)
+                        })
+                        .disabled_if(|state: &AppState, _env| {
+                            state.selected_on_disk().next().is_none()
+                                || state.selection_in_archive()
+                        }),
+                )
+                .with_child(
+                    Button::new("Up")
+                        .on_click(|_ctx, state: &mut AppState, _env| state.drill_up())
+                        .disabled_if(|state: &AppState, _env| state.view_root.is_none()),
+                )
+                .with_child(
+                    Button::dynamic(|state: &AppState, _env| {
+                        if state.scanning {
+                            String::from("Restart scan")
+                        } else {
+                            String::from("Traverse folder")
+                        }
+                    })
+                    .on_click(|ctx, state: &mut AppState, _env| {
+                        tracing::debug!("Clicky clicky! {}", &state.folder);
+                        start_scan(ctx.get_external_handle(), state);
+                    })
+                    .disabled_if(|state: &AppState, _env| folder_error(&state.folder).is_some())
+                    .align_horizontal(UnitPoint::LEFT),
+                )
+                .with_child(
+                    Button::new("Refresh sizes")
+                        .on_click(|ctx, state: &mut AppState, _env| {
+                            start_refresh(ctx.get_external_handle(), state)
                         })
-                        .align_horizontal(UnitPoint::LEFT),
+                        .disabled_if(|state: &AppState, _env| {
+                            state.all_files.is_none() || state.scanning
+                        }),
                 ),
         )
+        .with_child(breadcrumb_bar())
+        .with_child(
+            Flex::row()
+                .with_flex_child(ScanProgressBar::default(), 1.0)
+                .with_child(Label::dynamic(|state: &AppState, _env| {
+                    if state.scanning {
+                        return format!(
+                            "Scanned {} files",
+                            state.format_count(state.files_scanned)
+                        );
+                    }
+                    if let Some(err) = &state.scan_error {
+                        return err.clone();
+                    }
+                    let Some(stats) = state.tree_stats else {
+                        return String::default();
+                    };
+                    let count = |n| state.format_count(n);
+                    let mut summary = format!(
+                        "{} B in {} files and {} directories",
+                        count(stats.total_size),
+                        count(stats.file_count),
+                        count(stats.directory_count)
+                    );
+                    if stats.unknown_size_count > 0 {
+                        summary.push_str(&format!(
+                            ", {} files with unknown size",
+                            count(stats.unknown_size_count)
+                        ));
+                    }
+                    if state.special_files > 0 {
+                        summary
+                            .push_str(&format!(", {} special files", count(state.special_files)));
+                    }
+                    if state.pruned_files > 0 {
+                        summary.push_str(&format!(
+                            ", {} empty files pruned",
+                            count(state.pruned_files)
+                        ));
+                    }
+                    if state.excluded_bytes > 0 {
+                        summary.push_str(&format!(
+                            ", {} B in files of other extensions",
+                            count(state.excluded_bytes)
+                        ));
+                    }
+                    if stats.total_blocks > 0 {
+                        summary.push_str(&format!(
+                            ", {} blocks ({}) allocated",
+                            count(stats.total_blocks),
+                            format_size(stats.total_blocks * 512)
+                        ));
+                        // Inodes are only estimated where the scan counted blocks.
+                        summary
+                            .push_str(&format!(", about {} inodes", count(stats.inode_estimate)));
+                    }
+                    summary
+                }))
+                .with_child(
+                    Label::dynamic(|state: &AppState, _env| {
+                        state
+                            .scan_changes
+                            .map(|changes| changes.summary(state.thousands_separator))
+                            .unwrap_or_default()
+                    })
+                    .controller(ChangesNotice::default()),
+                ),
+        )
+        .with_child(Either::new(
+            |state: &AppState, _env| state.incomplete_reasons.is_empty() || state.scanning,
+            SizedBox::empty(),
+            Label::dynamic(|state: &AppState, _env| {
+                let reasons = state.incomplete_reasons.iter().cloned().collect::<Vec<_>>();
+                format!("⚠ Results are incomplete: {}", reasons.join(", "))
+            })
+            // Amber, to stand out from the rest of the status
+            .with_text_color(Color::rgb8(0xff, 0xb0, 0x00))
+            .align_left(),
+        ))
+        .with_child(
+            Flex::row()
+                .with_flex_child(VolumeBar, 1.0)
+                .with_child(Label::dynamic(|state: &AppState, _env| {
+                    let (Some(volume), Some(stats)) = (state.volume, state.tree_stats) else {
+                        return String::default();
+                    };
+                    format!(
+                        "{} B of {} B used, {} B free, {:.1}% scanned",
+                        state.format_count(volume.used()),
+                        state.format_count(volume.total),
+                        state.format_count(volume.free),
+                        volume.fraction_of(stats.total_size) * 100.0
+                    )
+                })),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(
+                    RadioGroup::row(vec![
+                        ("All", ViewFilter::All),
+                        ("Files", ViewFilter::Files),
+                        ("Directories", ViewFilter::Directories),
+                    ])
+                    .lens(AppState::view_filter)
+                    .disabled_if(|state: &AppState, _env| {
+                        state.overview_granularity != OverviewGranularity::Files
+                    }),
+                )
+                .with_child(
+                    RadioGroup::row(vec![
+                        ("Detailed", OverviewGranularity::Files),
+                        ("Top-level folders", OverviewGranularity::TopLevelDirs),
+                    ])
+                    .lens(AppState::overview_granularity),
+                )
+                .with_child(
+                    Checkbox::new("Group direct files")
+                        .lens(AppState::group_root_files)
+                        .disabled_if(|state: &AppState, _env| {
+                            state.overview_granularity == OverviewGranularity::Files
+                                && state.view_filter != ViewFilter::All
+                        }),
+                )
+                .with_child(
+                    RadioGroup::row(vec![("Bytes", Metric::Bytes), ("Files", Metric::FileCount)])
+                        .lens(AppState::metric),
+                )
+                .with_child(
+                    RadioGroup::row(vec![
+                        ("Bar", LayoutMode::Bar),
+                        ("Treemap", LayoutMode::Treemap),
+                        ("Rows", LayoutMode::Rows),
+                    ])
+                    .lens(AppState::layout_mode),
+                )
+                .with_child(Button::new("Settings").on_click(
+                    |_ctx, state: &mut AppState, _env| {
+                        state.settings = match state.settings {
+                            Some(_) => None,
+                            None => Some(Settings::of(state)),
+                        }
+                    },
+                )),
+        )
+        .with_child(settings_panel())
         .with_flex_child(
-            VisualizationWidget::default(),
+            Flex::row()
+                .with_flex_child(
+                    VisualizationWidget::default(),
+                    FlexParams::new(1.0, CrossAxisAlignment::Fill),
+                )
+                .with_child(extension_legend())
+                .cross_axis_alignment(CrossAxisAlignment::Start),
             FlexParams::new(1.0, CrossAxisAlignment::Fill),
         )
+        .with_child(
+            Flex::row()
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(ParseFormatter::new())
+                        .lens(AppState::export_width)
+                        .fix_width(60.0),
+                )
+                .with_child(Label::new("×"))
+                .with_child(
+                    TextBox::new()
+                        .with_formatter(ParseFormatter::new())
+                        .lens(AppState::export_height)
+                        .fix_width(60.0),
+                )
+                .with_child(Button::new("Export PNG").on_click(|ctx, _state, _env| {
+                    let options = FileDialogOptions::new()
+                        .allowed_types(vec![FileSpec::PNG])
+                        .default_name("rs-dir-stat.png");
+                    ctx.submit_command(SHOW_SAVE_PANEL.with(options));
+                }))
+                .with_child(
+                    Button::new("Copy stats as markdown")
+                        .on_click(|_ctx, state: &mut AppState, _env| {
+                            if let Some(files) = &state.all_files {
+                                Application::global()
+                                    .clipboard()
+                                    .put_string(stats_markdown(files, &state.folder));
+                            }
+                        })
+                        .disabled_if(|state: &AppState, _env| state.all_files.is_none()),
+                )
+                .with_child(
+                    Button::new("Copy as CSV")
+                        .on_click(|_ctx, state: &mut AppState, _env| {
+                            let files = state.visible_files();
+                            Application::global()
+                                .clipboard()
+                                .put_string(to_clipboard_text(|out| write_csv(&files, out)));
+                        })
+                        .disabled_if(|state: &AppState, _env| state.all_files.is_none()),
+                )
+                .with_child(
+                    Button::new("Copy as JSON")
+                        .on_click(|_ctx, state: &mut AppState, _env| {
+                            let files = state.visible_files();
+                            Application::global()
+                                .clipboard()
+                                .put_string(to_clipboard_text(|out| write_json(&files, out)));
+                        })
+                        .disabled_if(|state: &AppState, _env| state.all_files.is_none()),
+                )
+                .with_child(
+                    Button::new("Copy tree as text")
+                        .on_click(|_ctx, state: &mut AppState, _env| {
+                            if let Some(files) = &state.all_files {
+                                Application::global()
+                                    .clipboard()
+                                    .put_string(render_tree_text(files, state.report_min_size));
+                            }
+                        })
+                        .disabled_if(|state: &AppState, _env| state.all_files.is_none()),
+                ),
+        )
+        .with_child(largest_files_panel())
+        .with_child(largest_directories_panel())
+        .with_child(deepest_paths_panel())
+        .with_child(symlinks_panel())
+        .with_child(histogram_panel())
+        .with_child(folder_pie_panel())
+        .with_child(legend_panel())
+        .with_child(log_panel())
         .main_axis_alignment(druid::widget::MainAxisAlignment::Start)
+        .controller(AutoRescan::default())
+}
+
+/// What a scan found, to be applied to the state by [`AppState::finish_scan`].
+#[derive(Debug)]
+struct ScanOutcome {
+    files: Option<FileNode>,
+    special_files: u64,
+    scan_error: Option<String>,
+    pruned_files: u64,
+    excluded_bytes: u64,
+    total_blocks: u64,
+    compressed_sizes: HashMap<PathBuf, u64>,
+    archives: HashSet<PathBuf>,
+    incomplete_reasons: Vector<String>,
+    unreadable_directories: u64,
+    largest_files: Vector<ListedFile>,
+    /// What's different from the tree the scan replaces
+    changes: Option<ScanChanges>,
+    volume: Option<VolumeStats>,
+    files_scanned: u64,
+    folder: String,
+}
+
+/// Why `folder` can't be scanned, if it can't.
+///
+/// An empty path would resolve to the working directory, which is hardly ever what's meant.
+fn folder_error(folder: &str) -> Option<&'static str> {
+    folder
+        .trim()
+        .is_empty()
+        .then_some("Please enter a folder path")
+}
+
+/// Scans `state.folder` on a background thread, reporting progress and the result back to `state`.
+///
+/// A scan that's still running is cancelled, and whatever it reports afterwards is ignored.
+/// A folder that can't be scanned is only reported in `state.scan_error`.
+fn start_scan(sink: ExtEventSink, state: &mut AppState) {
+    if let Some(err) = folder_error(&state.folder) {
+        if !state.scanning {
+            state.scan_error = Some(err.to_owned());
+        }
+        return;
+    }
+    if state.scanning {
+        tracing::debug!("Cancelling running scan");
+        state.scan_cancel.store(true, Ordering::Relaxed);
+    }
+    state.scan_cancel = Default::default();
+    state.scan_generation += 1;
+    state.scanning = true;
+    state.files_scanned = 0;
+
+    let generation = state.scan_generation;
+    let cancelled = state.scan_cancel.clone();
+
+    let folder = state.folder.clone();
+    let options = ScanOptions {
+        only_extensions: extensions::parse_extensions(&state.only_extensions),
+        ..state.scan_options.clone()
+    };
+    let prune_empty_files = state.prune_empty_files;
+    // The tree being replaced is what the rescan is compared to
+    let previous = state.all_files.clone();
+    std::thread::spawn(move || {
+        let files_found = AtomicU64::new(0);
+        let done = AtomicBool::new(false);
+        let path = resolve_input_path(&folder);
+        let result = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(100));
+                    let files_scanned = files_found.load(Ordering::Relaxed);
+                    sink.add_idle_callback(move |state: &mut AppState| {
+                        if state.scan_generation == generation {
+                            state.files_scanned = files_scanned;
+                        }
+                    });
+                }
+            });
+            let checkpoint_file = AppDirs::new().map(|dirs| dirs.checkpoint_file());
+            let result = match checkpoint_file {
+                Some(Ok(file)) => {
+                    checkpoint::traverse_resumable(&path, &options, &files_found, &file, &cancelled)
+                }
+                Some(Err(err)) => {
+                    tracing::warn!("Not checkpointing, no cache directory: {err}");
+                    traverse(&path, &options, &files_found, &cancelled)
+                }
+                None => traverse(&path, &options, &files_found, &cancelled),
+            };
+            done.store(true, Ordering::Relaxed);
+            result
+        });
+        let (incomplete_reasons, unreadable_directories) = match &result {
+            Ok(result) if !result.is_complete() => (
+                result.incomplete_reasons().into(),
+                result.unreadable_directories,
+            ),
+            _ => Default::default(),
+        };
+        let (
+            mut files,
+            special_files,
+            excluded_bytes,
+            total_blocks,
+            compressed_sizes,
+            archives,
+            scan_error,
+        ) = match result {
+            Ok(result) => (
+                Some(result.root),
+                result.special_files,
+                result.excluded_bytes,
+                result.total_blocks,
+                result.compressed_sizes.into_iter().collect(),
+                result.archives.into_iter().collect(),
+                None,
+            ),
+            // Replaced by a newer scan, which reports instead
+            Err(ScanError::Cancelled) => return,
+            Err(err) => {
+                tracing::warn!("Failed scanning: {err}");
+                (
+                    None,
+                    0,
+                    0,
+                    0,
+                    Default::default(),
+                    Default::default(),
+                    Some(err.to_string()),
+                )
+            }
+        };
+        let pruned_files = match &mut files {
+            Some(files) if prune_empty_files => files.prune_empty_files(),
+            _ => 0,
+        };
+        let largest_files = files
+            .as_ref()
+            .map(|files| largest_files(files, LARGEST_FILES_COUNT))
+            .unwrap_or_default();
+        let changes = ScanChanges::of_rescan(previous.as_ref(), files.as_ref());
+        let volume = match VolumeStats::of(&path) {
+            Ok(volume) => Some(volume),
+            Err(err) => {
+                tracing::warn!("Failed reading volume stats of `{folder}`: {err}");
+                None
+            }
+        };
+
+        let outcome = ScanOutcome {
+            files,
+            special_files,
+            scan_error,
+            pruned_files,
+            excluded_bytes,
+            total_blocks,
+            compressed_sizes,
+            archives,
+            incomplete_reasons,
+            unreadable_directories,
+            largest_files,
+            changes,
+            volume,
+            files_scanned: files_found.load(Ordering::Relaxed),
+            folder,
+        };
+        sink.add_idle_callback(move |state: &mut AppState| {
+            let folder = outcome.folder.clone();
+            if state.finish_scan(generation, outcome) {
+                state.save(&folder);
+            }
+        });
+    });
+}
+
+/// Re-reads the sizes of the scanned tree's files in the background, without listing its
+/// directories again.
+fn start_refresh(sink: ExtEventSink, state: &mut AppState) {
+    let Some(mut files) = state.all_files.clone() else {
+        return;
+    };
+    state.scan_generation += 1;
+    state.scanning = true;
+    let generation = state.scan_generation;
+    let size_kind = state.scan_options.size_kind;
+    std::thread::spawn(move || {
+        let previous = files.clone();
+        let vanished = refresh_sizes(&mut files, size_kind);
+        tracing::debug!("Refreshed sizes, {vanished} files vanished since the scan");
+        let unknown_sizes = TreeStats::of(&files).unknown_size_count;
+        let changes = ScanChanges::of_rescan(Some(&previous), Some(&files));
+        sink.add_idle_callback(move |state: &mut AppState| {
+            state.finish_refresh(generation, files, vanished, unknown_sizes, changes);
+        });
+    });
+}
+
+/// The files listed on stdin, as if they had been scanned.
+fn outcome_from_stdin() -> ScanOutcome {
+    let files = path_list::nodes_from_paths(std::io::stdin().lock(), &metadata::RealFs);
+    let largest_files = files
+        .as_ref()
+        .map(|files| largest_files(files, LARGEST_FILES_COUNT))
+        .unwrap_or_default();
+    let folder = files
+        .as_ref()
+        .map(|files| files.path().display().to_string())
+        .unwrap_or_default();
+    ScanOutcome {
+        files_scanned: files.as_ref().map_or(0, FileNode::descendant_file_count),
+        scan_error: files
+            .is_none()
+            .then(|| String::from("No files were piped in")),
+        files,
+        special_files: 0,
+        pruned_files: 0,
+        excluded_bytes: 0,
+        total_blocks: 0,
+        compressed_sizes: Default::default(),
+        archives: Default::default(),
+        incomplete_reasons: Vector::new(),
+        unreadable_directories: 0,
+        largest_files,
+        changes: None,
+        volume: None,
+        folder,
+    }
+}
+
+/// Every extension with its share of the scanned size, and a checkbox to hide it.
+fn extension_legend() -> impl Widget<AppState> {
+    let entries = ViewSwitcher::new(
+        |state: &AppState, _env| state.extensions.clone(),
+        |extensions, state, _env| {
+            let total = state.tree_stats.map_or(0, |stats| stats.total_size);
+            let mut column = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+            for (extension, size) in extensions {
+                let name = if extension.is_empty() {
+                    String::from("(none)")
+                } else {
+                    format!(".{extension}")
+                };
+                let percentage = if total > 0 {
+                    *size as f64 / total as f64 * 100.0
+                } else {
+                    0.0
+                };
+                let label = format!("{name} {} ({percentage:.1}%)", format_size(*size));
+                let extension = extension.clone();
+                let shown = lens::Map::new(
+                    {
+                        let extension = extension.clone();
+                        move |state: &AppState| !state.hidden_extensions.contains(&extension)
+                    },
+                    move |state: &mut AppState, shown| {
+                        if shown {
+                            state.hidden_extensions.remove(&extension);
+                        } else {
+                            state.hidden_extensions.insert(extension.clone());
+                        }
+                    },
+                );
+                column.add_child(Checkbox::new(label).lens(shown));
+            }
+            Box::new(column)
+        },
+    );
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::dynamic(|state: &AppState, _env| {
+            if state.extensions.is_empty() {
+                return String::default();
+            }
+            format!("Shown: {}", format_size(state.visible_size()))
+        }))
+        .with_flex_child(Scroll::new(entries).vertical(), 1.0)
+        .fix_width(220.0)
+}
+
+/// Every symlink in `root`, in pre-order.
+fn symlinks(root: &FileNode) -> Vector<FileNode> {
+    root.symlinks().into_iter().cloned().collect()
+}
+
+/// Symlinks in the tree, the broken ones flagged.
+fn symlinks_panel() -> impl Widget<AppState> {
+    Label::dynamic(|state: &AppState, _env| {
+        let symlinks = &state.symlinks;
+        if symlinks.is_empty() {
+            return String::default();
+        }
+        let mut text = format!("Symlinks ({}):", symlinks.len());
+        for symlink in symlinks.iter().take(5) {
+            if let FileNode::Symlink {
+                path,
+                target,
+                broken,
+            } = symlink
+            {
+                text.push_str(&format!(
+                    "\n{} → {}",
+                    state.display_path(path),
+                    target.display()
+                ));
+                if *broken {
+                    text.push_str(" (broken)");
+                }
+            }
+        }
+        text
+    })
+    .expand_width()
+}
+
+/// How many files of which sizes there are, clicking a bucket shows only its files.
+fn histogram_panel() -> impl Widget<AppState> {
+    let mut buckets = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for bucket in 0..=SIZE_BUCKETS.len() {
+        let label = move |state: &AppState, _env: &Env| {
+            let stat = state
+                .size_histogram
+                .get(bucket)
+                .copied()
+                .unwrap_or_default();
+            let most = state.size_histogram.iter().map(|stat| stat.count).max();
+            let marker = if state.size_bucket == Some(bucket) {
+                "▶ "
+            } else {
+                ""
+            };
+            format!(
+                "{marker}{}: {} files, {} {}",
+                bucket_label(bucket),
+                state.format_count(stat.count),
+                format_size(stat.bytes),
+                histogram::bar(stat.count, most.unwrap_or(0))
+            )
+        };
+        buckets.add_child(Button::dynamic(label).on_click(
+            move |_ctx, state: &mut AppState, _env| {
+                state.size_bucket = (state.size_bucket != Some(bucket)).then_some(bucket);
+            },
+        ));
+    }
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new("Size histogram").lens(AppState::show_histogram))
+        .with_child(Either::new(
+            |state: &AppState, _env| state.show_histogram,
+            buckets,
+            Flex::column(),
+        ))
+}
+
+/// The shown folder's subdirectories as slices of a pie, with their sizes beside it.
+fn folder_pie_panel() -> impl Widget<AppState> {
+    let shares = Label::dynamic(|state: &AppState, _env| {
+        let total: u64 = state.folder_shares.iter().map(|share| share.size).sum();
+        state
+            .folder_shares
+            .iter()
+            .map(|share| {
+                format!(
+                    "{}: {} ({:.1}%)",
+                    share.name,
+                    format_size(share.size),
+                    share.size as f64 / total.max(1) as f64 * 100.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new("Show pie by folder").lens(AppState::show_folder_pie))
+        .with_child(Either::new(
+            |state: &AppState, _env| state.show_folder_pie,
+            Flex::row()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(FolderPie)
+                .with_spacer(8.0)
+                .with_child(shares),
+            Flex::column(),
+        ))
+}
+
+/// A key to the visual encodings that are enabled.
+fn legend_panel() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new("Show key").lens(AppState::show_legend))
+        .with_child(Either::new(
+            |state: &AppState, _env| state.show_legend,
+            Label::dynamic(|state: &AppState, _env| state.legend_entries().join("\n")),
+            Flex::column(),
+        ))
+}
+
+/// Recent log lines, so errors show up without a terminal.
+fn log_panel() -> impl Widget<AppState> {
+    let lines = Label::dynamic(|state: &AppState, _env| {
+        state
+            .log_lines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new("Show log").lens(AppState::show_log))
+        .with_child(Either::new(
+            |state: &AppState, _env| state.show_log,
+            Scroll::new(lines)
+                .vertical()
+                .fix_height(120.0)
+                .expand_width(),
+            Flex::column(),
+        ))
+}
+
+/// The largest files of the scan, sorted as chosen.
+fn largest_files_panel() -> impl Widget<AppState> {
+    let sort = Flex::row()
+        .with_child(Label::new("Largest files by"))
+        .with_child(
+            RadioGroup::row(vec![
+                ("Size", SortKey::Size),
+                ("Name", SortKey::Name),
+                ("Extension", SortKey::Extension),
+                ("Modified", SortKey::Modified),
+            ])
+            .lens(AppState::file_sort.then(FileSort::key)),
+        )
+        .with_child(
+            Checkbox::new("Descending").lens(AppState::file_sort.then(FileSort::descending)),
+        );
+    let files = Label::dynamic(|state: &AppState, _env| {
+        let mut text = String::new();
+        for file in state.file_sort.sorted(&state.largest_files) {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&format!(
+                "{}: {}",
+                format_size(file.size),
+                state.display_path(&file.path)
+            ));
+        }
+        text
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(sort)
+        .with_child(files)
+        .expand_width()
+}
+
+/// The directories in `root` with the most in them, largest first.
+fn largest_directories(root: &FileNode) -> Vector<Crumb> {
+    root.largest_directories(LARGEST_DIRECTORIES_COUNT)
+        .into_iter()
+        .map(|(path, size)| Crumb {
+            path: path.clone(),
+            size,
+        })
+        .collect()
+}
+
+/// Directories with the most in them, each a button drilling into it.
+fn largest_directories_panel() -> impl Widget<AppState> {
+    let directories = ViewSwitcher::new(
+        |state: &AppState, _env| state.largest_directories.clone(),
+        |directories, _state, _env| {
+            let mut column = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+            for directory in directories.iter().cloned() {
+                let Crumb { path, size } = directory.clone();
+                let text = move |state: &AppState, _env: &Env| {
+                    format!("{}: {}", format_size(size), state.display_path(&path))
+                };
+                column.add_child(Button::dynamic(text).on_click(
+                    move |_ctx, state: &mut AppState, _env| {
+                        state.set_view_root(Some(directory.path.clone()));
+                        state.select_only(None);
+                    },
+                ));
+            }
+            Box::new(column)
+        },
+    );
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Largest folders"))
+        .with_child(directories)
+        .expand_width()
+}
+
+fn deepest_paths_panel() -> impl Widget<AppState> {
+    Label::dynamic(|state: &AppState, _env| {
+        if state.all_files.is_none() {
+            return String::default();
+        }
+        let mut text = String::from("Deepest paths:");
+        for (path, depth) in &state.deepest_paths {
+            text.push_str(&format!("\n{depth}: {}", state.display_path(path)));
+        }
+        text
+    })
+    .expand_width()
 }
 
 pub fn main() {
-    let window = WindowDesc::new(main_widget()).title(String::from("rs-dir-stat"));
-    AppLauncher::with_window(window)
-        .log_to_console()
-        .launch(AppState::default())
-        .expect("launch failed");
+    // Set up first, so what loading and reading stdin warn about is kept too
+    let log = LogBuffer::new(LOG_CAPACITY);
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::DEBUG))
+        .with(log.clone().with_filter(LevelFilter::INFO))
+        .init();
+    let mut state = AppState::load();
+    if let Some(folder) = cli::bench_scan_arg(std::env::args().skip(1)) {
+        bench::attach_console();
+        let path = resolve_input_path(&folder);
+        match bench::bench_scan(&path, &state.scan_options, bench::BENCH_ITERATIONS) {
+            Ok(summary) => println!("{summary}"),
+            Err(err) => {
+                eprintln!("Failed scanning `{}`: {err}", path.display());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let folder = cli::folder_arg(std::env::args().skip(1));
+    if let Some(folder) = &folder {
+        state.folder = resolve_input_path(folder).display().to_string();
+    }
+    if let Some(min_size) = cli::report_min_size_arg(std::env::args().skip(1)) {
+        state.report_min_size = min_size;
+    }
+    if cli::from_stdin_arg(std::env::args().skip(1)) {
+        let outcome = outcome_from_stdin();
+        state.folder = outcome.folder.clone();
+        state.finish_scan(state.scan_generation, outcome);
+    }
+    let window =
+        WindowDesc::new(main_widget()).title(|state: &AppState, _env: &Env| state.window_title());
+    let launcher = AppLauncher::with_window(window).delegate(Delegate);
+    log.forward_to(launcher.get_external_handle());
+    if folder.is_some() {
+        // Started from a file manager to look at that folder, so scan it right away
+        let sink = launcher.get_external_handle();
+        launcher
+            .get_external_handle()
+            .add_idle_callback(move |state: &mut AppState| start_scan(sink, state));
+    }
+    launcher.launch(state).expect("launch failed");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::file_system::FileNode;
+    use druid::LensExt;
+
+    use crate::changes::ScanChanges;
+    use crate::delete::DeleteMode;
+    use crate::file_system::ScanOptions;
+    use crate::open::{MockLauncher, OpenAction};
+    use crate::settings::Settings;
+    use crate::{folder_error, AppState, ColorMode, LayoutMode, ScanOutcome, SelectionStyle};
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn ctrl_click_toggles_selection() {
+        let mut state = AppState::default();
+        state.select_only(Some(file("/a", 1)));
+        state.toggle_selected(file("/b", 2));
+        state.toggle_selected(file("/c", 4));
+        assert_eq!(state.selected_files.len(), 3);
+        assert_eq!(state.selected_file, Some(file("/c", 4)));
+        assert_eq!(state.selected_size(), 7);
+
+        state.toggle_selected(file("/c", 4));
+        assert_eq!(state.selected_files.len(), 2);
+        assert_eq!(state.selected_file, Some(file("/b", 2)));
+
+        state.toggle_selected(file("/a", 1));
+        state.toggle_selected(file("/b", 2));
+        assert!(state.selected_files.is_empty());
+        assert_eq!(state.selected_file, None);
+    }
+
+    #[test]
+    fn plain_click_selects_only_one() {
+        let mut state = AppState::default();
+        state.toggle_selected(file("/a", 1));
+        state.toggle_selected(file("/b", 2));
+        state.select_only(Some(file("/c", 4)));
+        assert_eq!(state.selected_files.len(), 1);
+        assert_eq!(state.selected_file, Some(file("/c", 4)));
+
+        state.select_only(None);
+        assert!(state.selected_files.is_empty());
+        assert_eq!(state.selected_file, None);
+    }
+
+    #[test]
+    fn window_title_names_scanned_folder() {
+        let mut state = AppState::default();
+        assert_eq!(state.window_title(), "rs-dir-stat");
+
+        state.all_files = Some(FileNode::Directory {
+            path: "/home/user/Videos".into(),
+            children: druid::im::vector![file("/home/user/Videos/a", 13_207_024_435)],
+        });
+        state.tree_stats = state.stats();
+        assert_eq!(
+            state.window_title(),
+            "rs-dir-stat — /home/user/Videos (12.3 GiB)"
+        );
+    }
+
+    #[test]
+    fn hiding_an_extension_shrinks_visible_size() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: druid::im::vector![file("/a.rs", 1), file("/b.rs", 2), file("/c.png", 4)],
+        };
+        let mut state = AppState {
+            extensions: crate::extensions::extension_sizes(&root),
+            all_files: Some(root.clone()),
+            ..Default::default()
+        };
+        assert_eq!(state.visible_size(), 7);
+
+        state.hidden_extensions.insert(String::from("rs"));
+        assert_eq!(state.visible_size(), 4);
+        assert_eq!(
+            crate::extensions::without_extensions(&root, &state.hidden_extensions).size(),
+            state.visible_size()
+        );
+    }
+
+    #[test]
+    fn filtered_out_selection_is_marked_hidden() {
+        let mut state = AppState::default();
+        state.select_only(Some(file("/a.rs", 1)));
+        assert_eq!(state.selection_summary(), "/a.rs");
+
+        // As the visualization notes it
+        state.selection_drawn = false;
+        assert_eq!(
+            state.selection_summary(),
+            "/a.rs (hidden by the current view)"
+        );
+        assert_eq!(state.selected_files.len(), 1);
+    }
+
+    #[test]
+    fn legend_lists_enabled_encodings() {
+        let mut state = AppState::default();
+        assert_eq!(
+            state.legend_entries(),
+            [
+                "Color: position, from #ff0000 to #0000ff",
+                "Outlined in a contrasting color: selected",
+            ]
+        );
+
+        state.nodes_folded = true;
+
+        state.color_mode = ColorMode::ByNameHash;
+        state.selection_style = SelectionStyle::DimOthers;
+        state.cushioned = true;
+        state.directory_borders = true;
+        state.scan_options.min_expand_size = 2048;
+        assert_eq!(
+            state.legend_entries(),
+            [
+                "Color: extension, or name for folders",
+                "Darkened: not selected",
+                "Shading: one cushion per rectangle",
+                "\"N items\" in a folder: folded, under 2.0 KiB",
+                "\"N others\": too small to draw on their own",
+            ]
+        );
+
+        state.layout_mode = LayoutMode::Treemap;
+        state.selection_style = SelectionStyle::Both;
+        state.black_or_white_outlines = true;
+        state.group_root_files = true;
+        let entries = state.legend_entries();
+        assert!(entries.contains(&String::from("Outlined in black or white: selected")));
+        assert!(entries.contains(&String::from("Thin colored borders: folders")));
+        assert!(entries.contains(&String::from(
+            "\"N files\": files directly in the shown folder"
+        )));
+        assert_eq!(entries.len(), 8);
+    }
+
+    #[test]
+    fn drilling_up_stops_at_scanned_folder() {
+        let mut state = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/root".into(),
+                children: druid::im::vector![FileNode::Directory {
+                    path: "/root/a".into(),
+                    children: druid::im::vector![FileNode::Directory {
+                        path: "/root/a/b".into(),
+                        children: druid::im::vector![file("/root/a/b/1", 1)],
+                    }],
+                }],
+            }),
+            view_root: Some("/root/a/b".into()),
+            ..Default::default()
+        };
+        assert_eq!(state.shown_root().unwrap().path(), Path::new("/root/a/b"));
+        state.drill_up();
+        assert_eq!(state.shown_root().unwrap().path(), Path::new("/root/a"));
+        // The pie by folder and the breadcrumbs follow the shown directory
+        assert_eq!(state.folder_shares[0].name, "b");
+        assert_eq!(state.breadcrumbs.len(), 2);
+        state.drill_up();
+        assert_eq!(state.view_root, None);
+        assert_eq!(state.shown_root().unwrap().path(), Path::new("/root"));
+        assert_eq!(state.folder_shares[0].name, "a");
+        assert_eq!(state.breadcrumbs.len(), 1);
+    }
+
+    #[test]
+    fn files_in_archives_cant_be_deleted() {
+        let mut state = AppState {
+            archives: [PathBuf::from("/a.zip")].into_iter().collect(),
+            ..Default::default()
+        };
+        state.select_only(Some(file("/a.zip/docs/1.txt", 4)));
+        assert!(state.selection_in_archive());
+        state.select_only(Some(FileNode::Directory {
+            path: "/a.zip/docs".into(),
+            children: Default::default(),
+        }));
+        assert!(state.selection_in_archive());
+        // The archive itself is on disk, as a file
+        state.select_only(Some(FileNode::Directory {
+            path: "/a.zip".into(),
+            children: Default::default(),
+        }));
+        assert!(!state.selection_in_archive());
+    }
+
+    #[test]
+    fn deleting_selection_updates_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/big.bin"), [0; 100]).unwrap();
+        std::fs::write(dir.path().join("small.txt"), [0; 3]).unwrap();
+        let root = crate::file_system::traverse(
+            dir.path(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap()
+        .root;
+        let mut state = AppState {
+            extensions: crate::extensions::extension_sizes(&root),
+            all_files: Some(root),
+            confirming_delete: true,
+            delete_mode: DeleteMode::Permanent,
+            total_blocks: 8,
+            ..Default::default()
+        };
+        let big = file(dir.path().join("sub/big.bin").to_str().unwrap(), 100);
+        state.select_only(Some(big.clone()));
+
+        state.delete_selected();
+        assert!(!big.path().exists());
+        assert_eq!(state.all_files.as_ref().unwrap().size(), 3);
+        assert_eq!(
+            state.extensions,
+            druid::im::vector![(String::from("txt"), 3)]
+        );
+        assert_eq!(
+            state.largest_directories,
+            druid::im::vector![crate::breadcrumbs::Crumb {
+                path: dir.path().join("sub"),
+                size: 0
+            }]
+        );
+        assert_eq!(state.tree_stats.map(|stats| stats.total_size), Some(3));
+        // What the deleted file held of the blocks isn't known
+        assert_eq!(state.total_blocks, 0);
+        // The emptied folder is as deep as what's left
+        assert_eq!(state.deepest_paths.len(), 2);
+        assert!(state.selected_files.is_empty());
+        assert!(!state.confirming_delete);
+    }
+
+    #[test]
+    fn trashing_selection_updates_tree_the_same() {
+        let big = file("/root/big.bin", 100);
+        let link = FileNode::Symlink {
+            path: "/root/link".into(),
+            target: "big.bin".into(),
+            broken: false,
+        };
+        let mut state = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/root".into(),
+                children: druid::im::vector![big.clone(), file("/root/small.txt", 3), link.clone()],
+            }),
+            symlinks: druid::im::vector![link.clone()],
+            ..Default::default()
+        };
+        state.select_only(Some(big.clone()));
+        state.toggle_selected(link);
+
+        let deleter = crate::delete::MockDeleter::default();
+        state.delete_selected_with(&deleter);
+        assert_eq!(
+            deleter.calls.into_inner(),
+            [
+                ("trash", PathBuf::from("/root/big.bin")),
+                ("trash", PathBuf::from("/root/link"))
+            ]
+        );
+        assert_eq!(state.all_files.as_ref().unwrap().size(), 3);
+        assert!(state.symlinks.is_empty());
+        assert!(state.selected_files.is_empty());
+    }
+
+    #[test]
+    fn stand_ins_are_never_deleted() {
+        let children: druid::im::Vector<_> = (0..4)
+            .map(|index| file(&format!("/root/{index}"), index))
+            .collect();
+        let root = FileNode::Directory {
+            path: "/root".into(),
+            children: children.clone(),
+        };
+        let visible = crate::overview::VisibleNodes::new(children, Default::default());
+        let others = crate::overview::cap_nodes(visible, 2).nodes.last().cloned();
+        let mut state = AppState {
+            all_files: Some(root.clone()),
+            delete_mode: DeleteMode::Permanent,
+            ..Default::default()
+        };
+        state.select_only(others.clone());
+        state.toggle_selected(others.clone().unwrap());
+        assert!(state.selected_files.is_empty());
+
+        // Even if one slips into the selection
+        state.selected_files = others.into_iter().collect();
+        let deleter = crate::delete::MockDeleter::default();
+        state.delete_selected_with(&deleter);
+        assert!(deleter.calls.into_inner().is_empty());
+        assert_eq!(state.all_files, Some(root));
+    }
+
+    #[test]
+    fn opening_selection_opens_each_folder_once() {
+        let mut state = AppState::default();
+        state.select_only(Some(file("/root/a.txt", 1)));
+        state.toggle_selected(file("/root/b.txt", 2));
+        state.toggle_selected(file("/root/sub/c.txt", 3));
+        // A stand-in for several files has nothing to open
+        state.selected_files.push_back(FileNode::Aggregate {
+            path: "/root/4 others".into(),
+            size: 4,
+            count: 4,
+        });
+
+        let launcher = MockLauncher::default();
+        state.open_selected(OpenAction::File, &launcher);
+        assert_eq!(launcher.launched.take().len(), 3);
+        state.open_selected(OpenAction::ContainingFolder, &launcher);
+        assert_eq!(
+            launcher.launched.into_inner(),
+            ["/root/a.txt", "/root/sub/c.txt"]
+                .map(|path| OpenAction::ContainingFolder.command(Path::new(path)))
+                .map(|(program, args)| (program.to_owned(), args))
+        );
+    }
+
+    #[test]
+    fn replaced_scans_are_ignored() {
+        let outcome = |path: &str| ScanOutcome {
+            files: Some(file(path, 1)),
+            special_files: 0,
+            scan_error: None,
+            pruned_files: 0,
+            excluded_bytes: 0,
+            total_blocks: 0,
+            compressed_sizes: Default::default(),
+            archives: Default::default(),
+            incomplete_reasons: druid::im::vector![String::from("1 directory unreadable")],
+            unreadable_directories: 1,
+            largest_files: Default::default(),
+            changes: None,
+            volume: None,
+            files_scanned: 1,
+            folder: String::from("/"),
+        };
+        let mut state = AppState {
+            scanning: true,
+            scan_generation: 2,
+            ..Default::default()
+        };
+        assert!(!state.finish_scan(1, outcome("/old")));
+        assert!(state.scanning);
+        assert_eq!(state.all_files, None);
+
+        assert!(state.finish_scan(2, outcome("/new")));
+        assert!(!state.scanning);
+        assert_eq!(state.all_files, Some(file("/new", 1)));
+        assert_eq!(state.tree_stats.map(|stats| stats.file_count), Some(1));
+        assert_eq!(state.incomplete_reasons.len(), 1);
+        assert_eq!(state.scan_changes, None);
+
+        let changes = ScanChanges {
+            net_bytes: 1,
+            ..Default::default()
+        };
+        state.scan_generation = 3;
+        assert!(state.finish_scan(
+            3,
+            ScanOutcome {
+                changes: Some(changes),
+                ..outcome("/new")
+            }
+        ));
+        assert_eq!(state.scan_changes, Some(changes));
+    }
+
+    #[test]
+    fn refreshing_retells_why_results_are_incomplete() {
+        let mut state = AppState {
+            all_files: Some(file("/root", 1)),
+            incomplete_reasons: druid::im::vector![
+                String::from("1 directory unreadable"),
+                String::from("2 files vanished while scanning"),
+            ],
+            unreadable_directories: 1,
+            total_blocks: 8,
+            scan_generation: 1,
+            scanning: true,
+            ..Default::default()
+        };
+        assert!(state.finish_refresh(1, file("/root", 2), 3, 0, None));
+        assert_eq!(state.total_blocks, 0);
+        // Directories still aren't listed, but what vanished is counted anew
+        assert_eq!(
+            state.incomplete_reasons,
+            druid::im::vector![
+                String::from("1 directory unreadable"),
+                String::from("3 files vanished while scanning"),
+            ]
+        );
+        assert!(!state.scanning);
+    }
+
+    #[test]
+    fn empty_folder_is_rejected() {
+        assert_eq!(folder_error(""), Some("Please enter a folder path"));
+        assert_eq!(folder_error(" \t "), Some("Please enter a folder path"));
+        assert_eq!(folder_error("~"), None);
+        assert_eq!(folder_error("/home/user"), None);
+    }
+
+    #[test]
+    fn settings_panel_applies_edits() {
+        let mut state = AppState::default();
+        let mut settings = Settings::of(&state);
+        Settings::cushioned.put(&mut settings, true);
+        Settings::scan_options
+            .then(ScanOptions::resume)
+            .put(&mut settings, true);
+        state.settings = Some(settings);
+        // Edits only take effect once they're applied
+        assert!(!state.cushioned && !state.scan_options.resume);
+
+        state.apply_settings();
+        assert!(state.cushioned && state.scan_options.resume);
+        assert_eq!(state.settings, Some(Settings::of(&state)));
+
+        state.settings = Some(Settings::default());
+        state.apply_settings();
+        assert!(!state.cushioned && !state.scan_options.resume);
+    }
 }
@@ -0,0 +1,361 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What kind of entry a path is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryKind {
+    Directory,
+    File,
+    Symlink,
+    /// Windows junctions and other reparse points that aren't symlinks, which can be followed
+    /// like one
+    ReparsePoint,
+    /// Devices, sockets, fifos and anything else
+    Special,
+}
+
+/// The part of a path's metadata a scan looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EntryMetadata {
+    pub(crate) kind: EntryKind,
+    /// Size in bytes
+    pub(crate) len: u64,
+    /// 512 byte blocks allocated on disk, 0 where that isn't known
+    pub(crate) blocks: u64,
+}
+
+impl From<std::fs::Metadata> for EntryMetadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() && is_reparse_point(&metadata) {
+            // Files have the attribute too, like OneDrive placeholders, and are read as files
+            EntryKind::ReparsePoint
+        } else if file_type.is_dir() {
+            EntryKind::Directory
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Special
+        };
+        Self {
+            kind,
+            len: metadata.len(),
+            blocks: blocks(&metadata),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn blocks(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks()
+}
+
+/// Only Unix reports allocated blocks.
+#[cfg(not(unix))]
+fn blocks(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(windows)]
+fn is_reparse_point(metadata: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+    metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
+/// Only Windows has reparse points.
+#[cfg(not(windows))]
+fn is_reparse_point(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Where a scan reads directories and metadata from, so scans can run against a fake
+/// filesystem in tests.
+pub(crate) trait MetadataProvider: Sync {
+    /// Paths of the entries in the directory `path`, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    /// Metadata of `path`, following symlinks.
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+    /// Metadata of `path` itself, even if it's a symlink or reparse point.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    /// `path` made absolute, with `..` and every symlink and reparse point in it resolved, so
+    /// paths can be compared.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// What the file `path` takes up on disk after the filesystem compressed it, `None` where
+    /// that can't be told.
+    fn compressed_size(&self, _path: &Path) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// The actual filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RealFs;
+
+impl MetadataProvider for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        std::fs::metadata(path).map(EntryMetadata::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        std::fs::symlink_metadata(path).map(EntryMetadata::from)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+
+    fn compressed_size(&self, path: &Path) -> io::Result<Option<u64>> {
+        compressed_size(path)
+    }
+}
+
+/// NTFS tells what compressed and sparse files take up.
+#[cfg(windows)]
+fn compressed_size(path: &Path) -> io::Result<Option<u64>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCompressedFileSizeW(file_name: *const u16, file_size_high: *mut u32) -> u32;
+    }
+    const INVALID_FILE_SIZE: u32 = u32::MAX;
+
+    let file_name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut high = 0;
+    // SAFETY: `file_name` is NUL terminated and both pointers outlive the call
+    let low = unsafe { GetCompressedFileSizeW(file_name.as_ptr(), &mut high) };
+    // The low half of a valid size may be all ones too, the error code tells them apart
+    if low == INVALID_FILE_SIZE {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(0) {
+            return Err(err);
+        }
+    }
+    Ok(Some((u64::from(high) << 32) | u64::from(low)))
+}
+
+/// APFS and HFS+ count the blocks of compressed files after compression.
+#[cfg(target_os = "macos")]
+fn compressed_size(path: &Path) -> io::Result<Option<u64>> {
+    use std::os::macos::fs::MetadataExt;
+
+    const UF_COMPRESSED: u32 = 0x20;
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.st_flags() & UF_COMPRESSED != 0).then(|| metadata.st_blocks() * 512))
+}
+
+/// Other filesystems, btrfs among them, don't tell through a portable call.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn compressed_size(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+pub(crate) use memory::MemoryFs;
+
+#[cfg(test)]
+mod memory {
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::path::{Component, Path, PathBuf};
+
+    use super::{EntryKind, EntryMetadata, MetadataProvider};
+
+    #[derive(Debug, Clone)]
+    enum MemoryEntry {
+        Directory,
+        File(u64),
+        Symlink(PathBuf),
+        ReparsePoint(PathBuf),
+        Special,
+        /// Listed as a file, but reading its metadata fails, like a file changing while it's
+        /// scanned
+        Failing(io::ErrorKind),
+        /// A file taking up `blocks` on disk, `compressed` bytes if the filesystem tells
+        Stored {
+            len: u64,
+            blocks: u64,
+            compressed: Option<u64>,
+        },
+    }
+
+    /// A filesystem in memory, built up entry by entry. Parents aren't created implicitly.
+    #[derive(Debug, Default, Clone)]
+    pub(crate) struct MemoryFs {
+        entries: BTreeMap<PathBuf, MemoryEntry>,
+    }
+
+    impl MemoryFs {
+        fn with(mut self, path: &str, entry: MemoryEntry) -> Self {
+            self.entries.insert(path.into(), entry);
+            self
+        }
+
+        pub(crate) fn dir(self, path: &str) -> Self {
+            self.with(path, MemoryEntry::Directory)
+        }
+
+        pub(crate) fn file(self, path: &str, size: u64) -> Self {
+            self.with(path, MemoryEntry::File(size))
+        }
+
+        /// A symlink to `target`, relative to the link's directory unless absolute.
+        pub(crate) fn symlink(self, path: &str, target: &str) -> Self {
+            self.with(path, MemoryEntry::Symlink(target.into()))
+        }
+
+        /// A reparse point to `target`, resolved like a symlink's.
+        pub(crate) fn reparse_point(self, path: &str, target: &str) -> Self {
+            self.with(path, MemoryEntry::ReparsePoint(target.into()))
+        }
+
+        pub(crate) fn special(self, path: &str) -> Self {
+            self.with(path, MemoryEntry::Special)
+        }
+
+        pub(crate) fn failing(self, path: &str, kind: io::ErrorKind) -> Self {
+            self.with(path, MemoryEntry::Failing(kind))
+        }
+
+        /// A file of `len` bytes taking up `blocks` on disk, and `compressed` bytes if the
+        /// filesystem tells.
+        pub(crate) fn stored(
+            self,
+            path: &str,
+            len: u64,
+            blocks: u64,
+            compressed: Option<u64>,
+        ) -> Self {
+            self.with(
+                path,
+                MemoryEntry::Stored {
+                    len,
+                    blocks,
+                    compressed,
+                },
+            )
+        }
+
+        /// `path` with every symlink and reparse point above it replaced by its target, and
+        /// `..` going up from there.
+        fn resolve(&self, path: &Path) -> PathBuf {
+            let mut resolved = PathBuf::new();
+            let mut components = path.components().peekable();
+            while let Some(component) = components.next() {
+                if component == Component::ParentDir {
+                    resolved.pop();
+                } else {
+                    resolved.push(component);
+                }
+                if components.peek().is_none() {
+                    break;
+                }
+                if let Some(MemoryEntry::Symlink(target) | MemoryEntry::ReparsePoint(target)) =
+                    self.entries.get(&resolved)
+                {
+                    let parent = resolved.parent().unwrap_or(Path::new("/"));
+                    resolved = self.resolve(&parent.join(target));
+                }
+            }
+            resolved
+        }
+
+        fn entry(&self, path: &Path) -> io::Result<&MemoryEntry> {
+            self.entries
+                .get(&self.resolve(path))
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+    }
+
+    impl MetadataProvider for MemoryFs {
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            let listed = match self.entry(path)? {
+                MemoryEntry::Directory => {
+                    let resolved = self.resolve(path);
+                    self.entries
+                        .keys()
+                        .filter(|child| child.parent() == Some(&resolved))
+                        .cloned()
+                        .collect()
+                }
+                MemoryEntry::Symlink(target) | MemoryEntry::ReparsePoint(target) => {
+                    let parent = path.parent().unwrap_or(Path::new("/"));
+                    self.read_dir(&parent.join(target))?
+                }
+                _ => return Err(io::ErrorKind::NotADirectory.into()),
+            };
+            // Entries are found below the path they're listed through
+            Ok(listed
+                .into_iter()
+                .filter_map(|child| Some(path.join(child.file_name()?)))
+                .collect())
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+            match self.entry(path)? {
+                MemoryEntry::Symlink(target) | MemoryEntry::ReparsePoint(target) => {
+                    let parent = path.parent().unwrap_or(Path::new("/"));
+                    self.metadata(&parent.join(target))
+                }
+                MemoryEntry::Failing(kind) => Err((*kind).into()),
+                _ => self.symlink_metadata(path),
+            }
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+            let (kind, len, blocks) = match self.entry(path)? {
+                MemoryEntry::Directory => (EntryKind::Directory, 0, 0),
+                MemoryEntry::File(size) => (EntryKind::File, *size, 0),
+                MemoryEntry::Symlink(_) => (EntryKind::Symlink, 0, 0),
+                MemoryEntry::ReparsePoint(_) => (EntryKind::ReparsePoint, 0, 0),
+                MemoryEntry::Special => (EntryKind::Special, 0, 0),
+                MemoryEntry::Failing(_) => (EntryKind::File, 0, 0),
+                MemoryEntry::Stored { len, blocks, .. } => (EntryKind::File, *len, *blocks),
+            };
+            Ok(EntryMetadata { kind, len, blocks })
+        }
+
+        fn compressed_size(&self, path: &Path) -> io::Result<Option<u64>> {
+            match self.entry(path)? {
+                MemoryEntry::Stored { compressed, .. } => Ok(*compressed),
+                _ => Ok(None),
+            }
+        }
+
+        fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+            match self.entry(path)? {
+                MemoryEntry::Symlink(target) | MemoryEntry::ReparsePoint(target) => {
+                    Ok(target.clone())
+                }
+                _ => Err(io::ErrorKind::InvalidInput.into()),
+            }
+        }
+
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            let resolved = self.resolve(path);
+            match self.entries.get(&resolved) {
+                Some(MemoryEntry::Symlink(target) | MemoryEntry::ReparsePoint(target)) => {
+                    let parent = resolved.parent().unwrap_or(Path::new("/"));
+                    self.canonicalize(&parent.join(target))
+                }
+                Some(_) => Ok(resolved),
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+}
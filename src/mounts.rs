@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+/// Where the kernel lists the mounts seen by this process
+#[cfg(target_os = "linux")]
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// One mount, as listed in `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Mount {
+    pub(crate) id: u64,
+    /// Major and minor number of the mounted filesystem's device
+    pub(crate) device: (u32, u32),
+    /// The directory of the filesystem that's mounted, `/` unless it's a bind mount
+    pub(crate) root: PathBuf,
+    pub(crate) mount_point: PathBuf,
+    /// Lower and upper directories of an overlay, empty for other filesystems
+    pub(crate) layers: Vec<PathBuf>,
+}
+
+/// The mounts in the text of a mountinfo file. Lines that can't be parsed are skipped.
+pub(crate) fn parse_mountinfo(text: &str) -> Vec<Mount> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+/// One line, like `36 35 98:0 /mnt1 /mnt/parent rw - ext3 /dev/root rw`.
+fn parse_line(line: &str) -> Option<Mount> {
+    let (mount, filesystem) = line.split_once(" - ")?;
+    let mut fields = mount.split(' ');
+    let id = fields.next()?.parse().ok()?;
+    let _parent = fields.next()?;
+    let (major, minor) = fields.next()?.split_once(':')?;
+    let device = (major.parse().ok()?, minor.parse().ok()?);
+    let root = unescape(fields.next()?).into();
+    let mount_point = unescape(fields.next()?).into();
+    let mut fields = filesystem.split(' ');
+    let fs_type = fields.next()?;
+    let super_options = fields.nth(1).unwrap_or_default();
+    let layers = if fs_type == "overlay" {
+        super_options
+            .split(',')
+            .filter_map(|option| {
+                option
+                    .strip_prefix("lowerdir=")
+                    .or_else(|| option.strip_prefix("upperdir="))
+            })
+            .flat_map(|dirs| dirs.split(':'))
+            .map(|dir| unescape(dir).into())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(Mount {
+        id,
+        device,
+        root,
+        mount_point,
+        layers,
+    })
+}
+
+/// Decodes the octal escapes like `\040` for a space that mountinfo writes in paths.
+fn unescape(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'\\')
+            .then(|| tail.get(..3))
+            .flatten()
+            .and_then(|octal| u8::from_str_radix(std::str::from_utf8(octal).ok()?, 8).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[3..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Mount points below `root` whose files a scan of `root` already finds elsewhere.
+///
+/// A bind mount shows a directory of a filesystem that is also mounted at another place, by
+/// the same device, so it's skipped if that place is below `root` too. Of two mounts showing
+/// exactly the same directory, the one mounted later is skipped. An overlay is skipped if one
+/// of its layers is below `root`, as the layers are where its files take up space.
+pub(crate) fn duplicate_mount_points(mounts: &[Mount], root: &Path) -> Vec<PathBuf> {
+    mounts
+        .iter()
+        .filter(|mount| mount.mount_point.starts_with(root) && mount.mount_point != root)
+        .filter(|mount| {
+            let shown_elsewhere = mounts.iter().any(|other| {
+                let shows_same = other.device == mount.device
+                    && other.id != mount.id
+                    && (other.root != mount.root || other.id < mount.id);
+                shows_same
+                    && mount
+                        .root
+                        .strip_prefix(&other.root)
+                        .is_ok_and(|inner| other.mount_point.join(inner).starts_with(root))
+            });
+            shown_elsewhere || mount.layers.iter().any(|layer| layer.starts_with(root))
+        })
+        .map(|mount| mount.mount_point.clone())
+        .collect()
+}
+
+/// The mounts of this process, `None` if they can't be read or the platform has no
+/// mountinfo.
+pub(crate) fn read_mounts() -> Option<Vec<Mount>> {
+    #[cfg(target_os = "linux")]
+    match std::fs::read_to_string(MOUNTINFO) {
+        Ok(text) => Some(parse_mountinfo(&text)),
+        Err(err) => {
+            tracing::warn!("Not skipping duplicate mounts, failed reading {MOUNTINFO}: {err}");
+            None
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{duplicate_mount_points, parse_mountinfo, Mount};
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 8:2 / /data rw shared:2 - ext4 /dev/sda2 rw
+41 22 8:2 /photos /home/me/My\\040Photos rw shared:2 - ext4 /dev/sda2 rw
+42 22 8:1 /srv /mnt/srv rw - ext4 /dev/sda1 rw
+43 22 8:2 / /mnt/data2 rw - ext4 /dev/sda2 rw
+50 22 0:52 / /merged rw - overlay overlay rw,lowerdir=/layers/a:/layers/b,upperdir=/layers/up,workdir=/layers/work
+garbage line
+";
+
+    #[test]
+    fn parses_mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 6);
+        assert_eq!(
+            mounts[2],
+            Mount {
+                id: 41,
+                device: (8, 2),
+                root: "/photos".into(),
+                mount_point: "/home/me/My Photos".into(),
+                layers: vec![],
+            }
+        );
+        assert_eq!(
+            mounts[5].layers,
+            [
+                PathBuf::from("/layers/a"),
+                "/layers/b".into(),
+                "/layers/up".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_mounts_found_elsewhere() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        let duplicates = |root: &str| duplicate_mount_points(&mounts, Path::new(root));
+
+        assert_eq!(
+            duplicates("/"),
+            [
+                PathBuf::from("/home/me/My Photos"),
+                "/mnt/srv".into(),
+                "/mnt/data2".into(),
+                "/merged".into()
+            ]
+        );
+        // `/data` isn't scanned, so its bind mounts are the only place its files are found
+        assert_eq!(duplicates("/home"), Vec::<PathBuf>::new());
+        assert_eq!(duplicates("/mnt"), Vec::<PathBuf>::new());
+        assert_eq!(duplicates("/merged"), Vec::<PathBuf>::new());
+    }
+}
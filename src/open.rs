@@ -0,0 +1,151 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// What opening a file does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpenAction {
+    /// Launch it with the app it's associated with
+    File,
+    /// Show it in the file manager
+    ContainingFolder,
+}
+
+impl OpenAction {
+    /// The program and arguments doing this to `path` on this platform.
+    pub(crate) fn command(self, path: &Path) -> (&'static str, Vec<OsString>) {
+        let path = path.as_os_str().to_owned();
+        match self {
+            OpenAction::File if cfg!(target_os = "macos") => ("open", vec![path]),
+            // Not `cmd /C start`, as cmd would run whatever follows a `&` in the file name
+            OpenAction::File if cfg!(windows) => ("explorer", vec![path]),
+            OpenAction::File => ("xdg-open", vec![path]),
+            OpenAction::ContainingFolder if cfg!(target_os = "macos") => {
+                ("open", vec!["-R".into(), path])
+            }
+            OpenAction::ContainingFolder if cfg!(windows) => {
+                let mut select = OsString::from("/select,");
+                select.push(path);
+                ("explorer", vec![select])
+            }
+            // There's no common way to select a file in a Linux file manager, so its folder is
+            // opened
+            OpenAction::ContainingFolder => {
+                let folder = Path::new(&path)
+                    .parent()
+                    .map_or(path.clone(), |folder| folder.as_os_str().to_owned());
+                ("xdg-open", vec![folder])
+            }
+        }
+    }
+
+    /// Does this to `path` through `launcher`, warning instead of failing if it can't.
+    pub(crate) fn open(self, path: &Path, launcher: &impl Launcher) -> bool {
+        let (program, args) = self.command(path);
+        match launcher.launch(program, &args) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!("Failed opening `{}` with {program}: {err}", path.display());
+                false
+            }
+        }
+    }
+}
+
+/// What runs the commands opening files, so tests can tell what would have been run.
+pub(crate) trait Launcher {
+    fn launch(&self, program: &str, args: &[OsString]) -> io::Result<()>;
+}
+
+/// Runs the commands, without waiting for them.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SystemLauncher;
+
+impl Launcher for SystemLauncher {
+    fn launch(&self, program: &str, args: &[OsString]) -> io::Result<()> {
+        let mut child = Command::new(program).args(args).spawn()?;
+        let (program, args) = (program.to_owned(), args.to_vec());
+        // Waited for apart from the UI, to tell when no app is associated with a file
+        std::thread::spawn(move || match child.wait() {
+            // Explorer exits with 1 even when it opened the file
+            Ok(status) if !status.success() && program != "explorer" => {
+                tracing::warn!("{program} {args:?} failed, {status}, maybe no app opens it")
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Failed waiting for {program}: {err}"),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) use mock::MockLauncher;
+
+#[cfg(test)]
+mod mock {
+    use std::cell::RefCell;
+    use std::ffi::OsString;
+    use std::io;
+
+    use super::Launcher;
+
+    /// Records what it's asked to run without running anything, failing if told to.
+    #[derive(Debug, Default)]
+    pub(crate) struct MockLauncher {
+        pub(crate) launched: RefCell<Vec<(String, Vec<OsString>)>>,
+        pub(crate) fail: bool,
+    }
+
+    impl Launcher for MockLauncher {
+        fn launch(&self, program: &str, args: &[OsString]) -> io::Result<()> {
+            self.launched
+                .borrow_mut()
+                .push((program.to_owned(), args.to_vec()));
+            if self.fail {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{MockLauncher, OpenAction};
+
+    #[test]
+    fn actions_dispatch_their_command() {
+        let path = Path::new("/home/user/movie.mkv");
+        let launcher = MockLauncher::default();
+        assert!(OpenAction::File.open(path, &launcher));
+        assert!(OpenAction::ContainingFolder.open(path, &launcher));
+        let launched = launcher.launched.into_inner();
+        assert_eq!(
+            launched,
+            [
+                OpenAction::File.command(path),
+                OpenAction::ContainingFolder.command(path)
+            ]
+            .map(|(program, args)| (program.to_owned(), args))
+        );
+        if cfg!(target_os = "linux") {
+            assert_eq!(launched[0], ("xdg-open".into(), vec![path.into()]));
+            assert_eq!(launched[1], ("xdg-open".into(), vec!["/home/user".into()]));
+        }
+        if cfg!(windows) {
+            // Straight to explorer, never through a shell
+            assert_eq!(launched[0], ("explorer".into(), vec![path.into()]));
+        }
+
+        // A launcher that can't run only gets warned about
+        let failing = MockLauncher {
+            fail: true,
+            ..Default::default()
+        };
+        assert!(!OpenAction::File.open(path, &failing));
+        assert_eq!(failing.launched.borrow().len(), 1);
+    }
+}
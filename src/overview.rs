@@ -0,0 +1,423 @@
+use std::path::PathBuf;
+
+use im::{HashSet, Vector};
+
+use crate::extensions::without_extensions;
+use crate::file_system::{FileNode, Metric};
+use crate::histogram::in_size_bucket;
+use crate::units::plural;
+
+/// Which kinds of nodes the visualization shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ViewFilter {
+    /// Direct children of the root, directories sized by their contents
+    All,
+    /// Every file in the tree
+    #[default]
+    Files,
+    /// Direct subdirectories of the root, sized by their contents
+    Directories,
+}
+
+impl ViewFilter {
+    pub(crate) fn visible_nodes(self, root: &FileNode) -> Vector<FileNode> {
+        let children = match root {
+            FileNode::Directory { children, .. } => children.clone(),
+            FileNode::File { .. } | FileNode::Symlink { .. } | FileNode::Aggregate { .. } => {
+                Vector::unit(root.clone())
+            }
+        };
+        match self {
+            ViewFilter::All => children,
+            ViewFilter::Files => root.clone().into_vector(),
+            ViewFilter::Directories => children
+                .into_iter()
+                .filter(|node| matches!(node, FileNode::Directory { .. }))
+                .collect(),
+        }
+    }
+}
+
+/// How finely the overview breaks down the shown directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OverviewGranularity {
+    /// Whatever the [`ViewFilter`] picks
+    #[default]
+    Files,
+    /// One segment per direct child, directories sized by their contents, that clicking drills
+    /// into
+    TopLevelDirs,
+}
+
+/// What of a directory the overview shows, apart from how it's drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ViewOptions {
+    pub(crate) view_filter: ViewFilter,
+    pub(crate) granularity: OverviewGranularity,
+    /// Show the files directly in the directory as one node
+    pub(crate) group_root_files: bool,
+    pub(crate) metric: Metric,
+    /// Most nodes to show, smaller ones are folded into one
+    pub(crate) max_rects: usize,
+    /// Extensions of files left out
+    pub(crate) hidden_extensions: HashSet<String>,
+    /// The only size bucket files are shown of, if any
+    pub(crate) size_bucket: Option<usize>,
+}
+
+/// A visible node's share of the overview, for drawing it with any toolkit.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    pub(crate) path: PathBuf,
+    /// What the node measures in the chosen metric
+    pub(crate) size: u64,
+    /// Where it starts and ends, from 0.0 to 1.0 of the whole overview
+    pub(crate) start_fraction: f64,
+    pub(crate) end_fraction: f64,
+    pub(crate) is_dir: bool,
+}
+
+/// The overview of `root` as `options` show it, one segment per visible node in order.
+pub(crate) fn compute_segments(root: &FileNode, options: &ViewOptions) -> Vec<Segment> {
+    let files = visible_nodes(root, options);
+    files
+        .nodes
+        .iter()
+        .zip(&files.sizes)
+        .zip(fractions(&files.sizes))
+        .map(|((node, size), (start_fraction, end_fraction))| Segment {
+            path: node.path().clone(),
+            size: *size,
+            start_fraction,
+            end_fraction,
+            is_dir: matches!(node, FileNode::Directory { .. }),
+        })
+        .collect()
+}
+
+/// Where each of `sizes` starts and ends as a share of their total, all 0.0 if it's 0.
+///
+/// Each starts where the one before ends, and ends at the exact cumulative size converted to
+/// a share only then, so no float error adds up and the last one ends at exactly 1.0. Sizes
+/// are added up wider than `u64`, so even totals beyond it can't overflow.
+pub(crate) fn fractions(sizes: &[u64]) -> Vec<(f64, f64)> {
+    let total: u128 = sizes.iter().map(|size| u128::from(*size)).sum();
+    let fraction = |size: u128| {
+        if total > 0 {
+            size as f64 / total as f64
+        } else {
+            0.0
+        }
+    };
+    let (mut done, mut end) = (0, 0.0);
+    sizes
+        .iter()
+        .map(|size| {
+            let start = end;
+            done += u128::from(*size);
+            end = fraction(done);
+            (start, end)
+        })
+        .collect()
+}
+
+/// The visible nodes with what painting them needs, computed whenever they change.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VisibleNodes {
+    pub(crate) nodes: Vector<FileNode>,
+    /// What each node measures in the chosen metric
+    pub(crate) sizes: Vec<u64>,
+    /// Whether there were too many nodes, so the smallest are folded into one
+    pub(crate) folded: bool,
+}
+
+impl VisibleNodes {
+    pub(crate) fn new(nodes: Vector<FileNode>, metric: Metric) -> Self {
+        let sizes = nodes.iter().map(|node| metric.of(node)).collect();
+        Self::with_sizes(nodes, sizes)
+    }
+
+    pub(crate) fn with_sizes(nodes: Vector<FileNode>, sizes: Vec<u64>) -> Self {
+        Self {
+            nodes,
+            sizes,
+            folded: false,
+        }
+    }
+}
+
+/// `root` without the files that hidden extensions and the chosen size bucket leave out, `None`
+/// if nothing is left out.
+pub(crate) fn filtered_tree(root: &FileNode, options: &ViewOptions) -> Option<FileNode> {
+    let mut filtered = None;
+    if !options.hidden_extensions.is_empty() {
+        filtered = Some(without_extensions(root, &options.hidden_extensions));
+    }
+    if let Some(bucket) = options.size_bucket {
+        filtered = Some(in_size_bucket(filtered.as_ref().unwrap_or(root), bucket));
+    }
+    filtered
+}
+
+/// The nodes of `root` that `options` show, leaving out hidden extensions.
+pub(crate) fn visible_nodes(root: &FileNode, options: &ViewOptions) -> VisibleNodes {
+    let view_filter = match options.granularity {
+        OverviewGranularity::Files => options.view_filter,
+        OverviewGranularity::TopLevelDirs => ViewFilter::All,
+    };
+    let filtered = filtered_tree(root, options);
+    let root = filtered.as_ref().unwrap_or(root);
+    let nodes = view_filter.visible_nodes(root);
+    let files = if options.group_root_files && view_filter == ViewFilter::All {
+        group_direct_files(root, nodes, options.metric)
+    } else {
+        VisibleNodes::new(nodes, options.metric)
+    };
+    cap_nodes(files, options.max_rects)
+}
+
+/// The direct children `nodes` of `root` with its files replaced by one node standing in for
+/// all of them, after the subdirectories.
+fn group_direct_files(root: &FileNode, nodes: Vector<FileNode>, metric: Metric) -> VisibleNodes {
+    if !matches!(root, FileNode::Directory { .. }) {
+        return VisibleNodes::new(nodes, metric);
+    }
+    let (directories, files): (Vector<_>, Vector<_>) = nodes
+        .into_iter()
+        .partition(|node| matches!(node, FileNode::Directory { .. }));
+    let mut grouped = VisibleNodes::new(directories, metric);
+    if files.is_empty() {
+        return grouped;
+    }
+    // Links and stand-ins are grouped along, but only files are counted in the name
+    let file_count = files
+        .iter()
+        .filter(|node| matches!(node, FileNode::File { .. }))
+        .count() as u64;
+    grouped.nodes.push_back(FileNode::Aggregate {
+        path: root.path().join(format!(
+            "{file_count} {}",
+            plural(file_count, "file", "files")
+        )),
+        size: root.direct_file_size(),
+        count: files.iter().map(FileNode::descendant_file_count).sum(),
+    });
+    grouped
+        .sizes
+        .push(files.iter().map(|file| metric.of(file)).sum());
+    VisibleNodes::with_sizes(grouped.nodes, grouped.sizes)
+}
+
+/// Keeps the `max` largest of `files` in their order, the last of them replaced by a node
+/// standing in for all the smaller ones if there are too many.
+pub(crate) fn cap_nodes(files: VisibleNodes, max: usize) -> VisibleNodes {
+    // The node standing in for the others takes one place
+    let max = max.max(1);
+    if files.nodes.len() <= max {
+        return files;
+    }
+    let mut by_size: Vec<_> = files.sizes.iter().enumerate().collect();
+    // Stable, so equally large nodes are kept in order
+    by_size.sort_by_key(|(_, size)| std::cmp::Reverse(**size));
+    let mut kept = vec![false; files.nodes.len()];
+    for (index, _) in &by_size[..max - 1] {
+        kept[*index] = true;
+    }
+
+    let mut capped = Vector::new();
+    let mut sizes = Vec::new();
+    let (mut others, mut others_bytes, mut others_size, mut others_files) = (0, 0, 0, 0);
+    for ((node, size), kept) in files.nodes.into_iter().zip(files.sizes).zip(kept) {
+        if kept {
+            capped.push_back(node);
+            sizes.push(size);
+        } else {
+            others += 1;
+            others_bytes += node.size();
+            others_size += size;
+            others_files += node.descendant_file_count();
+        }
+    }
+    capped.push_back(FileNode::Aggregate {
+        path: format!("{others} others").into(),
+        size: others_bytes,
+        count: others_files,
+    });
+    sizes.push(others_size);
+    VisibleNodes {
+        folded: true,
+        ..VisibleNodes::with_sizes(capped, sizes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use im::{vector, HashSet};
+
+    use super::{
+        cap_nodes, compute_segments, fractions, OverviewGranularity, Segment, ViewFilter,
+        ViewOptions, VisibleNodes,
+    };
+    use crate::file_system::{FileNode, Metric};
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    fn aggregate(path: &str, size: u64, count: u64) -> FileNode {
+        FileNode::Aggregate {
+            path: path.into(),
+            size,
+            count,
+        }
+    }
+
+    fn tree() -> FileNode {
+        FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![file("/a/1.rs", 1), file("/a/3.txt", 3)],
+                },
+                file("/2.rs", 2),
+                file("/4.txt", 4),
+            ],
+        }
+    }
+
+    #[test]
+    fn segments_under_view_options() {
+        let options = ViewOptions {
+            view_filter: ViewFilter::All,
+            granularity: OverviewGranularity::Files,
+            group_root_files: false,
+            metric: Metric::Bytes,
+            max_rects: 10_000,
+            hidden_extensions: HashSet::new(),
+            size_bucket: None,
+        };
+        let segment = |path: &str, size, start_fraction, end_fraction, is_dir| Segment {
+            path: path.into(),
+            size,
+            start_fraction,
+            end_fraction,
+            is_dir,
+        };
+        assert_eq!(
+            compute_segments(&tree(), &options),
+            [
+                segment("/a", 4, 0.0, 0.4, true),
+                segment("/2.rs", 2, 0.4, 0.6, false),
+                segment("/4.txt", 4, 0.6, 1.0, false),
+            ]
+        );
+
+        let paths = |options: &ViewOptions| -> Vec<_> {
+            compute_segments(&tree(), options)
+                .into_iter()
+                .map(|segment| (segment.path.display().to_string(), segment.size))
+                .collect()
+        };
+        let every_file = ViewOptions {
+            view_filter: ViewFilter::Files,
+            ..options.clone()
+        };
+        assert_eq!(
+            paths(&every_file),
+            [
+                ("/a/1.rs".into(), 1),
+                ("/a/3.txt".into(), 3),
+                ("/2.rs".into(), 2),
+                ("/4.txt".into(), 4)
+            ]
+        );
+        let by_count = ViewOptions {
+            metric: Metric::FileCount,
+            ..options.clone()
+        };
+        assert_eq!(
+            paths(&by_count),
+            [("/a".into(), 2), ("/2.rs".into(), 1), ("/4.txt".into(), 1)]
+        );
+        let without_txt = ViewOptions {
+            hidden_extensions: ["txt".to_owned()].into_iter().collect(),
+            ..options.clone()
+        };
+        assert_eq!(paths(&without_txt), [("/a".into(), 1), ("/2.rs".into(), 2)]);
+        let grouped = ViewOptions {
+            group_root_files: true,
+            ..options.clone()
+        };
+        assert_eq!(paths(&grouped), [("/a".into(), 4), ("/2 files".into(), 6)]);
+        let linked = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                file("/1", 1),
+                FileNode::Symlink {
+                    path: "/link".into(),
+                    target: "/1".into(),
+                    broken: false,
+                },
+            ],
+        };
+        assert_eq!(
+            compute_segments(&linked, &grouped),
+            [segment("/1 file", 1, 0.0, 1.0, false)]
+        );
+        let capped = ViewOptions {
+            max_rects: 2,
+            ..options.clone()
+        };
+        assert_eq!(paths(&capped), [("/a".into(), 4), ("2 others".into(), 6)]);
+        let top_level = ViewOptions {
+            granularity: OverviewGranularity::TopLevelDirs,
+            ..every_file
+        };
+        assert_eq!(paths(&top_level), paths(&options));
+
+        // Nothing to measure leaves every segment empty rather than dividing by zero
+        let empty = FileNode::Directory {
+            path: "/".into(),
+            children: vector![file("/0", 0)],
+        };
+        assert_eq!(
+            compute_segments(&empty, &options),
+            [segment("/0", 0, 0.0, 0.0, false)]
+        );
+    }
+
+    #[test]
+    fn huge_totals_end_exactly_at_one() {
+        let sizes = [u64::MAX / 2, 1, u64::MAX / 3, u64::MAX / 2, 3];
+        let shares = fractions(&sizes);
+        assert_eq!(shares.first().unwrap().0, 0.0);
+        assert_eq!(shares.last().unwrap().1, 1.0);
+        for pair in shares.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        assert_eq!(fractions(&[u64::MAX, u64::MAX]), [(0.0, 0.5), (0.5, 1.0)]);
+    }
+
+    #[test]
+    fn smallest_nodes_are_folded_into_others() {
+        let nodes = vector![
+            file("/5", 5),
+            file("/1", 1),
+            file("/4", 4),
+            file("/2", 2),
+            file("/3", 3)
+        ];
+        let capped = |max| cap_nodes(VisibleNodes::new(nodes.clone(), Metric::Bytes), max);
+        assert_eq!(capped(5).nodes, nodes);
+        assert_eq!(
+            capped(3).nodes,
+            vector![file("/5", 5), file("/4", 4), aggregate("3 others", 6, 3)]
+        );
+        assert_eq!(capped(3).sizes, [5, 4, 6]);
+        assert_eq!(capped(1).nodes, vector![aggregate("5 others", 15, 5)]);
+    }
+}
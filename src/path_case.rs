@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use druid::Data;
+
+/// Whether paths differing only in case are the same path when they're used as keys.
+///
+/// Defaults to what the platform's filesystems usually do, insensitive on macOS and Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum PathCase {
+    Sensitive,
+    Insensitive,
+}
+
+impl Default for PathCase {
+    fn default() -> Self {
+        if cfg!(any(target_os = "macos", target_os = "windows")) {
+            PathCase::Insensitive
+        } else {
+            PathCase::Sensitive
+        }
+    }
+}
+
+impl PathCase {
+    /// What `path` is keyed by. Paths that aren't valid Unicode are kept as they are.
+    pub(crate) fn key(self, path: &Path) -> PathBuf {
+        match (self, path.to_str()) {
+            (PathCase::Insensitive, Some(path)) => path.to_lowercase().into(),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    pub(crate) fn is_same_path(self, a: &Path, b: &Path) -> bool {
+        self.key(a) == self.key(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::PathCase;
+
+    #[test]
+    fn keys_by_case() {
+        let (upper, lower) = (Path::new("/Docs/Foo.txt"), Path::new("/docs/foo.txt"));
+        assert!(!PathCase::Sensitive.is_same_path(upper, lower));
+        assert!(PathCase::Sensitive.is_same_path(upper, upper));
+        assert!(PathCase::Insensitive.is_same_path(upper, lower));
+        assert_eq!(PathCase::Insensitive.key(upper), lower);
+        assert!(!PathCase::Insensitive.is_same_path(upper, Path::new("/docs/foo.txt.bak")));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use druid::im::Vector;
+
+use crate::file_system::FileNode;
+use crate::metadata::{EntryKind, MetadataProvider};
+
+/// The files of a newline-separated list of paths, like `find` or `fd` print, as a flat tree
+/// under the deepest directory they all share.
+///
+/// Listed directories are left out, their files are in the list themselves if they're wanted.
+/// Paths whose metadata can't be read are skipped, and so is everything that isn't a file or
+/// a symlink. `None` if no path is left.
+pub(crate) fn nodes_from_paths(
+    reader: impl BufRead,
+    fs: &impl MetadataProvider,
+) -> Option<FileNode> {
+    let mut nodes = BTreeMap::new();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::warn!("Stopped reading paths: {err}");
+                break;
+            }
+        };
+        let path = PathBuf::from(line.trim_end_matches('\r'));
+        if path.as_os_str().is_empty() || nodes.contains_key(&path) {
+            continue;
+        }
+        let metadata = match fs.symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                tracing::warn!("Skipping `{}`: {err}", path.display());
+                continue;
+            }
+        };
+        let node = match metadata.kind {
+            EntryKind::File => FileNode::File {
+                path: path.clone(),
+                size: Some(metadata.len),
+            },
+            EntryKind::Symlink => match fs.read_link(&path) {
+                Ok(target) => FileNode::Symlink {
+                    broken: fs.metadata(&path).is_err(),
+                    path: path.clone(),
+                    target,
+                },
+                Err(err) => {
+                    tracing::warn!("Skipping `{}`: {err}", path.display());
+                    continue;
+                }
+            },
+            EntryKind::Directory | EntryKind::ReparsePoint | EntryKind::Special => continue,
+        };
+        nodes.insert(path, node);
+    }
+    let root = common_ancestor(nodes.keys().map(PathBuf::as_path))?;
+    Some(FileNode::Directory {
+        path: root,
+        children: nodes.into_values().collect::<Vector<_>>(),
+    })
+}
+
+/// The deepest directory containing all of `paths`.
+fn common_ancestor<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let first = paths.next()?;
+    let mut ancestor = first.parent().unwrap_or(first).to_path_buf();
+    for path in paths {
+        while !path.starts_with(&ancestor) {
+            if !ancestor.pop() {
+                break;
+            }
+        }
+    }
+    Some(ancestor)
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+
+    use super::nodes_from_paths;
+    use crate::file_system::FileNode;
+    use crate::metadata::MemoryFs;
+
+    #[test]
+    fn files_from_a_path_list() {
+        let fs = MemoryFs::default()
+            .dir("/home")
+            .dir("/home/docs")
+            .file("/home/docs/a.txt", 3)
+            .dir("/home/music")
+            .file("/home/music/b.mp3", 5)
+            .symlink("/home/music/link", "b.mp3");
+        let list = "/home/music/b.mp3\n/home/docs/a.txt\r\n/home/gone\n\n/home/docs\n\
+                    /home/music/link\n/home/docs/a.txt\n";
+        assert_eq!(
+            nodes_from_paths(list.as_bytes(), &fs),
+            Some(FileNode::Directory {
+                path: "/home".into(),
+                children: vector![
+                    FileNode::File {
+                        path: "/home/docs/a.txt".into(),
+                        size: Some(3),
+                    },
+                    FileNode::File {
+                        path: "/home/music/b.mp3".into(),
+                        size: Some(5),
+                    },
+                    FileNode::Symlink {
+                        path: "/home/music/link".into(),
+                        target: "b.mp3".into(),
+                        broken: false,
+                    },
+                ],
+            })
+        );
+        assert_eq!(nodes_from_paths("/home/gone\n".as_bytes(), &fs), None);
+    }
+}
@@ -0,0 +1,129 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+use druid::im::HashMap;
+
+const LAST_FOLDER: &str = "last_folder";
+const SCAN_ESTIMATES: &str = "scan_estimates";
+const CHECKPOINT: &str = "checkpoint.json";
+
+/// Where persistent state lives: settings in the config directory, anything that can be
+/// recomputed in the cache directory. Directories are created when first written to.
+#[derive(Debug, Clone)]
+pub(crate) struct AppDirs {
+    config: PathBuf,
+    cache: PathBuf,
+}
+
+impl AppDirs {
+    /// The platform's directories for this app, `None` if there's no home directory.
+    pub(crate) fn new() -> Option<Self> {
+        ProjectDirs::from("", "", "rs-dir-stat")
+            .map(|dirs| Self::with_roots(dirs.config_dir(), dirs.cache_dir()))
+    }
+
+    fn with_roots(config: impl Into<PathBuf>, cache: impl Into<PathBuf>) -> Self {
+        Self {
+            config: config.into(),
+            cache: cache.into(),
+        }
+    }
+
+    pub(crate) fn config_dir(&self) -> io::Result<&Path> {
+        std::fs::create_dir_all(&self.config)?;
+        Ok(&self.config)
+    }
+
+    pub(crate) fn cache_dir(&self) -> io::Result<&Path> {
+        std::fs::create_dir_all(&self.cache)?;
+        Ok(&self.cache)
+    }
+
+    pub(crate) fn last_folder(&self) -> io::Result<Option<String>> {
+        read_optional(&self.config.join(LAST_FOLDER))
+    }
+
+    pub(crate) fn save_last_folder(&self, folder: &str) -> io::Result<()> {
+        std::fs::write(self.config_dir()?.join(LAST_FOLDER), folder)
+    }
+
+    /// File counts of previous scans, by folder.
+    pub(crate) fn scan_estimates(&self) -> io::Result<HashMap<String, u64>> {
+        let Some(text) = read_optional(&self.cache.join(SCAN_ESTIMATES))? else {
+            return Ok(HashMap::new());
+        };
+        Ok(text
+            .lines()
+            .filter_map(|line| {
+                let (count, folder) = line.split_once('\t')?;
+                Some((folder.to_owned(), count.parse().ok()?))
+            })
+            .collect())
+    }
+
+    pub(crate) fn save_scan_estimates(&self, estimates: &HashMap<String, u64>) -> io::Result<()> {
+        let text = estimates
+            .iter()
+            .map(|(folder, count)| format!("{count}\t{folder}\n"))
+            .collect::<String>();
+        std::fs::write(self.cache_dir()?.join(SCAN_ESTIMATES), text)
+    }
+
+    /// Where a running scan saves its progress.
+    pub(crate) fn checkpoint_file(&self) -> io::Result<PathBuf> {
+        Ok(self.cache_dir()?.join(CHECKPOINT))
+    }
+}
+
+/// Reads `path` to a string, `None` if it doesn't exist.
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::hashmap;
+
+    use super::AppDirs;
+
+    #[test]
+    fn platform_dirs_are_app_specific() {
+        let Some(dirs) = AppDirs::new() else {
+            return;
+        };
+        assert!(dirs.config.ends_with("rs-dir-stat"));
+        assert!(dirs.cache.ends_with("rs-dir-stat"));
+        #[cfg(target_os = "linux")]
+        assert_ne!(dirs.config, dirs.cache);
+    }
+
+    #[test]
+    fn dirs_are_created_on_first_use() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = AppDirs::with_roots(root.path().join("config"), root.path().join("a/cache"));
+        assert_eq!(dirs.last_folder().unwrap(), None);
+        assert!(!root.path().join("config").exists());
+
+        assert_eq!(dirs.config_dir().unwrap(), root.path().join("config"));
+        assert_eq!(dirs.cache_dir().unwrap(), root.path().join("a/cache"));
+        assert!(root.path().join("config").is_dir());
+        assert!(root.path().join("a/cache").is_dir());
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = AppDirs::with_roots(root.path().join("config"), root.path().join("cache"));
+        dirs.save_last_folder("/home/me").unwrap();
+        assert_eq!(dirs.last_folder().unwrap().as_deref(), Some("/home/me"));
+
+        let estimates = hashmap! {"/a".to_owned() => 3, "/b\twith tab".to_owned() => 5};
+        dirs.save_scan_estimates(&estimates).unwrap();
+        assert_eq!(dirs.scan_estimates().unwrap(), estimates);
+    }
+}
@@ -0,0 +1,89 @@
+use druid::{Color, LifeCycle, Rect, RenderContext, Size, Widget};
+
+/// Height of the bar in logical pixels
+const BAR_HEIGHT: f64 = 6.0;
+/// Width of the sliding block while the total is unknown, as a fraction of the bar
+const INDETERMINATE_WIDTH: f64 = 0.25;
+/// Seconds the sliding block takes to cross the bar once
+const INDETERMINATE_PERIOD: f64 = 1.5;
+
+/// Progress of a running scan.
+///
+/// Determinate when a previous scan of the same folder gave an estimate of the file count,
+/// otherwise an animated block slides across the bar until the scan completes.
+#[derive(Debug, Default)]
+pub(crate) struct ScanProgressBar {
+    /// Position of the indeterminate block, from 0.0 to 1.0
+    phase: f64,
+}
+
+impl Widget<crate::AppState> for ScanProgressBar {
+    fn event(
+        &mut self,
+        ctx: &mut druid::EventCtx,
+        event: &druid::Event,
+        data: &mut crate::AppState,
+        _env: &druid::Env,
+    ) {
+        if let druid::Event::AnimFrame(interval) = event {
+            if data.scanning {
+                self.phase = (self.phase + *interval as f64 / 1e9 / INDETERMINATE_PERIOD) % 1.0;
+                ctx.request_paint();
+                ctx.request_anim_frame();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &crate::AppState,
+        data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+        if data.scanning && !old_data.scanning {
+            self.phase = 0.0;
+            ctx.request_anim_frame();
+        }
+        if data.scanning != old_data.scanning || data.files_scanned != old_data.files_scanned {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &druid::BoxConstraints,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) -> Size {
+        bc.constrain(Size::new(bc.max().width, BAR_HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &crate::AppState, _env: &druid::Env) {
+        if !data.scanning {
+            return;
+        }
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::grey(0.2));
+
+        let (start, end) = match data.scan_fraction() {
+            Some(fraction) => (0.0, fraction),
+            None => {
+                let start = self.phase * (1.0 + INDETERMINATE_WIDTH) - INDETERMINATE_WIDTH;
+                (start.max(0.0), (start + INDETERMINATE_WIDTH).min(1.0))
+            }
+        };
+        let bar = Rect::new(size.width * start, 0.0, size.width * end, size.height);
+        ctx.fill(bar, &Color::rgb(0.3, 0.5, 1.0));
+    }
+}
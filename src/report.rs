@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use crate::file_system::FileNode;
+use crate::units::{format_size, plural};
+
+/// An indented text report of `root`, one line per node with its size.
+///
+/// Children smaller than `min_size` bytes aren't expanded, each directory's small children are
+/// collapsed into one `... (N items, X total)` line instead. With 0 everything is expanded.
+pub(crate) fn render_tree_text(root: &FileNode, min_size: u64) -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{} {}",
+        root.path().display(),
+        format_size(root.size())
+    );
+    render_children(&mut text, root, min_size, 1);
+    text
+}
+
+fn render_children(text: &mut String, node: &FileNode, min_size: u64, depth: usize) {
+    let FileNode::Directory { children, .. } = node else {
+        return;
+    };
+    let indent = "  ".repeat(depth);
+    let (mut collapsed, mut collapsed_size) = (0, 0);
+    for child in children {
+        let size = child.size();
+        if size < min_size {
+            collapsed += 1;
+            collapsed_size += size;
+            continue;
+        }
+        let name = child
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let _ = writeln!(text, "{indent}{name} {}", format_size(size));
+        render_children(text, child, min_size, depth + 1);
+    }
+    if collapsed > 0 {
+        let _ = writeln!(
+            text,
+            "{indent}... ({collapsed} {}, {} total)",
+            plural(collapsed, "item", "items"),
+            format_size(collapsed_size)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+
+    use super::render_tree_text;
+    use crate::file_system::FileNode;
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn small_subtrees_collapse() {
+        let root = FileNode::Directory {
+            path: "/data".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/data/cache".into(),
+                    children: vector![file("/data/cache/a", 10), file("/data/cache/b", 20)],
+                },
+                FileNode::Directory {
+                    path: "/data/videos".into(),
+                    children: vector![
+                        file("/data/videos/big.mkv", 5000),
+                        file("/data/videos/tiny", 1)
+                    ],
+                },
+                file("/data/notes.txt", 5),
+            ],
+        };
+        assert_eq!(
+            render_tree_text(&root, 100),
+            "/data 4.9 KiB\n\
+             \x20 videos 4.9 KiB\n\
+             \x20   big.mkv 4.9 KiB\n\
+             \x20   ... (1 item, 1 B total)\n\
+             \x20 ... (2 items, 35 B total)\n"
+        );
+        assert_eq!(render_tree_text(&root, 0).lines().count(), 8);
+    }
+}
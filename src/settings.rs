@@ -0,0 +1,365 @@
+use druid::text::ParseFormatter;
+use druid::widget::{
+    Button, Checkbox, CrossAxisAlignment, Either, Flex, Label, Maybe, RadioGroup, SizedBox, Slider,
+    TextBox,
+};
+use druid::{Color, Data, Lens, LensExt, Widget, WidgetExt};
+
+use crate::color::{ColorMode, Gradient, HexColorFormatter, Palette};
+use crate::file_system::{ReparsePolicy, RetryPolicy, ScanOptions, SizeKind};
+use crate::path_case::PathCase;
+use crate::treemap::{TreemapAlgo, TreemapOrder};
+use crate::visualization_widget::SelectionStyle;
+use crate::AppState;
+
+/// The options of [`AppState`] the settings panel edits, applied all at once.
+#[derive(Debug, Clone, PartialEq, Data, Lens)]
+pub(crate) struct Settings {
+    pub(crate) thousands_separator: char,
+    pub(crate) max_rects: usize,
+    pub(crate) click_tolerance_px: f64,
+    pub(crate) show_labels: bool,
+    pub(crate) max_label_chars: usize,
+    pub(crate) show_tooltips: bool,
+    pub(crate) tooltip_width: f64,
+    pub(crate) relative_paths: bool,
+    pub(crate) treemap_algo: TreemapAlgo,
+    pub(crate) treemap_order: TreemapOrder,
+    pub(crate) treemap_padding: f64,
+    pub(crate) directory_borders: bool,
+    pub(crate) selection_style: SelectionStyle,
+    pub(crate) black_or_white_outlines: bool,
+    pub(crate) min_outline_contrast: f64,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) palette: Palette,
+    pub(crate) gradient: Gradient,
+    pub(crate) cushioned: bool,
+    pub(crate) directory_tint: f64,
+    pub(crate) scan_options: ScanOptions,
+    pub(crate) only_extensions: String,
+    pub(crate) prune_empty_files: bool,
+    pub(crate) auto_rescan_secs: Option<u64>,
+    pub(crate) animate_rescans: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::of(&AppState::default())
+    }
+}
+
+impl Settings {
+    /// The settings `state` has now.
+    pub(crate) fn of(state: &AppState) -> Self {
+        Self {
+            thousands_separator: state.thousands_separator,
+            max_rects: state.max_rects,
+            click_tolerance_px: state.click_tolerance_px,
+            show_labels: state.show_labels,
+            max_label_chars: state.max_label_chars,
+            show_tooltips: state.show_tooltips,
+            tooltip_width: state.tooltip_width,
+            relative_paths: state.relative_paths,
+            treemap_algo: state.treemap_algo,
+            treemap_order: state.treemap_order,
+            treemap_padding: state.treemap_padding,
+            directory_borders: state.directory_borders,
+            selection_style: state.selection_style,
+            black_or_white_outlines: state.black_or_white_outlines,
+            min_outline_contrast: state.min_outline_contrast,
+            color_mode: state.color_mode,
+            palette: state.palette,
+            gradient: state.gradient.clone(),
+            cushioned: state.cushioned,
+            directory_tint: state.directory_tint,
+            scan_options: state.scan_options.clone(),
+            only_extensions: state.only_extensions.clone(),
+            prune_empty_files: state.prune_empty_files,
+            auto_rescan_secs: state.auto_rescan_secs,
+            animate_rescans: state.animate_rescans,
+        }
+    }
+
+    /// Sets every option of `state` to these settings.
+    pub(crate) fn apply_to(self, state: &mut AppState) {
+        state.thousands_separator = self.thousands_separator;
+        state.max_rects = self.max_rects;
+        state.click_tolerance_px = self.click_tolerance_px;
+        state.show_labels = self.show_labels;
+        state.max_label_chars = self.max_label_chars;
+        state.show_tooltips = self.show_tooltips;
+        state.tooltip_width = self.tooltip_width;
+        state.relative_paths = self.relative_paths;
+        state.treemap_algo = self.treemap_algo;
+        state.treemap_order = self.treemap_order;
+        state.treemap_padding = self.treemap_padding;
+        state.directory_borders = self.directory_borders;
+        state.selection_style = self.selection_style;
+        state.black_or_white_outlines = self.black_or_white_outlines;
+        state.min_outline_contrast = self.min_outline_contrast;
+        state.color_mode = self.color_mode;
+        state.palette = self.palette;
+        state.gradient = self.gradient;
+        state.cushioned = self.cushioned;
+        state.directory_tint = self.directory_tint;
+        state.scan_options = self.scan_options;
+        state.only_extensions = self.only_extensions;
+        state.prune_empty_files = self.prune_empty_files;
+        state.auto_rescan_secs = self.auto_rescan_secs;
+        state.animate_rescans = self.animate_rescans;
+    }
+}
+
+/// A text box for a number of at most `width` pixels.
+fn number<T: Data + std::fmt::Debug + std::fmt::Display + std::str::FromStr>(
+    lens: impl Lens<Settings, T> + 'static,
+    width: f64,
+) -> impl Widget<Settings>
+where
+    <T as std::str::FromStr>::Err: std::error::Error + 'static,
+{
+    TextBox::new()
+        .with_formatter(ParseFormatter::new())
+        .lens(lens)
+        .fix_width(width)
+}
+
+/// A row of `title` followed by the settings in it.
+fn group(title: &str) -> Flex<Settings> {
+    Flex::row().with_child(Label::new(title).fix_width(80.0))
+}
+
+fn gradient_editor() -> impl Widget<Settings> {
+    fn endpoint(lens: impl Lens<Gradient, Color> + 'static) -> impl Widget<Settings> {
+        TextBox::new()
+            .with_formatter(HexColorFormatter)
+            .lens(Settings::gradient.then(lens))
+            .fix_width(70.0)
+    }
+    Flex::row()
+        .with_child(Label::new("Gradient"))
+        .with_child(endpoint(Gradient::start))
+        .with_child(Label::new("to"))
+        .with_child(endpoint(Gradient::end))
+        .with_child(
+            Button::new("From palette").on_click(|_ctx, settings: &mut Settings, _env| {
+                settings.gradient = settings.palette.gradient()
+            }),
+        )
+        .disabled_if(|settings: &Settings, _env| settings.color_mode != ColorMode::Position)
+}
+
+fn display_settings() -> impl Widget<Settings> {
+    group("Display")
+        .with_child(
+            RadioGroup::row(vec![("1,000", ','), ("1.000", '.'), ("1 000", ' ')])
+                .lens(Settings::thousands_separator),
+        )
+        .with_child(Label::new("Max rectangles"))
+        .with_child(number(Settings::max_rects, 60.0))
+        .with_child(Label::new("Click tolerance"))
+        .with_child(number(Settings::click_tolerance_px, 40.0))
+        .with_child(Checkbox::new("Labels up to").lens(Settings::show_labels))
+        .with_child(
+            number(Settings::max_label_chars, 40.0)
+                .disabled_if(|settings: &Settings, _env| !settings.show_labels),
+        )
+        .with_child(Checkbox::new("Tooltips wrapped at").lens(Settings::show_tooltips))
+        .with_child(
+            number(Settings::tooltip_width, 50.0)
+                .disabled_if(|settings: &Settings, _env| !settings.show_tooltips),
+        )
+        .with_child(
+            Checkbox::new("Paths relative to the scanned folder").lens(Settings::relative_paths),
+        )
+}
+
+fn treemap_settings() -> impl Widget<Settings> {
+    group("Treemap")
+        .with_child(
+            RadioGroup::row(vec![
+                ("Squarified", TreemapAlgo::Squarified),
+                ("Slice and dice", TreemapAlgo::SliceAndDice),
+            ])
+            .lens(Settings::treemap_algo),
+        )
+        .with_child(
+            RadioGroup::row(vec![
+                ("Largest first", TreemapOrder::BySize),
+                ("By name", TreemapOrder::ByPath),
+                ("Folders first", TreemapOrder::DirectoriesFirst),
+            ])
+            .lens(Settings::treemap_order)
+            .disabled_if(|settings: &Settings, _env| {
+                settings.treemap_algo != TreemapAlgo::Squarified
+            }),
+        )
+        .with_child(Label::new("Padding"))
+        .with_child(number(Settings::treemap_padding, 40.0))
+        .with_child(Checkbox::new("Directory borders").lens(Settings::directory_borders))
+}
+
+fn color_settings() -> impl Widget<Settings> {
+    group("Colors")
+        .with_child(
+            RadioGroup::row(vec![
+                ("Outline", SelectionStyle::Outline),
+                ("Dim others", SelectionStyle::DimOthers),
+                ("Both", SelectionStyle::Both),
+            ])
+            .lens(Settings::selection_style),
+        )
+        .with_child(
+            Checkbox::new("Black or white outlines")
+                .lens(Settings::black_or_white_outlines)
+                .disabled_if(|settings: &Settings, _env| !settings.selection_style.outlines()),
+        )
+        .with_child(Label::dynamic(|settings: &Settings, _env| {
+            format!("Outline contrast {:.1}:1", settings.min_outline_contrast)
+        }))
+        .with_child(
+            Slider::new()
+                .with_range(1.0, 4.5)
+                .lens(Settings::min_outline_contrast)
+                .fix_width(60.0)
+                .disabled_if(|settings: &Settings, _env| {
+                    !settings.selection_style.outlines() || settings.black_or_white_outlines
+                }),
+        )
+        .with_child(
+            RadioGroup::row(vec![
+                ("Color by position", ColorMode::Position),
+                ("Color by name", ColorMode::ByNameHash),
+            ])
+            .lens(Settings::color_mode),
+        )
+        .with_child(
+            RadioGroup::row(vec![
+                ("Vivid", Palette::Vivid),
+                ("Color-blind safe", Palette::OkabeIto),
+            ])
+            .lens(Settings::palette),
+        )
+        .with_child(gradient_editor())
+        .with_child(Checkbox::new("Cushions").lens(Settings::cushioned))
+        .with_child(Label::new("Folder tint"))
+        .with_child(Slider::new().lens(Settings::directory_tint).fix_width(60.0))
+}
+
+fn scan_settings() -> impl Widget<Settings> {
+    group("Scanning")
+        .with_child(
+            Checkbox::new("Include special files")
+                .lens(Settings::scan_options.then(ScanOptions::include_special_files)),
+        )
+        .with_child(
+            Checkbox::new("Skip duplicate mounts")
+                .lens(Settings::scan_options.then(ScanOptions::skip_duplicate_mounts))
+                .disabled_if(|_settings: &Settings, _env| !cfg!(target_os = "linux")),
+        )
+        .with_child(
+            Checkbox::new("Scan into archives")
+                .lens(Settings::scan_options.then(ScanOptions::scan_archives))
+                .disabled_if(|_settings: &Settings, _env| !cfg!(feature = "archives")),
+        )
+        .with_child(
+            RadioGroup::row(vec![
+                ("Skip junctions", ReparsePolicy::Skip),
+                ("Flag junctions", ReparsePolicy::Flag),
+                ("Follow junctions", ReparsePolicy::Follow),
+            ])
+            .lens(Settings::scan_options.then(ScanOptions::reparse_points)),
+        )
+        .with_child(
+            RadioGroup::row(vec![
+                ("Apparent sizes", SizeKind::Apparent),
+                ("Compressed sizes", SizeKind::Compressed),
+            ])
+            .lens(Settings::scan_options.then(ScanOptions::size_kind)),
+        )
+        .with_child(
+            TextBox::new()
+                .with_placeholder("Only extensions")
+                .lens(Settings::only_extensions)
+                .fix_width(120.0),
+        )
+        .with_child(Label::new("Fold folders under (B)"))
+        .with_child(number(
+            Settings::scan_options.then(ScanOptions::min_expand_size),
+            80.0,
+        ))
+        .with_child(
+            Checkbox::new("Resume interrupted scan")
+                .lens(Settings::scan_options.then(ScanOptions::resume)),
+        )
+        .with_child(Checkbox::new("Ignore case in paths").lens(
+            Settings::scan_options.then(ScanOptions::path_case).map(
+                |case| *case == PathCase::Insensitive,
+                |case, insensitive| {
+                    *case = if insensitive {
+                        PathCase::Insensitive
+                    } else {
+                        PathCase::Sensitive
+                    }
+                },
+            ),
+        ))
+        .with_child(Label::new("Retries"))
+        .with_child(number(
+            Settings::scan_options
+                .then(ScanOptions::retry)
+                .then(RetryPolicy::retries),
+            30.0,
+        ))
+        .with_child(Label::new("after (ms)"))
+        .with_child(number(
+            Settings::scan_options
+                .then(ScanOptions::retry)
+                .then(RetryPolicy::backoff_ms),
+            50.0,
+        ))
+        .with_child(Checkbox::new("Prune empty files").lens(Settings::prune_empty_files))
+        .with_child(Label::new("Rescan every (s, 0 = off)"))
+        .with_child(number(
+            Settings::auto_rescan_secs.map(
+                |secs| secs.unwrap_or(0),
+                |secs, value| *secs = (value > 0).then_some(value),
+            ),
+            40.0,
+        ))
+        .with_child(Checkbox::new("Animate changes").lens(Settings::animate_rescans))
+}
+
+/// Every option, grouped, edited in [`AppState::settings`] until they're applied.
+pub(crate) fn settings_panel() -> impl Widget<AppState> {
+    let fields = || {
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(display_settings())
+            .with_child(treemap_settings())
+            .with_child(color_settings())
+            .with_child(scan_settings())
+    };
+    let buttons = Flex::row()
+        .with_child(
+            Button::new("Apply")
+                .on_click(|_ctx, state: &mut AppState, _env| state.apply_settings())
+                .disabled_if(|state: &AppState, _env| {
+                    state.settings.as_ref() == Some(&Settings::of(state))
+                }),
+        )
+        .with_child(Button::new("Reset to defaults").on_click(
+            |_ctx, state: &mut AppState, _env| state.settings = Some(Settings::default()),
+        ))
+        .with_child(
+            Button::new("Close").on_click(|_ctx, state: &mut AppState, _env| state.settings = None),
+        );
+    Either::new(
+        |state: &AppState, _env| state.settings.is_some(),
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(Maybe::new(fields, SizedBox::empty).lens(AppState::settings))
+            .with_child(buttons),
+        SizedBox::empty(),
+    )
+}
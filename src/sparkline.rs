@@ -0,0 +1,69 @@
+use druid::{Color, Data, LifeCycle, RenderContext, Size, Widget};
+
+use crate::visualization_widget::{bar_segments, visible_nodes_changed};
+
+/// Height of the sparkline in logical pixels
+const SPARKLINE_HEIGHT: f64 = 8.0;
+
+/// A miniature of the overview bar, small enough for the toolbar, that only shows and can't be
+/// clicked.
+#[derive(Debug, Default)]
+pub(crate) struct Sparkline;
+
+impl Widget<crate::AppState> for Sparkline {
+    fn event(
+        &mut self,
+        _ctx: &mut druid::EventCtx,
+        _event: &druid::Event,
+        _data: &mut crate::AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &crate::AppState,
+        data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+        if visible_nodes_changed(old_data, data)
+            || old_data.color_mode != data.color_mode
+            || old_data.directory_tint != data.directory_tint
+            || !old_data.gradient.same(&data.gradient)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &druid::BoxConstraints,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) -> Size {
+        bc.constrain(Size::new(bc.max().width, SPARKLINE_HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &crate::AppState, _env: &druid::Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::grey(0.2));
+        for (segment, color) in bar_segments(data) {
+            let (start, end) = (
+                segment.start_fraction * size.width,
+                segment.end_fraction * size.width,
+            );
+            ctx.fill(druid::Rect::new(start, 0.0, end, size.height), &color);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use druid::Selector;
+
+use crate::file_list::largest_files;
+use crate::file_system::FileNode;
+use crate::units::format_size;
+
+/// Logs the [`TreeStats`] of the current tree.
+pub(crate) const QUERY_STATS: Selector = Selector::new("rs-dir-stat.query-stats");
+
+/// Files listed in the markdown summary
+const MARKDOWN_TOP_FILES: usize = 10;
+
+/// Summary numbers of a scanned tree.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TreeStats {
+    pub(crate) total_size: u64,
+    pub(crate) file_count: u64,
+    /// Files whose size couldn't be read, left out of `total_size`
+    pub(crate) unknown_size_count: u64,
+    /// Directories including the root
+    pub(crate) directory_count: u64,
+    pub(crate) symlink_count: u64,
+    /// 512 byte blocks allocated on disk, as counted by the scan, 0 if unknown
+    pub(crate) total_blocks: u64,
+    /// One inode per file, directory and symlink, ignoring hard links
+    pub(crate) inode_estimate: u64,
+}
+
+impl TreeStats {
+    pub(crate) fn of(root: &FileNode) -> Self {
+        let mut stats = TreeStats::default();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            match node {
+                FileNode::Directory { children, .. } => {
+                    stats.directory_count += 1;
+                    stack.extend(children);
+                }
+                FileNode::File { size, .. } => {
+                    stats.file_count += 1;
+                    match size {
+                        Some(size) => stats.total_size += size,
+                        None => stats.unknown_size_count += 1,
+                    }
+                }
+                FileNode::Symlink { .. } => stats.symlink_count += 1,
+                FileNode::Aggregate { size, count, .. } => {
+                    stats.file_count += count;
+                    stats.total_size += size;
+                }
+            }
+        }
+        stats.inode_estimate = stats.file_count + stats.directory_count + stats.symlink_count;
+        stats
+    }
+}
+
+/// A markdown summary of the scan of `folder`, for pasting into issues or docs.
+pub(crate) fn stats_markdown(root: &FileNode, folder: &str) -> String {
+    let stats = TreeStats::of(root);
+    let mut markdown = format!(
+        "## Disk usage of `{folder}`\n\n\
+         - Total size: {}\n\
+         - Files: {}\n\
+         - Directories: {}\n\n\
+         | File | Size |\n\
+         | --- | ---: |\n",
+        format_size(stats.total_size),
+        stats.file_count,
+        stats.directory_count,
+    );
+    for file in largest_files(root, MARKDOWN_TOP_FILES) {
+        let path = file.path.strip_prefix(root.path()).unwrap_or(&file.path);
+        // A pipe would end the cell early
+        let path = path.display().to_string().replace('|', "\\|");
+        markdown.push_str(&format!("| `{path}` | {} |\n", format_size(file.size)));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+
+    use super::{stats_markdown, TreeStats};
+    use crate::file_system::FileNode;
+    use crate::AppState;
+
+    #[test]
+    fn stats_of_app_state() {
+        let state = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/".into(),
+                children: vector![
+                    FileNode::File {
+                        path: "/1".into(),
+                        size: Some(1),
+                    },
+                    FileNode::Directory {
+                        path: "/a".into(),
+                        children: vector![FileNode::File {
+                            path: "/a/2".into(),
+                            size: Some(2),
+                        }],
+                    },
+                ],
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            state.stats(),
+            Some(TreeStats {
+                total_size: 3,
+                file_count: 2,
+                unknown_size_count: 0,
+                directory_count: 2,
+                symlink_count: 0,
+                total_blocks: 0,
+                inode_estimate: 4,
+            })
+        );
+        assert_eq!(AppState::default().stats(), None);
+    }
+
+    #[test]
+    fn unknown_sizes_are_counted_separately() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::File {
+                    path: "/1".into(),
+                    size: Some(1),
+                },
+                FileNode::File {
+                    path: "/unknown".into(),
+                    size: None,
+                },
+            ],
+        };
+        let stats = TreeStats::of(&root);
+        assert_eq!(stats.total_size, 1);
+        assert_eq!(stats.file_count, 2);
+        assert_eq!(stats.unknown_size_count, 1);
+    }
+
+    #[test]
+    fn markdown_summary() {
+        let root = FileNode::Directory {
+            path: "/data".into(),
+            children: vector![
+                FileNode::File {
+                    path: "/data/small|odd.txt".into(),
+                    size: Some(10),
+                },
+                FileNode::Directory {
+                    path: "/data/videos".into(),
+                    children: vector![FileNode::File {
+                        path: "/data/videos/big.mkv".into(),
+                        size: Some(2048),
+                    }],
+                },
+            ],
+        };
+        assert_eq!(
+            stats_markdown(&root, "/data"),
+            "## Disk usage of `/data`\n\
+             \n\
+             - Total size: 2.0 KiB\n\
+             - Files: 2\n\
+             - Directories: 2\n\
+             \n\
+             | File | Size |\n\
+             | --- | ---: |\n\
+             | `videos/big.mkv` | 2.0 KiB |\n\
+             | `small\\|odd.txt` | 10 B |\n"
+        );
+    }
+}
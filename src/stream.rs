@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+
+use futures::channel::mpsc;
+use futures::Stream;
+
+use crate::file_system::{traverse_reporting, FileNode, ScanOptions};
+use crate::metadata::RealFs;
+
+/// Scans `path` on a background thread, yielding every file, symlink and special file as it's
+/// found, in no particular order. The stream ends when the scan is done.
+///
+/// For embedding the scan in async code, the app itself doesn't use it. A failed scan is
+/// logged and ends the stream early.
+#[allow(dead_code)]
+pub(crate) fn traverse_stream(path: PathBuf, options: ScanOptions) -> impl Stream<Item = FileNode> {
+    let (sender, receiver) = mpsc::unbounded();
+    std::thread::spawn(move || {
+        let found = |node: &FileNode| {
+            // Nobody's listening anymore if this fails, so the rest can be dropped too
+            let _ = sender.unbounded_send(node.clone());
+        };
+        if let Err(err) = traverse_reporting(
+            &RealFs,
+            &path,
+            &options,
+            &AtomicU64::new(0),
+            &found,
+            &AtomicBool::new(false),
+        ) {
+            tracing::warn!("Failed streaming scan of `{}`: {err}", path.display());
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::atomic::AtomicU64;
+
+    use futures::StreamExt;
+
+    use super::traverse_stream;
+    use crate::file_system::{traverse, FileNode, ScanOptions};
+
+    #[test]
+    fn stream_yields_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("1"), [0; 1]).unwrap();
+        std::fs::write(dir.path().join("a/2"), [0; 2]).unwrap();
+        std::fs::write(dir.path().join("a/b/3"), [0; 3]).unwrap();
+        let path = dir.path().to_path_buf();
+
+        let streamed: BTreeSet<FileNode> = futures::executor::block_on(
+            traverse_stream(path.clone(), ScanOptions::default()).collect(),
+        );
+        let scanned: BTreeSet<FileNode> = traverse(
+            &path,
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap()
+        .root
+        .into_iter()
+        .collect();
+        assert_eq!(streamed.len(), 3);
+        assert_eq!(streamed, scanned);
+    }
+}
@@ -0,0 +1,564 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use druid::im::Vector;
+use druid::{Data, Point, Rect};
+
+use crate::file_system::{FileNode, Metric};
+
+/// How a treemap splits a rectangle between its items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum TreemapAlgo {
+    /// Slices along one axis, alternating between horizontal and vertical with depth.
+    /// Keeps items in their given order, which is stable across rescans.
+    SliceAndDice,
+    /// Lays out the largest items first in rows that keep cells close to square.
+    #[default]
+    Squarified,
+}
+
+/// Which items of a directory a squarified treemap places first, from the top left.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum TreemapOrder {
+    /// Largest first, which gives the squarest cells
+    #[default]
+    BySize,
+    /// By path, so cells stay roughly in place when sizes change between scans
+    ByPath,
+    /// Directories before files, each largest first
+    DirectoriesFirst,
+}
+
+impl TreemapOrder {
+    /// How `a` and `b`, of sizes `a_size` and `b_size`, are ordered among their siblings.
+    fn compare(self, (a, a_size): (&FileNode, u64), (b, b_size): (&FileNode, u64)) -> Ordering {
+        let is_dir = |node: &FileNode| matches!(node, FileNode::Directory { .. });
+        match self {
+            TreemapOrder::BySize => b_size.cmp(&a_size),
+            TreemapOrder::ByPath => a.path().cmp(b.path()),
+            TreemapOrder::DirectoriesFirst => {
+                is_dir(b).cmp(&is_dir(a)).then_with(|| b_size.cmp(&a_size))
+            }
+        }
+    }
+
+    /// Indices of `nodes` with `sizes` in the order they're placed. Ties keep their order.
+    fn placement(self, nodes: &Vector<FileNode>, sizes: &[u64]) -> Vec<usize> {
+        let mut order = (0..nodes.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| self.compare((&nodes[a], sizes[a]), (&nodes[b], sizes[b])));
+        order
+    }
+}
+
+impl TreemapAlgo {
+    /// Splits `bounds` into one rect per entry of `sizes`, with areas proportional to the sizes.
+    /// Squarified treemaps place the entries in `order`, slice and dice ones always in the
+    /// order they're given.
+    ///
+    /// The rects are returned in the same order as `sizes`, whatever order they're placed in.
+    pub(crate) fn layout(
+        self,
+        sizes: &[u64],
+        order: &[usize],
+        bounds: Rect,
+        depth: usize,
+    ) -> Vec<Rect> {
+        let mut rects = vec![Rect::from_origin_size(bounds.origin(), (0.0, 0.0)); sizes.len()];
+        if sizes.iter().sum::<u64>() == 0 || bounds.area() == 0.0 {
+            return rects;
+        }
+        match self {
+            TreemapAlgo::SliceAndDice => {
+                slice_and_dice(sizes, bounds, depth.is_multiple_of(2), &mut rects)
+            }
+            TreemapAlgo::Squarified => squarify(sizes, order, bounds, &mut rects),
+        }
+        rects
+    }
+}
+
+fn slice_and_dice(sizes: &[u64], bounds: Rect, horizontal: bool, rects: &mut [Rect]) {
+    let total = sizes.iter().sum::<u64>() as f64;
+    let mut done = 0;
+    for (rect, size) in rects.iter_mut().zip(sizes) {
+        let start = done as f64 / total;
+        done += size;
+        let end = done as f64 / total;
+        *rect = if horizontal {
+            Rect::new(
+                bounds.x0 + bounds.width() * start,
+                bounds.y0,
+                bounds.x0 + bounds.width() * end,
+                bounds.y1,
+            )
+        } else {
+            Rect::new(
+                bounds.x0,
+                bounds.y0 + bounds.height() * start,
+                bounds.x1,
+                bounds.y0 + bounds.height() * end,
+            )
+        };
+    }
+}
+
+/// Squarified treemap as described by Bruls, Huizing and van Wijk.
+///
+/// The entries are placed in `order` rather than always largest first.
+fn squarify(sizes: &[u64], order: &[usize], bounds: Rect, rects: &mut [Rect]) {
+    let scale = bounds.area() / sizes.iter().sum::<u64>() as f64;
+    let area = |index: usize| sizes[index] as f64 * scale;
+
+    let order = order
+        .iter()
+        .copied()
+        .filter(|&index| sizes[index] > 0)
+        .collect::<Vec<_>>();
+
+    let mut remaining = bounds;
+    let mut row: Vec<usize> = Vec::new();
+    let mut pending = order.into_iter().peekable();
+    while let Some(&next) = pending.peek() {
+        let side = remaining.width().min(remaining.height());
+        let current = worst_aspect_ratio(row.iter().map(|&index| area(index)), side);
+        let extended =
+            worst_aspect_ratio(row.iter().chain([&next]).map(|&index| area(index)), side);
+        if row.is_empty() || extended <= current {
+            row.push(next);
+            pending.next();
+        } else {
+            remaining = lay_out_row(&row, &area, remaining, rects);
+            row.clear();
+        }
+    }
+    if !row.is_empty() {
+        lay_out_row(&row, &area, remaining, rects);
+    }
+}
+
+/// The worst aspect ratio of a row of cells with the given areas placed along `side`.
+fn worst_aspect_ratio(areas: impl Iterator<Item = f64> + Clone, side: f64) -> f64 {
+    let sum = areas.clone().sum::<f64>();
+    if sum == 0.0 {
+        return f64::INFINITY;
+    }
+    let max = areas.clone().fold(f64::MIN, f64::max);
+    let min = areas.fold(f64::MAX, f64::min);
+    let side_squared = side * side;
+    (side_squared * max / (sum * sum)).max(sum * sum / (side_squared * min))
+}
+
+/// Places `row` along the shorter side of `bounds`, returning the space left over.
+fn lay_out_row(
+    row: &[usize],
+    area: &impl Fn(usize) -> f64,
+    bounds: Rect,
+    rects: &mut [Rect],
+) -> Rect {
+    let row_area = row.iter().map(|&index| area(index)).sum::<f64>();
+    if bounds.width() >= bounds.height() {
+        let thickness = row_area / bounds.height();
+        let mut y = bounds.y0;
+        for &index in row {
+            let height = area(index) / thickness;
+            rects[index] = Rect::new(bounds.x0, y, bounds.x0 + thickness, y + height);
+            y += height;
+        }
+        Rect::new(bounds.x0 + thickness, bounds.y0, bounds.x1, bounds.y1)
+    } else {
+        let thickness = row_area / bounds.width();
+        let mut x = bounds.x0;
+        for &index in row {
+            let width = area(index) / thickness;
+            rects[index] = Rect::new(x, bounds.y0, x + width, bounds.y0 + thickness);
+            x += width;
+        }
+        Rect::new(bounds.x0, bounds.y0 + thickness, bounds.x1, bounds.y1)
+    }
+}
+
+/// How to lay out a treemap.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TreemapStyle {
+    pub(crate) algo: TreemapAlgo,
+    pub(crate) order: TreemapOrder,
+    /// What the area of each cell is proportional to
+    pub(crate) metric: Metric,
+    /// Whether directories are subdivided between their children, otherwise they're one cell
+    pub(crate) nested: bool,
+    /// Space between the border of a top-level directory and its children, halving with every
+    /// level deeper
+    pub(crate) padding: f64,
+}
+
+/// A laid out treemap.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct TreemapLayout {
+    /// The cells of all leaves in pre-order
+    pub(crate) cells: Vec<(FileNode, Rect)>,
+    /// Every laid out node, subdivided directories enclosing their children
+    pub(crate) tree: Vec<LaidOutNode>,
+}
+
+/// A node and its rect in a treemap.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LaidOutNode {
+    pub(crate) node: FileNode,
+    pub(crate) rect: Rect,
+    /// Empty unless the node is a directory subdivided between its children
+    pub(crate) children: Vec<LaidOutNode>,
+}
+
+/// The deepest node in `tree` whose rect contains `pos`.
+///
+/// A directory is only hit where none of its children are, like in gaps left by rounding, or
+/// where its child is a stand-in for several files.
+pub(crate) fn innermost_at(tree: &[LaidOutNode], pos: Point) -> Option<&FileNode> {
+    let outer = tree.iter().find(|laid_out| laid_out.rect.contains(pos))?;
+    innermost_at(&outer.children, pos)
+        .filter(|node| node.is_selectable())
+        .or(Some(&outer.node))
+}
+
+/// The rects of the subdivided directories in `tree`, parents before their children.
+pub(crate) fn subdivided_directories(tree: &[LaidOutNode]) -> Vec<(PathBuf, Rect)> {
+    let mut directories = Vec::new();
+    let mut stack: Vec<_> = tree.iter().rev().collect();
+    while let Some(laid_out) = stack.pop() {
+        if !laid_out.children.is_empty() {
+            directories.push((laid_out.node.path().clone(), laid_out.rect));
+            stack.extend(laid_out.children.iter().rev());
+        }
+    }
+    directories
+}
+
+impl TreemapStyle {
+    /// Space between the border of a directory `depth` levels deep and its children.
+    pub(crate) fn padding_fn(self, depth: usize) -> f64 {
+        self.padding / 2f64.powi(depth as i32)
+    }
+    /// Lays out `nodes` with `sizes` in `bounds`.
+    ///
+    /// The sizes of nested children are measured with the metric.
+    pub(crate) fn layout_nodes(
+        self,
+        nodes: &Vector<FileNode>,
+        sizes: &[u64],
+        bounds: Rect,
+    ) -> TreemapLayout {
+        let mut cells = Vec::new();
+        let tree = self.layout_level(nodes, sizes, bounds, 0, &mut cells);
+        TreemapLayout { cells, tree }
+    }
+
+    fn layout_level(
+        self,
+        nodes: &Vector<FileNode>,
+        sizes: &[u64],
+        bounds: Rect,
+        depth: usize,
+        cells: &mut Vec<(FileNode, Rect)>,
+    ) -> Vec<LaidOutNode> {
+        let rects = self
+            .algo
+            .layout(sizes, &self.order.placement(nodes, sizes), bounds, depth);
+        nodes
+            .iter()
+            .zip(rects)
+            .map(|(node, rect)| {
+                let children = match node {
+                    FileNode::Directory { children, .. } if self.nested && !children.is_empty() => {
+                        let sizes = children
+                            .iter()
+                            .map(|child| self.metric.of(child))
+                            .collect::<Vec<_>>();
+                        // Small directories keep most of their space for their children
+                        let padding = self
+                            .padding_fn(depth)
+                            .min(rect.width().min(rect.height()) / 4.0);
+                        let inner = rect.inset(-padding);
+                        self.layout_level(children, &sizes, inner, depth + 1, cells)
+                    }
+                    _ => {
+                        cells.push((node.clone(), rect));
+                        Vec::new()
+                    }
+                };
+                LaidOutNode {
+                    node: node.clone(),
+                    rect,
+                    children,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::Rect;
+
+    use druid::im::Vector;
+
+    use super::{innermost_at, subdivided_directories, TreemapAlgo, TreemapOrder, TreemapStyle};
+    use crate::file_system::{FileNode, Metric};
+
+    const SIZES: [u64; 6] = [6, 6, 4, 3, 2, 1];
+
+    fn file(path: &str, size: u64) -> FileNode {
+        FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        }
+    }
+
+    /// `/a`, holding `/a/1` and the folder `/a/b` of `/a/b/2` and `/a/b/3`, 8 bytes in all.
+    fn nested_tree() -> FileNode {
+        FileNode::Directory {
+            path: "/a".into(),
+            children: druid::im::vector![
+                file("/a/1", 3),
+                FileNode::Directory {
+                    path: "/a/b".into(),
+                    children: druid::im::vector![file("/a/b/2", 4), file("/a/b/3", 1)],
+                },
+            ],
+        }
+    }
+
+    /// Indices of `sizes` from the largest to the smallest size.
+    fn largest_first(sizes: &[u64]) -> Vec<usize> {
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]));
+        order
+    }
+
+    fn assert_area_conserved(algo: TreemapAlgo) {
+        let bounds = Rect::new(10.0, 20.0, 610.0, 420.0);
+        let rects = algo.layout(&SIZES, &largest_first(&SIZES), bounds, 0);
+        let total = SIZES.iter().sum::<u64>() as f64;
+        for (rect, size) in rects.iter().zip(SIZES) {
+            let expected = bounds.area() * size as f64 / total;
+            assert!((rect.area() - expected).abs() < 1e-6, "{algo:?}: {rect:?}");
+            assert!(
+                bounds.inset(1e-9).union(*rect) == bounds.inset(1e-9),
+                "{algo:?}: {rect:?} outside"
+            );
+        }
+        let covered = rects.iter().map(Rect::area).sum::<f64>();
+        assert!((covered - bounds.area()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slice_and_dice_conserves_area() {
+        assert_area_conserved(TreemapAlgo::SliceAndDice);
+    }
+
+    #[test]
+    fn squarified_conserves_area() {
+        assert_area_conserved(TreemapAlgo::Squarified);
+    }
+
+    #[test]
+    fn slice_and_dice_preserves_order() {
+        let sizes = [1, 5, 2, 8];
+        let bounds = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let order = largest_first(&sizes);
+        let horizontal = TreemapAlgo::SliceAndDice.layout(&sizes, &order, bounds, 0);
+        assert!(horizontal.windows(2).all(|pair| pair[0].x1 == pair[1].x0));
+        let vertical = TreemapAlgo::SliceAndDice.layout(&sizes, &order, bounds, 1);
+        assert!(vertical.windows(2).all(|pair| pair[0].y1 == pair[1].y0));
+    }
+
+    #[test]
+    fn path_order_ignores_sizes() {
+        let nodes = |sizes: [u64; 3]| {
+            ["/c", "/a", "/b"]
+                .into_iter()
+                .zip(sizes)
+                .map(|(path, size)| FileNode::File {
+                    path: path.into(),
+                    size: Some(size),
+                })
+                .collect::<Vector<_>>()
+        };
+        let placement = |order: TreemapOrder, nodes: &Vector<FileNode>| {
+            order.placement(nodes, &nodes.iter().map(FileNode::size).collect::<Vec<_>>())
+        };
+        let before = nodes([1, 2, 3]);
+        let after = nodes([30, 1, 200]);
+        assert_eq!(placement(TreemapOrder::ByPath, &before), [1, 2, 0]);
+        assert_eq!(
+            placement(TreemapOrder::ByPath, &before),
+            placement(TreemapOrder::ByPath, &after)
+        );
+        assert_eq!(placement(TreemapOrder::BySize, &before), [2, 1, 0]);
+        assert_eq!(placement(TreemapOrder::BySize, &after), [2, 0, 1]);
+
+        let bounds = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let first = |nodes: &Vector<FileNode>| {
+            let sizes = nodes.iter().map(FileNode::size).collect::<Vec<_>>();
+            let rects = TreemapAlgo::Squarified.layout(
+                &sizes,
+                &placement(TreemapOrder::ByPath, nodes),
+                bounds,
+                0,
+            );
+            rects[1].origin()
+        };
+        // `/a` keeps the top left corner
+        assert_eq!(first(&before), bounds.origin());
+        assert_eq!(first(&after), bounds.origin());
+    }
+
+    #[test]
+    fn directory_rects_enclose_their_children() {
+        let nodes = druid::im::vector![nested_tree(), file("/4", 5)];
+        let sizes = nodes.iter().map(FileNode::size).collect::<Vec<_>>();
+        for algo in [TreemapAlgo::SliceAndDice, TreemapAlgo::Squarified] {
+            let style = TreemapStyle {
+                algo,
+                order: TreemapOrder::BySize,
+                metric: Metric::Bytes,
+                nested: true,
+                padding: 0.0,
+            };
+            let layout = style.layout_nodes(&nodes, &sizes, Rect::new(0.0, 0.0, 130.0, 70.0));
+            let directories = subdivided_directories(&layout.tree);
+            let paths = directories
+                .iter()
+                .map(|(path, _)| path.display().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(paths, ["/a", "/a/b"]);
+            for (directory, bounds) in &directories {
+                let inside = layout
+                    .cells
+                    .iter()
+                    .filter(|(node, _)| node.path().starts_with(directory))
+                    .map(|(_, rect)| *rect)
+                    .collect::<Vec<_>>();
+                assert!(!inside.is_empty());
+                for rect in &inside {
+                    assert!(
+                        bounds.inset(1e-9).union(*rect) == bounds.inset(1e-9),
+                        "{algo:?}"
+                    );
+                }
+                // The children fill the directory completely
+                let area = inside.iter().map(Rect::area).sum::<f64>();
+                assert!((area - bounds.area()).abs() < 1e-6, "{algo:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn clicks_select_innermost_node() {
+        let nodes = druid::im::vector![nested_tree(), file("/4", 5)];
+        let sizes = nodes.iter().map(FileNode::size).collect::<Vec<_>>();
+        let style = TreemapStyle {
+            algo: TreemapAlgo::Squarified,
+            order: TreemapOrder::BySize,
+            metric: Metric::Bytes,
+            nested: true,
+            padding: 0.0,
+        };
+        let layout = style.layout_nodes(&nodes, &sizes, Rect::new(0.0, 0.0, 130.0, 70.0));
+        for (cell, rect) in &layout.cells {
+            assert_eq!(innermost_at(&layout.tree, rect.center()), Some(cell));
+        }
+        assert_eq!(
+            innermost_at(&layout.tree, druid::Point::new(-1.0, 0.0)),
+            None
+        );
+
+        // Where no child covers a directory, the directory itself is hit
+        let mut tree = layout.tree;
+        let directory = tree[0].children.get_mut(1).unwrap();
+        directory.children.truncate(1);
+        let uncovered = subdivided_directories(&tree)[1].1;
+        let gap = druid::Point::new(uncovered.x1 - 0.1, uncovered.y1 - 0.1);
+        assert_eq!(
+            innermost_at(&tree, gap).map(FileNode::path),
+            Some(&"/a/b".into())
+        );
+    }
+
+    #[test]
+    fn padding_shrinks_with_depth() {
+        let nodes = druid::im::vector![nested_tree()];
+        let style = TreemapStyle {
+            algo: TreemapAlgo::SliceAndDice,
+            order: TreemapOrder::BySize,
+            metric: Metric::Bytes,
+            nested: true,
+            padding: 8.0,
+        };
+        assert_eq!(style.padding_fn(0), 8.0);
+        assert_eq!(style.padding_fn(1), 4.0);
+        assert_eq!(style.padding_fn(3), 1.0);
+
+        let bounds = Rect::new(0.0, 0.0, 400.0, 200.0);
+        let layout = style.layout_nodes(&nodes, &[8], bounds);
+        let a = &layout.tree[0];
+        assert_eq!(a.rect, bounds);
+        let children = a
+            .children
+            .iter()
+            .map(|child| child.rect)
+            .reduce(|a, b| a.union(b))
+            .unwrap();
+        assert_eq!(children, bounds.inset(-8.0));
+
+        let b = &a.children[1];
+        assert_eq!(b.node.path(), &std::path::PathBuf::from("/a/b"));
+        let grandchildren = b
+            .children
+            .iter()
+            .map(|child| child.rect)
+            .reduce(|a, b| a.union(b))
+            .unwrap();
+        assert_eq!(grandchildren, b.rect.inset(-4.0));
+        for child in &b.children {
+            assert!(b.rect.union(child.rect) == b.rect);
+        }
+    }
+
+    #[test]
+    fn orders_children_before_layout() {
+        let nodes: Vector<FileNode> = [
+            file("/b.txt", 5),
+            FileNode::Directory {
+                path: "/d".into(),
+                children: druid::im::vector![file("/d/1", 2)],
+            },
+            file("/a.txt", 9),
+            FileNode::Directory {
+                path: "/c".into(),
+                children: druid::im::vector![file("/c/1", 7)],
+            },
+        ]
+        .into_iter()
+        .collect();
+        let sizes = nodes.iter().map(FileNode::size).collect::<Vec<_>>();
+        let paths = |order: TreemapOrder| {
+            order
+                .placement(&nodes, &sizes)
+                .into_iter()
+                .map(|index| nodes[index].path().to_str().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(TreemapOrder::BySize),
+            ["/a.txt", "/c", "/b.txt", "/d"]
+        );
+        assert_eq!(
+            paths(TreemapOrder::ByPath),
+            ["/a.txt", "/b.txt", "/c", "/d"]
+        );
+        assert_eq!(
+            paths(TreemapOrder::DirectoriesFirst),
+            ["/c", "/d", "/a.txt", "/b.txt"]
+        );
+    }
+}
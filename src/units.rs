@@ -0,0 +1,104 @@
+/// Binary prefixes of byte sizes, each 1024 times the previous
+const PREFIXES: [&str; 6] = ["Ki", "Mi", "Gi", "Ti", "Pi", "Ei"];
+/// The same prefixes by their first letter, for where there's little room
+const COMPACT_PREFIXES: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+
+/// Formats `bytes` for display, like `12.3 GiB`.
+pub(crate) fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut prefix = 0;
+    // Also move up when rounding to one decimal would show 1024.0
+    while size >= 1023.95 && prefix < PREFIXES.len() - 1 {
+        size /= 1024.0;
+        prefix += 1;
+    }
+    format!("{size:.1} {}B", PREFIXES[prefix])
+}
+
+/// Formats `bytes` as tersely as possible for treemap cells, like `340M`, rounded to whole
+/// units.
+pub(crate) fn format_size_compact(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut prefix = 0;
+    // Also move up when rounding would show 1024
+    while size >= 1023.5 && prefix < COMPACT_PREFIXES.len() - 1 {
+        size /= 1024.0;
+        prefix += 1;
+    }
+    format!("{size:.0}{}", COMPACT_PREFIXES[prefix])
+}
+
+/// Formats `n` with its digits grouped in threes by `sep`, like `1,234,567`.
+pub(crate) fn format_int(n: u64, sep: char) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(sep);
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// `one` if there's one of what's counted, `many` otherwise.
+pub(crate) fn plural<'a>(count: u64, one: &'a str, many: &'a str) -> &'a str {
+    if count == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_int, format_size, format_size_compact, plural};
+
+    #[test]
+    fn sizes_across_magnitudes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(format_size(13_207_024_435), "12.3 GiB");
+        assert_eq!(format_size(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn compact_sizes_across_magnitudes() {
+        assert_eq!(format_size_compact(0), "0B");
+        assert_eq!(format_size_compact(1023), "1023B");
+        assert_eq!(format_size_compact(1024), "1K");
+        assert_eq!(format_size_compact(1536), "2K");
+        assert_eq!(format_size_compact(356_323_328), "340M");
+        assert_eq!(format_size_compact(1024 * 1024 - 1), "1M");
+        assert_eq!(format_size_compact(13_207_024_435), "12G");
+        assert_eq!(format_size_compact(5 << 40), "5T");
+        assert_eq!(format_size_compact(u64::MAX), "16E");
+    }
+
+    #[test]
+    fn ints_grouped_by_separator() {
+        assert_eq!(format_int(0, ','), "0");
+        assert_eq!(format_int(999, ','), "999");
+        assert_eq!(format_int(1000, ','), "1,000");
+        assert_eq!(format_int(123_456, '.'), "123.456");
+        assert_eq!(format_int(1_234_567, ','), "1,234,567");
+        assert_eq!(format_int(1_234_567, ' '), "1 234 567");
+        assert_eq!(format_int(u64::MAX, ','), "18,446,744,073,709,551,615");
+    }
+
+    #[test]
+    fn only_one_is_singular() {
+        assert_eq!(plural(0, "item", "items"), "items");
+        assert_eq!(plural(1, "item", "items"), "item");
+        assert_eq!(plural(2, "item", "items"), "items");
+    }
+}
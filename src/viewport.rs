@@ -0,0 +1,92 @@
+use druid::{Affine, Point, Size, Vec2};
+
+const MIN_SCALE: f64 = 1.0;
+const MAX_SCALE: f64 = 64.0;
+
+/// Zoom and pan of the visualization, mapping content coordinates to widget coordinates.
+///
+/// The content has the size of the widget, so at the minimum scale it fits exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Viewport {
+    scale: f64,
+    offset: Vec2,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            scale: MIN_SCALE,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+impl Viewport {
+    /// Converts a point in widget coordinates to content coordinates.
+    pub(crate) fn to_content(self, point: Point) -> Point {
+        ((point.to_vec2() - self.offset) / self.scale).to_point()
+    }
+
+    pub(crate) fn scale(self) -> f64 {
+        self.scale
+    }
+
+    pub(crate) fn transform(self) -> Affine {
+        Affine::translate(self.offset) * Affine::scale(self.scale)
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the content under `focus` in place.
+    pub(crate) fn zoom_at(&mut self, factor: f64, focus: Point, size: Size) {
+        let content = self.to_content(focus);
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = focus.to_vec2() - content.to_vec2() * self.scale;
+        self.clamp(size);
+    }
+
+    pub(crate) fn pan(&mut self, delta: Vec2, size: Size) {
+        self.offset += delta;
+        self.clamp(size);
+    }
+
+    /// Keeps the content covering the whole widget.
+    fn clamp(&mut self, size: Size) {
+        let min = size.to_vec2() * (1.0 - self.scale);
+        self.offset = Vec2::new(
+            self.offset.x.clamp(min.x, 0.0),
+            self.offset.y.clamp(min.y, 0.0),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::{Point, Size};
+
+    use super::{Viewport, MAX_SCALE, MIN_SCALE};
+
+    const SIZE: Size = Size::new(400.0, 300.0);
+
+    #[test]
+    fn zoom_keeps_focus_in_place() {
+        let mut viewport = Viewport::default();
+        let focus = Point::new(100.0, 50.0);
+        viewport.zoom_at(2.0, focus, SIZE);
+        assert_eq!(viewport.to_content(focus), focus);
+        viewport.zoom_at(1.5, Point::new(300.0, 200.0), SIZE);
+        let content = viewport.to_content(Point::new(300.0, 200.0));
+        viewport.zoom_at(1.5, Point::new(300.0, 200.0), SIZE);
+        assert!((viewport.to_content(Point::new(300.0, 200.0)) - content).hypot() < 1e-9);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut viewport = Viewport::default();
+        viewport.zoom_at(0.5, Point::new(10.0, 10.0), SIZE);
+        assert_eq!(viewport, Viewport::default());
+        viewport.zoom_at(1000.0, Point::new(10.0, 10.0), SIZE);
+        assert_eq!(viewport.scale, MAX_SCALE);
+        viewport.zoom_at(1e-6, Point::new(10.0, 10.0), SIZE);
+        assert_eq!(viewport.scale, MIN_SCALE);
+        assert_eq!(viewport.to_content(Point::ZERO), Point::ZERO);
+    }
+}
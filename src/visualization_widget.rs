@@ -1,89 +1,1034 @@
-use druid::{im::Vector, Color, Data, LifeCycle, Rect, RenderContext, Size, Widget};
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use druid::{
+    im::Vector, Affine, Color, Data, FontFamily, LifeCycle, Rect, RenderContext, Selector, Size,
+    Widget,
+};
+
+use crate::color::{
+    cushion_gradient, dim, directory_border_color, get_black_or_white, get_contrasting_color,
+    tint_directory, ColorMode,
+};
+use crate::extensions::extension;
 use crate::file_system::FileNode;
+use crate::labels::{truncate_to_width, wrap_to_width};
+use crate::overview::{cap_nodes, fractions, VisibleNodes};
+pub(crate) use crate::overview::{OverviewGranularity, ViewFilter};
+use crate::treemap::{innermost_at, subdivided_directories, LaidOutNode, TreemapStyle};
+use crate::units::{format_size, format_size_compact};
+use crate::viewport::Viewport;
+
+/// How much one unit of ctrl+scroll zooms, as an exponent
+const ZOOM_PER_SCROLL_UNIT: f64 = 0.002;
+/// Seconds a drilled into directory takes to grow to fill the widget
+const DRILL_ANIMATION_DURATION: f64 = 0.2;
+/// Seconds the changes of a rescan take to grow in, when rescans aren't periodic
+const GROWTH_ANIMATION_DURATION: f64 = 1.0;
+/// Narrowest a bar is drawn at, below it there's no room to tell files apart
+const MIN_BAR_WIDTH_PX: f64 = 16.0;
+/// How far in physical pixels fills are drawn inside their rect, so neighbours don't overlap
+const FILL_OVERLAP_PX: f64 = 1.0;
+/// Width in physical pixels of the outline around selected rects
+const OUTLINE_WIDTH_PX: f64 = 2.0;
+/// Width in physical pixels of the border around directories in a treemap
+const DIRECTORY_BORDER_PX: f64 = 1.0;
+/// Sent by the widget to itself, to note in the state whether it draws the selected file
+const SELECTION_DRAWN: Selector<bool> = Selector::new("rs-dir-stat.selection-drawn");
+/// Sent by the widget to itself, to note in the state whether it folds nodes into the others
+const NODES_FOLDED: Selector<bool> = Selector::new("rs-dir-stat.nodes-folded");
+/// Font size of the labels in rectangles
+const LABEL_FONT_SIZE: f64 = 11.0;
+/// Font size of tooltips
+const TOOLTIP_FONT_SIZE: f64 = 12.0;
+/// Space around the text of labels and tooltips
+const TEXT_PADDING: f64 = 3.0;
+
+/// How the visible nodes are arranged.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum LayoutMode {
+    /// One horizontal bar, split proportionally
+    #[default]
+    Bar,
+    /// Nested rectangles filling the whole widget
+    Treemap,
+    /// One bar per top-level folder stacked vertically, each split by its own total so
+    /// siblings of any size can be compared
+    Rows,
+}
+
+impl LayoutMode {
+    /// The mode the layout shortcut switches to from this one, back to the first after the last.
+    pub(crate) fn next(self) -> Self {
+        match self {
+            LayoutMode::Bar => LayoutMode::Treemap,
+            LayoutMode::Treemap => LayoutMode::Rows,
+            LayoutMode::Rows => LayoutMode::Bar,
+        }
+    }
+}
+
+/// How the selected node is highlighted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum SelectionStyle {
+    /// Outline the selected node
+    #[default]
+    Outline,
+    /// Darken everything but the selected node
+    DimOthers,
+    Both,
+}
+
+impl SelectionStyle {
+    pub(crate) fn outlines(self) -> bool {
+        matches!(self, SelectionStyle::Outline | SelectionStyle::Both)
+    }
+
+    pub(crate) fn dims_others(self) -> bool {
+        matches!(self, SelectionStyle::DimOthers | SelectionStyle::Both)
+    }
+}
 
-#[derive(Debug, Default, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub(crate) struct VisualizationWidget {
-    width: f64,
-    files: Option<(Vector<FileNode>, u64)>,
+    size: Size,
+    /// Shared rather than owned, so copies of the widget don't copy it. The nodes share their
+    /// structure with `AppState::all_files` when they're the shown folder's own children,
+    /// rather than every file in it or grouped.
+    files: Option<Arc<VisibleNodes>>,
+    /// Shapes from the last paint, for hit-testing
+    shapes: Vec<Shape>,
+    /// The laid out treemap of the last paint, empty for a bar
+    tree: Vec<LaidOutNode>,
+    /// The size and visible nodes the shapes and tree were laid out for, so paints that only
+    /// redraw them don't lay them out again. `None` if they have to be.
+    laid_out: Option<(Size, Arc<VisibleNodes>)>,
+    viewport: Viewport,
+    /// Last known mouse position, the focus point of trackpad zoom gestures
+    mouse_pos: druid::Point,
+    /// The node under the mouse, while tooltips are shown
+    hovered: Option<FileNode>,
+    drill_animation: Option<DrillAnimation>,
+    growth_animation: Option<GrowthAnimation>,
+}
+
+/// A drilled into directory growing from where it was drawn to fill the widget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DrillAnimation {
+    /// Where the directory was drawn, in widget coordinates
+    from: Rect,
+    /// From 0.0 when starting to 1.0 when done
+    progress: f64,
+}
+
+/// The visible nodes of a rescan growing and shrinking from the sizes they had before.
+#[derive(Debug, Clone, PartialEq)]
+struct GrowthAnimation {
+    /// What the nodes visible before the rescan measured, by path
+    from: HashMap<PathBuf, u64>,
+    /// From 0.0 when starting to 1.0 when done
+    progress: f64,
+    /// Seconds it takes
+    duration: f64,
+}
+
+/// The sizes `t` of the way from what `nodes` measured before, by `from`, to `sizes`.
+///
+/// Nodes that weren't there before grow from nothing.
+fn interpolate_sizes(
+    from: &HashMap<PathBuf, u64>,
+    nodes: &Vector<FileNode>,
+    sizes: &[u64],
+    t: f64,
+) -> Vec<u64> {
+    let t = t.clamp(0.0, 1.0);
+    nodes
+        .iter()
+        .zip(sizes)
+        .map(|(node, size)| {
+            let old = from.get(node.path()).copied().unwrap_or(0) as f64;
+            (old + (*size as f64 - old) * t).round() as u64
+        })
+        .collect()
+}
+
+/// The rect `t` of the way from `from` to `to`.
+fn lerp_rect(from: Rect, to: Rect, t: f64) -> Rect {
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    Rect::new(
+        lerp(from.x0, to.x0),
+        lerp(from.y0, to.y0),
+        lerp(from.x1, to.x1),
+        lerp(from.y1, to.y1),
+    )
+}
+
+/// The transform squeezing `from` into `to`.
+fn rect_transform(from: Rect, to: Rect) -> Affine {
+    let (scale_x, scale_y) = (to.width() / from.width(), to.height() / from.height());
+    Affine::new([
+        scale_x,
+        0.0,
+        0.0,
+        scale_y,
+        to.x0 - from.x0 * scale_x,
+        to.y0 - from.y0 * scale_y,
+    ])
+}
+
+// The overview doesn't depend on druid, so its options are only made `Data` for the widgets here
+impl Data for ViewFilter {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Data for OverviewGranularity {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
 }
 
 impl Data for VisualizationWidget {
     fn same(&self, other: &Self) -> bool {
-        self.width == other.width && self.files == other.files
+        self.size == other.size
+            && self.files.same(&other.files)
+            && self.shapes == other.shapes
+            && self.tree == other.tree
+            && self.viewport == other.viewport
+    }
+}
+
+impl VisualizationWidget {
+    /// Derives the visible nodes again if what they're derived from changed. Returns whether
+    /// that started animating a rescan's changes.
+    fn update_files(&mut self, old_data: &crate::AppState, data: &crate::AppState) -> bool {
+        if !visible_nodes_changed(old_data, data) {
+            return false;
+        }
+        let rescanned = match (&old_data.all_files, &data.all_files) {
+            (Some(old), Some(new)) => old.path() == new.path() && old != new,
+            _ => false,
+        };
+        let from = self
+            .files
+            .as_ref()
+            .filter(|_| data.animate_rescans && rescanned)
+            .map(|files| {
+                files
+                    .nodes
+                    .iter()
+                    .map(|node| node.path().clone())
+                    .zip(files.sizes.iter().copied())
+                    .collect()
+            });
+        self.files = data
+            .shown_root()
+            .map(|root| Arc::new(visible_nodes(data, root)));
+        self.growth_animation = from.map(|from| GrowthAnimation {
+            from,
+            progress: 0.0,
+            // Growing until the next rescan is due, so changes keep flowing in
+            duration: data
+                .auto_rescan_secs
+                .map_or(GROWTH_ANIMATION_DURATION, |secs| secs as f64),
+        });
+        self.growth_animation.is_some()
+    }
+
+    /// Whether the shapes of the last paint are still laid out for `size` and the visible nodes.
+    /// A rescan growing in changes them every frame.
+    fn is_laid_out_for(&self, size: Size) -> bool {
+        match (&self.laid_out, &self.files) {
+            (Some((laid_out_size, laid_out_files)), Some(files)) => {
+                self.growth_animation.is_none()
+                    && *laid_out_size == size
+                    && Arc::ptr_eq(laid_out_files, files)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the visible nodes are drawn with the smallest folded into one, as there are more
+    /// than `max_rects` or, in a bar, than fit in its width.
+    fn folds_nodes(&self, layout_mode: LayoutMode) -> bool {
+        self.files.as_ref().is_some_and(|files| {
+            files.folded
+                || (layout_mode == LayoutMode::Bar
+                    && bar_capacity(self.size.width)
+                        .is_some_and(|capacity| files.nodes.len() > capacity))
+        })
+    }
+
+    /// The node of the bar the last paint laid out at `x`, as it was fitted to the width.
+    fn bar_node_at(&self, x: f64) -> Option<&FileNode> {
+        tracing::debug!("clicked at x: {}", x);
+        if x < 0.0 {
+            return None;
+        }
+        self.shapes
+            .iter()
+            .find(|(_, rect, _)| x < rect.x1)
+            .map(|(node, ..)| node)
+    }
+
+    /// The visible directory `clicked` is in, or is.
+    fn drill_target(&self, clicked: &FileNode) -> Option<&FileNode> {
+        self.files.as_ref()?.nodes.iter().find(|node| {
+            matches!(node, FileNode::Directory { .. }) && clicked.path().starts_with(node.path())
+        })
+    }
+
+    /// Where the last paint drew `directory`, in widget coordinates.
+    fn drawn_rect(&self, directory: &Path) -> Option<Rect> {
+        let rect = self
+            .shapes
+            .iter()
+            .filter(|(node, ..)| node.path().starts_with(directory))
+            .map(|(_, rect, _)| *rect)
+            .reduce(|a, b| a.union(b))?;
+        Some(self.viewport.transform().transform_rect_bbox(rect))
+    }
+
+    fn treemap_node_at(&self, pos: druid::Point) -> Option<&FileNode> {
+        innermost_at(&self.tree, pos)
+    }
+
+    /// The node at `pos` in widget coordinates, or the nearest one within the click tolerance.
+    /// Stand-ins for several files are never hit, as there's nothing on disk to select.
+    fn node_at(&self, data: &crate::AppState, pos: druid::Point) -> Option<&FileNode> {
+        let pos = self.viewport.to_content(pos);
+        let hit = match data.layout_mode {
+            LayoutMode::Bar => self.bar_node_at(pos.x),
+            LayoutMode::Treemap => self.treemap_node_at(pos),
+            LayoutMode::Rows => data
+                .shown_root()
+                .and_then(|root| self.rows_node_at(root.path(), pos)),
+        };
+        // The tolerance is in screen pixels, however far in the view is zoomed
+        let tolerance = data.click_tolerance_px / self.viewport.scale();
+        hit.or_else(|| nearest_node(&self.shapes, pos, tolerance))
+            .filter(|node| node.is_selectable())
+    }
+
+    fn rows_node_at(&self, root: &Path, pos: druid::Point) -> Option<&FileNode> {
+        let files = self.files.as_ref()?;
+        let rows = top_level_rows(&files.nodes, root);
+        row_node_at(&rows, &files.sizes, self.size, pos).map(|index| &files.nodes[index])
+    }
+}
+
+/// Distance from `pos` to the nearest point of `rect`, 0.0 inside it.
+fn distance_to_rect(pos: druid::Point, rect: Rect) -> f64 {
+    let dx = (rect.x0 - pos.x).max(pos.x - rect.x1).max(0.0);
+    let dy = (rect.y0 - pos.y).max(pos.y - rect.y1).max(0.0);
+    dx.hypot(dy)
+}
+
+/// The node of the shape nearest to `pos`, if any is within `tolerance`.
+fn nearest_node(shapes: &[Shape], pos: druid::Point, tolerance: f64) -> Option<&FileNode> {
+    shapes
+        .iter()
+        .map(|(node, rect, _)| (node, distance_to_rect(pos, *rect)))
+        .filter(|(_, distance)| *distance <= tolerance)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(node, _)| node)
+}
+
+/// Where a node starts and ends along the bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    start: f64,
+    end: f64,
+}
+
+/// Splits `length` into one segment per entry of `sizes`, each as long as its share of their
+/// total.
+///
+/// Both painting and hit-testing the bar go through this, so they always agree, and the last
+/// segment ends exactly at `length`.
+fn layout_segments(sizes: &[u64], length: f64) -> Vec<Segment> {
+    fractions(sizes)
+        .into_iter()
+        .map(|(start, end)| Segment {
+            start: start * length,
+            end: end * length,
+        })
+        .collect()
+}
+
+/// How many files a bar `width` wide can show apart, one per pixel, or `None` if it's too
+/// narrow to show any detail.
+fn bar_capacity(width: f64) -> Option<usize> {
+    // Also rules out a width of NaN
+    (width >= MIN_BAR_WIDTH_PX).then_some(width as usize)
+}
+
+/// `files` as a bar `width` wide shows them, the smallest merged into one when there are more
+/// than fit on top of the `max_rects` cap they already have. `None` if it's too narrow.
+fn fit_bar(files: &VisibleNodes, width: f64) -> Option<Cow<'_, VisibleNodes>> {
+    let capacity = bar_capacity(width)?;
+    if files.nodes.len() <= capacity {
+        return Some(Cow::Borrowed(files));
+    }
+    Some(Cow::Owned(cap_nodes(files.clone(), capacity)))
+}
+
+/// Index of the segment `x` falls into, each including its start but not its end.
+fn segment_at(segments: &[Segment], x: f64) -> Option<usize> {
+    if x < 0.0 {
+        return None;
+    }
+    segments.iter().position(|segment| x < segment.end)
+}
+
+/// The indices of `nodes` split into runs below the same direct child of `root`, the rows of
+/// [`LayoutMode::Rows`]. Nodes outside of `root`, like the one standing in for the others, are
+/// a row of their own.
+fn top_level_rows(nodes: &Vector<FileNode>, root: &Path) -> Vec<Range<usize>> {
+    let top_level = |node: &FileNode| {
+        node.path()
+            .strip_prefix(root)
+            .ok()
+            .and_then(|rest| rest.components().next())
+            .map(|component| component.as_os_str().to_owned())
+    };
+    let mut rows: Vec<Range<usize>> = Vec::new();
+    let mut current = None;
+    for (index, node) in nodes.iter().enumerate() {
+        let key = top_level(node);
+        match rows.last_mut() {
+            Some(row) if key == current => row.end = index + 1,
+            _ => rows.push(index..index + 1),
+        }
+        current = key;
+    }
+    rows
+}
+
+/// Index of the row `y` falls into, of `count` rows evenly filling `height`.
+fn row_at(count: usize, height: f64, y: f64) -> Option<usize> {
+    if count == 0 || !(0.0..height).contains(&y) {
+        return None;
+    }
+    Some(((y / height * count as f64) as usize).min(count - 1))
+}
+
+/// Index of the node at `pos` in the rows layout filling `size`, each row of `rows` split by
+/// the `sizes` of its nodes.
+fn row_node_at(
+    rows: &[Range<usize>],
+    sizes: &[u64],
+    size: Size,
+    pos: druid::Point,
+) -> Option<usize> {
+    let row = rows.get(row_at(rows.len(), size.height, pos.y)?)?;
+    let sizes = &sizes[row.clone()];
+    let segments = layout_segments(sizes, size.width);
+    segment_at(&segments, pos.x).map(|index| row.start + index)
+}
+
+/// The shown directory's overview segments, each with the color the bar fills it with.
+pub(crate) fn bar_segments(data: &crate::AppState) -> Vec<(crate::overview::Segment, Color)> {
+    let Some(root) = data.shown_root() else {
+        return Vec::new();
+    };
+    crate::overview::compute_segments(root, &data.view_options())
+        .into_iter()
+        .map(|segment| {
+            let color = fill_color(data, &segment.path, segment.is_dir, segment.start_fraction);
+            (segment, color)
+        })
+        .collect()
+}
+
+/// The nodes of `root` that `data` shows.
+fn visible_nodes(data: &crate::AppState, root: &FileNode) -> VisibleNodes {
+    crate::overview::visible_nodes(root, &data.view_options())
+}
+
+/// A visible node's rect, with its position from 0.0 to 1.0 that picks its color
+type Shape = (FileNode, Rect, f64);
+
+/// Lays out `files` to fill `size`, returning the shapes and, for a treemap, the laid out tree.
+fn layout_shapes(
+    data: &crate::AppState,
+    files: &VisibleNodes,
+    size: Size,
+) -> (Vec<Shape>, Vec<LaidOutNode>) {
+    match data.layout_mode {
+        LayoutMode::Bar => {
+            let Some(files) = fit_bar(files, size.width) else {
+                return Default::default();
+            };
+            let shapes = files
+                .nodes
+                .iter()
+                .zip(layout_segments(&files.sizes, size.width))
+                .map(|(node, segment)| {
+                    let file_rect = Rect::new(segment.start, 0.0, segment.end, size.height);
+                    let position = if size.width > 0.0 {
+                        segment.start / size.width
+                    } else {
+                        0.0
+                    };
+                    (node.clone(), file_rect, position)
+                })
+                .collect::<Vec<_>>();
+            (shapes, Vec::new())
+        }
+        LayoutMode::Treemap => {
+            let style = TreemapStyle {
+                algo: data.treemap_algo,
+                order: data.treemap_order,
+                metric: data.metric,
+                nested: nests(data),
+                padding: data.treemap_padding,
+            };
+            let layout = style.layout_nodes(&files.nodes, &files.sizes, size.to_rect());
+            let count = layout.cells.len() as f64;
+            let shapes = layout
+                .cells
+                .into_iter()
+                .enumerate()
+                .map(|(index, (node, rect))| (node, rect, index as f64 / count))
+                .collect();
+            (shapes, layout.tree)
+        }
+        LayoutMode::Rows => {
+            let root = data.shown_root().map_or(Path::new(""), |root| root.path());
+            let rows = top_level_rows(&files.nodes, root);
+            let row_height = size.height / rows.len().max(1) as f64;
+            let mut shapes = Vec::new();
+            for (row_index, row) in rows.into_iter().enumerate() {
+                let y = row_index as f64 * row_height;
+                let sizes = &files.sizes[row.clone()];
+                let segments = layout_segments(sizes, size.width);
+                for (node, segment) in files.nodes.iter().skip(row.start).zip(segments) {
+                    let rect = Rect::new(segment.start, y, segment.end, y + row_height);
+                    let position = if size.width > 0.0 {
+                        segment.start / size.width
+                    } else {
+                        0.0
+                    };
+                    shapes.push((node.clone(), rect, position));
+                }
+            }
+            (shapes, Vec::new())
+        }
+    }
+}
+
+/// The name a node's color is hashed from: the lowercase extension of files and the name of
+/// directories.
+fn color_name(path: &Path, is_dir: bool) -> String {
+    if is_dir {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    } else {
+        extension(path)
+    }
+}
+
+/// The color a shape at `position` is filled with before any highlighting.
+fn shape_color(data: &crate::AppState, node: &FileNode, position: f64) -> Color {
+    let is_dir = matches!(node, FileNode::Directory { .. });
+    fill_color(data, node.path(), is_dir, position)
+}
+
+/// The color the node at `path` is filled with at `position`, before any highlighting.
+fn fill_color(data: &crate::AppState, path: &Path, is_dir: bool, position: f64) -> Color {
+    let color = match data.color_mode {
+        ColorMode::Position => data.gradient.at(position),
+        ColorMode::ByNameHash => data.palette.name_color(&color_name(path, is_dir)),
+    };
+    if is_dir && data.directory_tint > 0.0 {
+        tint_directory(color, data.directory_tint)
+    } else {
+        color
+    }
+}
+
+/// Line widths in logical units that come out as whole physical pixels.
+///
+/// Only drawing depends on the display scale, shapes and hit-testing stay in logical units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LineWidths {
+    fill_overlap: f64,
+    outline: f64,
+    directory_border: f64,
+}
+
+impl LineWidths {
+    /// The widths for a display with `scale` physical pixels per logical one.
+    fn for_scale(scale: f64) -> Self {
+        Self {
+            fill_overlap: FILL_OVERLAP_PX / scale,
+            outline: OUTLINE_WIDTH_PX / scale,
+            directory_border: DIRECTORY_BORDER_PX / scale,
+        }
+    }
+}
+
+/// Fills every shape with a color picked by its position, highlighting it according to the
+/// selection style.
+fn draw_shapes(
+    rc: &mut impl RenderContext,
+    data: &crate::AppState,
+    shapes: &[Shape],
+    tree: &[LaidOutNode],
+    widths: LineWidths,
+) {
+    let mut outlines = Vec::new();
+    // A selection the filters hide doesn't dim everything that is drawn
+    let dims = data.selection_style.dims_others()
+        && shapes
+            .iter()
+            .any(|(node, ..)| data.selected_files.contains(node));
+    for (node, rect, done) in shapes {
+        let done = *done;
+        let stroke_color = shape_color(data, node, done);
+        let is_selected = data.selected_files.contains(node);
+        let fill_color = if dims && !is_selected {
+            dim(stroke_color)
+        } else {
+            stroke_color
+        };
+        let fill_rect = rect.inset(-widths.fill_overlap);
+        if data.cushioned {
+            rc.fill(fill_rect, &cushion_gradient(fill_color));
+        } else {
+            rc.fill(fill_rect, &fill_color);
+        }
+        if data.show_labels {
+            draw_label(rc, node, fill_rect, fill_color, data.max_label_chars);
+        }
+        if is_selected && data.selection_style.outlines() {
+            let contrasting_color = if data.black_or_white_outlines {
+                get_black_or_white(stroke_color)
+            } else {
+                get_contrasting_color(stroke_color, data.min_outline_contrast)
+            };
+            tracing::debug!("contrasting color: {:?}", contrasting_color);
+            outlines.push((*rect, contrasting_color));
+        }
+    }
+    if data.directory_borders {
+        for (path, rect) in subdivided_directories(tree) {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            // Inside the directory's rect, so the borders of siblings don't overlap
+            let inset = widths.directory_border / 2.0;
+            rc.stroke(
+                rect.inset(-inset),
+                &directory_border_color(&name),
+                widths.directory_border,
+            );
+        }
+    }
+    // Stroked last so fills of later shapes can't cover them
+    for (rect, color) in outlines {
+        rc.stroke(rect, &color, widths.outline);
+    }
+}
+
+/// Width of `text` laid out in the system font at `font_size`.
+fn text_width(rc: &mut impl RenderContext, text: &str, font_size: f64) -> f64 {
+    rc.text()
+        .new_text_layout(text.to_owned())
+        .font(FontFamily::SYSTEM_UI, font_size)
+        .build()
+        .map_or(f64::INFINITY, |layout| layout.size().width)
+}
+
+/// Writes the name and compact size of `node` into the top left of `rect`, cut to fit, if it's
+/// tall enough.
+fn draw_label(
+    rc: &mut impl RenderContext,
+    node: &FileNode,
+    rect: Rect,
+    fill_color: Color,
+    max_chars: usize,
+) {
+    if rect.height() < LABEL_FONT_SIZE + 2.0 * TEXT_PADDING {
+        return;
     }
+    let name = node
+        .path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| node.path().display().to_string());
+    let name = format!("{name} {}", format_size_compact(node.size()));
+    let max_width = rect.width() - 2.0 * TEXT_PADDING;
+    let Some(label) = truncate_to_width(&name, max_chars, max_width, |text| {
+        text_width(rc, text, LABEL_FONT_SIZE)
+    }) else {
+        return;
+    };
+    let Ok(layout) = rc
+        .text()
+        .new_text_layout(label)
+        .font(FontFamily::SYSTEM_UI, LABEL_FONT_SIZE)
+        .text_color(get_black_or_white(fill_color))
+        .build()
+    else {
+        return;
+    };
+    rc.draw_text(&layout, rect.origin() + (TEXT_PADDING, TEXT_PADDING));
+}
+
+/// Draws `text` wrapped at `max_width` in a box next to `pos`, kept inside `size`.
+fn draw_tooltip(
+    rc: &mut impl RenderContext,
+    text: &str,
+    pos: druid::Point,
+    size: Size,
+    max_width: f64,
+) {
+    let lines = wrap_to_width(text, max_width, |line| {
+        text_width(rc, line, TOOLTIP_FONT_SIZE)
+    });
+    let layouts: Vec<_> = lines
+        .into_iter()
+        .filter_map(|line| {
+            rc.text()
+                .new_text_layout(line)
+                .font(FontFamily::SYSTEM_UI, TOOLTIP_FONT_SIZE)
+                .text_color(Color::WHITE)
+                .build()
+                .ok()
+        })
+        .collect();
+    let width = layouts
+        .iter()
+        .map(|layout| layout.size().width)
+        .fold(0.0, f64::max);
+    let height: f64 = layouts.iter().map(|layout| layout.size().height).sum();
+    let box_size = Size::new(width + 2.0 * TEXT_PADDING, height + 2.0 * TEXT_PADDING);
+    // Below and right of the pointer, unless that leaves the widget
+    let x = (pos.x + 12.0).min(size.width - box_size.width).max(0.0);
+    let y = (pos.y + 12.0).min(size.height - box_size.height).max(0.0);
+    let rect = Rect::from_origin_size((x, y), box_size);
+    rc.fill(rect, &Color::rgba8(0, 0, 0, 0xdd));
+    rc.stroke(rect, &Color::grey(0.6), 1.0);
+    let mut origin = rect.origin() + (TEXT_PADDING, TEXT_PADDING);
+    for layout in layouts {
+        rc.draw_text(&layout, origin);
+        origin.y += layout.size().height;
+    }
+}
+
+/// Whether the treemap draws the children of visible directories inside them.
+fn nests(data: &crate::AppState) -> bool {
+    data.overview_granularity == OverviewGranularity::Files && data.view_filter == ViewFilter::All
+}
+
+/// Whether `node` is drawn as it is among `files`, rather than left out by the view's filters,
+/// folded into the others or outside the shown directory. If `nested`, so is everything in the
+/// visible directories.
+fn is_drawn(files: &VisibleNodes, nested: bool, node: &FileNode) -> bool {
+    files.nodes.iter().any(|shown| {
+        shown == node
+            || (nested
+                && matches!(shown, FileNode::Directory { .. })
+                && node.path().starts_with(shown.path())
+                && shown.find(node.path()) == Some(node))
+    })
+}
+
+pub(crate) fn visible_nodes_changed(old: &crate::AppState, new: &crate::AppState) -> bool {
+    old.all_files != new.all_files
+        || old.view_root != new.view_root
+        || old.view_filter != new.view_filter
+        || old.hidden_extensions != new.hidden_extensions
+        || old.size_bucket != new.size_bucket
+        || old.max_rects != new.max_rects
+        || old.overview_granularity != new.overview_granularity
+        || old.metric != new.metric
+        || old.group_root_files != new.group_root_files
+}
+
+/// Whether the visible nodes are laid out differently between `old` and `new`, apart from
+/// changes to the nodes themselves.
+fn layout_changed(old: &crate::AppState, new: &crate::AppState) -> bool {
+    old.layout_mode != new.layout_mode
+        || old.treemap_algo != new.treemap_algo
+        || old.treemap_order != new.treemap_order
+        || old.treemap_padding != new.treemap_padding
+}
+
+/// Whether `key` is the plain `T` that cycles the layout, leaving Ctrl, Alt and Meta chords to
+/// menus and the system.
+fn is_layout_shortcut(key: &druid::KeyEvent) -> bool {
+    let chorded = key.mods.ctrl() || key.mods.alt() || key.mods.meta();
+    !chorded && matches!(&key.key, druid::KbKey::Character(c) if c.eq_ignore_ascii_case("t"))
+}
+
+/// Whether anything the visualization draws differs between `old` and `new`.
+fn needs_repaint(old: &crate::AppState, new: &crate::AppState) -> bool {
+    visible_nodes_changed(old, new)
+        || layout_changed(old, new)
+        || old.selected_file != new.selected_file
+        || old.selected_files != new.selected_files
+        || old.selection_style != new.selection_style
+        || old.black_or_white_outlines != new.black_or_white_outlines
+        || old.min_outline_contrast != new.min_outline_contrast
+        || old.cushioned != new.cushioned
+        || old.color_mode != new.color_mode
+        || !old.gradient.same(&new.gradient)
+        || old.directory_borders != new.directory_borders
+        || old.directory_tint != new.directory_tint
+        || old.show_labels != new.show_labels
+        || old.max_label_chars != new.max_label_chars
+        || old.show_tooltips != new.show_tooltips
+        || old.tooltip_width != new.tooltip_width
+        || old.relative_paths != new.relative_paths
+        || old.scanning != new.scanning
+}
+
+/// Describes a scanned root that is a lone file, which gets shown as text instead of a single
+/// rectangle filling the whole widget.
+fn single_file_summary(root: &FileNode) -> Option<String> {
+    let FileNode::File { path, size } = root else {
+        return None;
+    };
+    Some(match size {
+        Some(size) => format!("{}: {size} B", path.display()),
+        None => format!("{}: unknown size", path.display()),
+    })
+}
+
+/// Text shown instead of the visualization, if there's nothing to visualize: instructions
+/// before the first scan, progress while it runs, or the summary of a lone file.
+fn placeholder_message(data: &crate::AppState) -> Option<String> {
+    match &data.all_files {
+        Some(root) => single_file_summary(root),
+        None if data.scanning => Some(String::from("Scanning…")),
+        None => Some(String::from(
+            "Enter a folder and click \"Traverse folder\" to see what takes up its space",
+        )),
+    }
+}
+
+/// Draws `message` centered in `size`.
+fn draw_message(rc: &mut impl RenderContext, size: Size, message: &str) {
+    let layout = match rc
+        .text()
+        .new_text_layout(message.to_owned())
+        .font(FontFamily::SYSTEM_UI, 16.0)
+        .text_color(Color::WHITE)
+        .build()
+    {
+        Ok(layout) => layout,
+        Err(err) => {
+            tracing::warn!("Failed laying out `{message}`: {err}");
+            return;
+        }
+    };
+    let origin = ((size.to_vec2() - layout.size().to_vec2()) / 2.0).to_point();
+    rc.draw_text(&layout, origin);
+}
+
+/// Draws the visualization of `data` filling `size`, unzoomed, on any render context.
+pub(crate) fn draw_state(rc: &mut impl RenderContext, data: &crate::AppState, size: Size) {
+    rc.fill(size.to_rect(), &Color::BLACK);
+    if let Some(message) = placeholder_message(data) {
+        draw_message(rc, size, &message);
+        return;
+    }
+    let Some(root) = &data.all_files else {
+        return;
+    };
+    let files = visible_nodes(data, data.shown_root().unwrap_or(root));
+    // Exported images are drawn at one pixel per logical unit
+    let (shapes, tree) = layout_shapes(data, &files, size);
+    draw_shapes(rc, data, &shapes, &tree, LineWidths::for_scale(1.0));
 }
 
 impl Widget<crate::AppState> for VisualizationWidget {
     fn event(
         &mut self,
-        _ctx: &mut druid::EventCtx,
+        ctx: &mut druid::EventCtx,
         event: &druid::Event,
         data: &mut crate::AppState,
         _env: &druid::Env,
     ) {
-        if let druid::Event::MouseDown(event) = event {
-            let Some((files, total_size)) = &self.files else {
-                tracing::debug!("clicked at x: {}, but don't have any files", event.pos.x);
-                return;
-            };
-            tracing::debug!("clicked at x: {}", event.pos.x);
-            let target_size = *total_size as f64 * (event.pos.x / self.width);
-
-            let mut size_so_far = 0;
-            let Some(file) = files.iter()
-                .skip_while(|&file| {
-                    size_so_far += file.size();
-                    (size_so_far as f64) < target_size
-                })
-                .next() else {
+        match event {
+            druid::Event::Command(command) if command.is(SELECTION_DRAWN) => {
+                data.selection_drawn = *command.get_unchecked(SELECTION_DRAWN);
+                ctx.set_handled();
+            }
+            druid::Event::Command(command) if command.is(NODES_FOLDED) => {
+                data.nodes_folded = *command.get_unchecked(NODES_FOLDED);
+                ctx.set_handled();
+            }
+            druid::Event::MouseMove(event) => {
+                self.mouse_pos = event.pos;
+                if data.show_tooltips {
+                    let hovered = self.node_at(data, event.pos).cloned();
+                    if hovered != self.hovered {
+                        self.hovered = hovered;
+                        ctx.request_paint();
+                    }
+                }
+            }
+            druid::Event::Wheel(event) => {
+                if event.mods.ctrl() {
+                    // Trackpad pinches also arrive as ctrl+scroll on most platforms
+                    let factor = (-event.wheel_delta.y * ZOOM_PER_SCROLL_UNIT).exp();
+                    self.viewport.zoom_at(factor, event.pos, self.size);
+                } else {
+                    self.viewport.pan(-event.wheel_delta, self.size);
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            druid::Event::Zoom(delta) => {
+                self.viewport
+                    .zoom_at(1.0 + delta, self.mouse_pos, self.size);
+                ctx.request_paint();
+            }
+            druid::Event::AnimFrame(interval) => {
+                if let Some(animation) = &mut self.drill_animation {
+                    animation.progress += *interval as f64 / 1e9 / DRILL_ANIMATION_DURATION;
+                    if animation.progress >= 1.0 {
+                        self.drill_animation = None;
+                    } else {
+                        ctx.request_anim_frame();
+                    }
+                    ctx.request_paint();
+                }
+                if let Some(animation) = &mut self.growth_animation {
+                    animation.progress += *interval as f64 / 1e9 / animation.duration;
+                    if animation.progress >= 1.0 {
+                        self.growth_animation = None;
+                    } else {
+                        ctx.request_anim_frame();
+                    }
+                    ctx.request_paint();
+                }
+            }
+            druid::Event::KeyDown(key) if is_layout_shortcut(key) => {
+                data.layout_mode = data.layout_mode.next();
+                ctx.set_handled();
+            }
+            druid::Event::MouseDown(event) => {
+                // Focused so the layout shortcut reaches it
+                ctx.request_focus();
+                let Some(file) = self.node_at(data, event.pos).cloned() else {
                     tracing::warn!("clicked on empty space");
-                    data.selected_file = None;
+                    if !event.mods.ctrl() {
+                        data.select_only(None);
+                    }
                     return;
                 };
 
-            assert!(
-                matches!(file, FileNode::File { .. }),
-                "Folders shouldn't be clickable"
-            );
-
-            tracing::debug!("clicked: {} ({} B)", file.path().display(), file.size());
-            data.selected_file = Some(file.clone());
+                tracing::debug!("clicked: {} ({} B)", file.path().display(), file.size());
+                let drills = event.count >= 2
+                    || data.overview_granularity == OverviewGranularity::TopLevelDirs;
+                if drills && !event.mods.ctrl() {
+                    if let Some(directory) = self.drill_target(&file) {
+                        tracing::debug!("drilling into {}", directory.path().display());
+                        data.set_view_root(Some(directory.path().clone()));
+                        data.select_only(None);
+                        return;
+                    }
+                }
+                if event.mods.ctrl() {
+                    data.toggle_selected(file);
+                } else {
+                    data.select_only(Some(file));
+                }
+            }
+            _ => {}
         }
     }
 
     fn lifecycle(
         &mut self,
-        _ctx: &mut druid::LifeCycleCtx,
+        ctx: &mut druid::LifeCycleCtx,
         event: &druid::LifeCycle,
-        _data: &crate::AppState,
+        data: &crate::AppState,
         _env: &druid::Env,
     ) {
-        if let LifeCycle::Size(size) = event {
-            self.width = size.width;
+        match event {
+            LifeCycle::Size(size) => {
+                self.size = *size;
+                // A bar fits fewer nodes the narrower it is
+                let folded = self.folds_nodes(data.layout_mode);
+                if folded != data.nodes_folded {
+                    ctx.submit_command(NODES_FOLDED.with(folded).to(ctx.widget_id()));
+                }
+            }
+            LifeCycle::BuildFocusChain => ctx.register_for_focus(),
+            LifeCycle::HotChanged(false) if self.hovered.is_some() => {
+                self.hovered = None;
+                ctx.request_paint();
+            }
+            _ => {}
         }
     }
 
     fn update(
         &mut self,
         ctx: &mut druid::UpdateCtx,
-        _old_data: &crate::AppState,
+        old_data: &crate::AppState,
         data: &crate::AppState,
         _env: &druid::Env,
     ) {
-        self.files = match &data.all_files {
-            None => None,
-            Some(files) => Some((
-                files.clone().as_vector(),
-                files
-                    .clone()
-                    .as_vector()
-                    .iter()
-                    .map(|node| node.size())
-                    .sum::<u64>(),
-            )),
-        };
-        ctx.request_paint();
+        if old_data.view_root != data.view_root {
+            self.drill_animation = data
+                .view_root
+                .as_deref()
+                .and_then(|directory| self.drawn_rect(directory))
+                .map(|from| DrillAnimation {
+                    from,
+                    progress: 0.0,
+                });
+            if self.drill_animation.is_some() {
+                ctx.request_anim_frame();
+            }
+            self.viewport = Viewport::default();
+        }
+        let files_changed = visible_nodes_changed(old_data, data);
+        if self.update_files(old_data, data) {
+            ctx.request_anim_frame();
+        }
+        let selection_moved = files_changed
+            || old_data.selected_file != data.selected_file
+            || old_data.layout_mode != data.layout_mode;
+        if selection_moved {
+            let nested = data.layout_mode == LayoutMode::Treemap && nests(data);
+            let drawn = match (&data.selected_file, &self.files) {
+                (Some(file), Some(files)) => is_drawn(files, nested, file),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            if drawn != data.selection_drawn {
+                ctx.submit_command(SELECTION_DRAWN.with(drawn).to(ctx.widget_id()));
+            }
+        }
+        let folded = self.folds_nodes(data.layout_mode);
+        if folded != data.nodes_folded {
+            ctx.submit_command(NODES_FOLDED.with(folded).to(ctx.widget_id()));
+        }
+        // What's under the mouse is somewhere else in another layout
+        if old_data.layout_mode != data.layout_mode {
+            self.hovered = None;
+        }
+        if layout_changed(old_data, data) {
+            self.laid_out = None;
+        }
+        if needs_repaint(old_data, data) {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -101,72 +1046,862 @@ impl Widget<crate::AppState> for VisualizationWidget {
         let size = ctx.size();
         ctx.fill(Rect::new(0.0, 0.0, size.width, size.height), &Color::BLACK);
 
-        let Some(files) = &self.files else {
+        let too_narrow = data.layout_mode == LayoutMode::Bar && bar_capacity(size.width).is_none();
+        let message = placeholder_message(data)
+            .or_else(|| too_narrow.then(|| String::from("Too small to show detail")));
+        if let Some(message) = message {
+            self.shapes = Vec::new();
+            self.tree = Vec::new();
+            self.laid_out = None;
+            ctx.with_save(|ctx| {
+                ctx.clip(size.to_rect());
+                draw_message(ctx.render_ctx, size, &message);
+            });
             return;
-        };
+        }
 
-        let total_filesize: f64 = files.1 as f64;
-        tracing::debug!("total filesize is {}", total_filesize);
-        let mut done = 0f64;
-        for node in files.0.iter() {
-            assert!(
-                matches!(node, FileNode::File { .. }),
-                "VisualizationWidget can only draw Files, not directories"
-            );
-            let percentage = node.size() as f64 / total_filesize;
-            tracing::debug!(
-                "Drawing `{}` which makes up {}% of width",
-                node.path().display(),
-                percentage
+        let widths = LineWidths::for_scale(ctx.scale().x());
+        ctx.with_save(|ctx| {
+            ctx.clip(size.to_rect());
+            if let Some(animation) = &self.drill_animation {
+                let to = lerp_rect(animation.from, size.to_rect(), animation.progress);
+                ctx.transform(rect_transform(size.to_rect(), to));
+            }
+            ctx.transform(self.viewport.transform());
+            if !self.is_laid_out_for(size) {
+                (self.shapes, self.tree) = match (&self.files, &self.growth_animation) {
+                    (Some(files), Some(animation)) => {
+                        let sizes = interpolate_sizes(
+                            &animation.from,
+                            &files.nodes,
+                            &files.sizes,
+                            animation.progress,
+                        );
+                        let growing = VisibleNodes::with_sizes(files.nodes.clone(), sizes);
+                        layout_shapes(data, &growing, size)
+                    }
+                    (Some(files), None) => layout_shapes(data, files, size),
+                    (None, _) => Default::default(),
+                };
+                self.laid_out = self.files.clone().map(|files| (size, files));
+            }
+            draw_shapes(ctx.render_ctx, data, &self.shapes, &self.tree, widths);
+        });
+        if let Some(hovered) = self.hovered.as_ref().filter(|_| data.show_tooltips) {
+            let path = data.display_path(hovered.path());
+            let text = match data.compressed_sizes.get(hovered.path()) {
+                Some(compressed) => format!(
+                    "{path} ({}, {} compressed)",
+                    format_size(hovered.size()),
+                    format_size(*compressed)
+                ),
+                None => format!("{path} ({})", format_size(hovered.size())),
+            };
+            draw_tooltip(
+                ctx.render_ctx,
+                &text,
+                self.mouse_pos,
+                size,
+                data.tooltip_width,
             );
+        }
+        tracing::debug!("Done painting");
+    }
+}
 
-            let file_rect = Rect::new(
-                size.width * done,
-                0.0,
-                size.width * (done + percentage),
-                size.height,
-            );
-            // Blue to green (possibly less red/blue for blue light filter)
-            // let stroke_color = Color::rgb(0.0, done, 1.0 - done);
-            // Red to blue (Bi theme)
-            let stroke_color = Color::rgb(1.0 - done, 0.0, done);
-            // Greyscale
-            // let stroke_color = Color::rgb(done, done, done);
-            ctx.fill(file_rect.inset(-1.0), &stroke_color);
-            if let Some(selected) = &data.selected_file {
-                if node == selected {
-                    let contrasting_color = get_contrasting_color(stroke_color);
-                    tracing::debug!("contrasting color: {:?}", contrasting_color);
-                    ctx.paint_with_z_index(1, move |ctx| {
-                        ctx.stroke(file_rect, &contrasting_color, 2.0)
-                    });
+#[cfg(test)]
+mod tests {
+    use druid::im::vector;
+    use std::path::Path;
+
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+
+    use druid::Size;
+
+    use druid::Rect;
+
+    use super::{
+        bar_capacity, bar_segments, cap_nodes, fit_bar, interpolate_sizes, is_layout_shortcut,
+        layout_segments, layout_shapes, lerp_rect, nearest_node, needs_repaint,
+        placeholder_message, rect_transform, row_at, row_node_at, segment_at, single_file_summary,
+        top_level_rows, visible_nodes, LayoutMode, LineWidths, OverviewGranularity, Segment,
+        ViewFilter, VisibleNodes, VisualizationWidget, MIN_BAR_WIDTH_PX,
+    };
+    use crate::file_system::{traverse, FileNode, Metric, ScanOptions};
+    use crate::AppState;
+
+    fn tree() -> FileNode {
+        FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/a".into(),
+                    children: vector![FileNode::File {
+                        path: "/a/1".into(),
+                        size: Some(1),
+                    }],
+                },
+                FileNode::File {
+                    path: "/2".into(),
+                    size: Some(2),
+                },
+            ],
+        }
+    }
+
+    fn visible_paths(filter: ViewFilter) -> Vec<String> {
+        filter
+            .visible_nodes(&tree())
+            .iter()
+            .map(|node| node.path().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn view_filter_all() {
+        assert_eq!(visible_paths(ViewFilter::All), ["/a", "/2"]);
+    }
+
+    #[test]
+    fn direct_files_group_into_one_node() {
+        let mut data = AppState {
+            view_filter: ViewFilter::All,
+            group_root_files: true,
+            ..Default::default()
+        };
+        let mut root = tree();
+        if let FileNode::Directory { children, .. } = &mut root {
+            children.push_back(FileNode::File {
+                path: "/3".into(),
+                size: Some(3),
+            });
+        }
+        let files = visible_nodes(&data, &root);
+        let paths: Vec<_> = files
+            .nodes
+            .iter()
+            .map(|node| node.path().display().to_string())
+            .collect();
+        assert_eq!(paths, ["/a", "/2 files"]);
+        assert_eq!(files.sizes, [1, 5]);
+
+        data.metric = Metric::FileCount;
+        assert_eq!(visible_nodes(&data, &root).sizes, [1, 2]);
+        // Only direct children are grouped
+        data.view_filter = ViewFilter::Files;
+        assert_eq!(visible_nodes(&data, &root).nodes.len(), 3);
+    }
+
+    #[test]
+    fn view_filter_files() {
+        assert_eq!(visible_paths(ViewFilter::Files), ["/a/1", "/2"]);
+    }
+
+    #[test]
+    fn view_filter_directories() {
+        assert_eq!(visible_paths(ViewFilter::Directories), ["/a"]);
+        let total: u64 = ViewFilter::Directories
+            .visible_nodes(&tree())
+            .iter()
+            .map(FileNode::size)
+            .sum();
+        assert_eq!(total, 1);
+    }
+
+    #[test]
+    fn only_plain_t_cycles_the_layout() {
+        use druid::{KeyEvent, Modifiers};
+        assert!(is_layout_shortcut(&KeyEvent::for_test(
+            Modifiers::empty(),
+            "t"
+        )));
+        assert!(is_layout_shortcut(&KeyEvent::for_test(
+            Modifiers::SHIFT,
+            "T"
+        )));
+        assert!(!is_layout_shortcut(&KeyEvent::for_test(
+            Modifiers::CONTROL,
+            "t"
+        )));
+        assert!(!is_layout_shortcut(&KeyEvent::for_test(
+            Modifiers::ALT,
+            "t"
+        )));
+        assert!(!is_layout_shortcut(&KeyEvent::for_test(
+            Modifiers::empty(),
+            "r"
+        )));
+    }
+
+    #[test]
+    fn typing_a_folder_does_not_repaint() {
+        let old = AppState {
+            all_files: Some(tree()),
+            ..Default::default()
+        };
+        let new = AppState {
+            folder: "/somewhere/else".into(),
+            ..old.clone()
+        };
+        assert!(!needs_repaint(&old, &new));
+
+        let selected = AppState {
+            selected_file: Some(tree()),
+            ..old.clone()
+        };
+        assert!(needs_repaint(&old, &selected));
+        let wider_tooltips = AppState {
+            tooltip_width: old.tooltip_width * 2.0,
+            ..old.clone()
+        };
+        assert!(needs_repaint(&old, &wider_tooltips));
+    }
+
+    #[test]
+    fn single_file_is_summarized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lonely");
+        std::fs::write(&path, [0; 42]).unwrap();
+
+        let root = traverse(
+            &path,
+            &ScanOptions::default(),
+            &AtomicU64::new(0),
+            &Default::default(),
+        )
+        .unwrap()
+        .root;
+        assert_eq!(
+            root,
+            FileNode::File {
+                path: path.clone(),
+                size: Some(42),
+            }
+        );
+        assert_eq!(
+            single_file_summary(&root),
+            Some(format!("{}: 42 B", path.display()))
+        );
+        assert_eq!(single_file_summary(&tree()), None);
+
+        for layout_mode in [LayoutMode::Bar, LayoutMode::Treemap] {
+            for view_filter in [ViewFilter::All, ViewFilter::Files, ViewFilter::Directories] {
+                let data = AppState {
+                    all_files: Some(root.clone()),
+                    layout_mode,
+                    view_filter,
+                    ..Default::default()
+                };
+                let files = VisibleNodes::new(view_filter.visible_nodes(&root), Metric::Bytes);
+                layout_shapes(&data, &files, Size::new(100.0, 100.0));
+            }
+        }
+    }
+
+    #[test]
+    fn bar_fractions_add_up() {
+        let files = VisibleNodes::new(ViewFilter::Files.visible_nodes(&tree()), Metric::Bytes);
+        let segments = layout_segments(&files.sizes, 300.0);
+        assert_eq!(segments.first().unwrap().start, 0.0);
+        assert_eq!(segments.last().unwrap().end, 300.0);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+
+        let empty = VisibleNodes::new(
+            vector![FileNode::File {
+                path: "/empty".into(),
+                size: Some(0),
+            }],
+            Metric::Bytes,
+        );
+        assert_eq!(
+            layout_segments(&empty.sizes, 300.0),
+            [Segment {
+                start: 0.0,
+                end: 0.0
+            }]
+        );
+    }
+
+    #[test]
+    fn huge_sizes_fill_the_whole_bar() {
+        let sizes = [u64::MAX / 3, u64::MAX / 3, u64::MAX / 3, u64::MAX / 7, 1];
+        for width in [1.0, 333.3, 1920.0, 7680.0] {
+            let segments = layout_segments(&sizes, width);
+            assert_eq!(segments.last().unwrap().end, width);
+            for pair in segments.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+        }
+    }
+
+    #[test]
+    fn clicking_a_segment_midpoint_selects_it() {
+        let layouts: [&[u64]; 4] = [&[1], &[1, 1, 1], &[7, 1, 0, 3, 1000], &[1, 2, 3, 4, 5, 6]];
+        for sizes in layouts {
+            for length in [1.0, 123.4, 1920.0] {
+                let segments = layout_segments(sizes, length);
+                for (index, segment) in segments.iter().enumerate() {
+                    if segment.start == segment.end {
+                        continue;
+                    }
+                    let midpoint = (segment.start + segment.end) / 2.0;
+                    assert_eq!(segment_at(&segments, midpoint), Some(index), "{sizes:?}");
+                    assert_eq!(segment_at(&segments, segment.start), Some(index));
                 }
+                assert_eq!(segment_at(&segments, -1.0), None);
             }
-            done += percentage;
         }
-        tracing::debug!("Done painting");
     }
-}
 
-fn get_contrasting_color(color: Color) -> Color {
-    let (red, green, blue, _) = color.as_rgba();
-    // Calculate the relative luminance of the color
-    let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    #[test]
+    fn drill_animation_interpolates_rects() {
+        let from = Rect::new(10.0, 20.0, 30.0, 60.0);
+        let to = Rect::new(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(lerp_rect(from, to, 0.0), from);
+        assert_eq!(lerp_rect(from, to, 0.5), Rect::new(5.0, 10.0, 65.0, 80.0));
+        assert_eq!(lerp_rect(from, to, 1.0), to);
+
+        let transform = rect_transform(to, from);
+        assert_eq!(transform.transform_rect_bbox(to), from);
+    }
 
-    // Handle the case of grey shades separately
-    if red == green && green == blue {
-        let contrasting_lightness = if luminance > 0.5 { 0.0 } else { 1.0 };
-        return Color::rgb(
-            contrasting_lightness,
-            contrasting_lightness,
-            contrasting_lightness,
+    #[test]
+    fn top_level_segments_are_sized_by_contents() {
+        let data = AppState {
+            overview_granularity: OverviewGranularity::TopLevelDirs,
+            view_filter: ViewFilter::Files,
+            ..Default::default()
+        };
+        let segments = visible_nodes(&data, &tree())
+            .nodes
+            .iter()
+            .map(|node| (node.path().display().to_string(), node.size()))
+            .collect::<Vec<_>>();
+        assert_eq!(segments, [(String::from("/a"), 1), (String::from("/2"), 2)]);
+    }
+
+    #[test]
+    fn near_miss_selects_nearest_rect() {
+        let shapes = vec![
+            (
+                FileNode::File {
+                    path: "/a".into(),
+                    size: Some(1),
+                },
+                Rect::new(0.0, 0.0, 10.0, 10.0),
+                0.0,
+            ),
+            (
+                FileNode::File {
+                    path: "/b".into(),
+                    size: Some(1),
+                },
+                Rect::new(14.0, 0.0, 15.0, 10.0),
+                0.5,
+            ),
+        ];
+        let near = |x, y, tolerance| {
+            nearest_node(&shapes, druid::Point::new(x, y), tolerance)
+                .map(|node| node.path().display().to_string())
+        };
+        assert_eq!(near(5.0, 5.0, 0.0).as_deref(), Some("/a"));
+        assert_eq!(near(12.5, 5.0, 4.0).as_deref(), Some("/b"));
+        assert_eq!(near(11.5, 5.0, 4.0).as_deref(), Some("/a"));
+        assert_eq!(near(15.0, 13.0, 4.0).as_deref(), Some("/b"));
+        assert_eq!(near(20.0, 5.0, 4.0), None);
+        assert_eq!(near(12.5, 5.0, 1.0), None);
+    }
+
+    #[test]
+    fn file_count_metric_sizes_segments_by_files() {
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![
+                FileNode::Directory {
+                    path: "/many".into(),
+                    children: (0..3)
+                        .map(|index| FileNode::File {
+                            path: format!("/many/{index}").into(),
+                            size: Some(1),
+                        })
+                        .collect(),
+                },
+                FileNode::File {
+                    path: "/big".into(),
+                    size: Some(100),
+                },
+            ],
+        };
+        let segment_sizes = |metric| {
+            let data = AppState {
+                all_files: Some(root.clone()),
+                view_filter: ViewFilter::All,
+                metric,
+                ..Default::default()
+            };
+            let files = visible_nodes(&data, &root);
+            layout_segments(&files.sizes, 400.0)
+                .iter()
+                .map(|segment| segment.end - segment.start)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            segment_sizes(Metric::Bytes),
+            [400.0 * 3.0 / 103.0, 400.0 * 100.0 / 103.0]
+        );
+        assert_eq!(segment_sizes(Metric::FileCount), [300.0, 100.0]);
+    }
+
+    #[test]
+    fn line_widths_scale_but_hit_testing_stays_logical() {
+        assert_eq!(
+            LineWidths::for_scale(1.0),
+            LineWidths {
+                fill_overlap: 1.0,
+                outline: 2.0,
+                directory_border: 1.0,
+            }
+        );
+        assert_eq!(
+            LineWidths::for_scale(2.0),
+            LineWidths {
+                fill_overlap: 0.5,
+                outline: 1.0,
+                directory_border: 0.5,
+            }
+        );
+
+        // Shapes are laid out in the logical size whatever the scale, so a click in logical
+        // coordinates hits what's drawn there
+        let data = AppState {
+            layout_mode: LayoutMode::Bar,
+            ..Default::default()
+        };
+        let files = VisibleNodes::new(ViewFilter::Files.visible_nodes(&tree()), Metric::Bytes);
+        let (shapes, _) = layout_shapes(&data, &files, Size::new(300.0, 50.0));
+        assert_eq!(shapes[1].1, Rect::new(100.0, 0.0, 300.0, 50.0));
+        assert_eq!(
+            nearest_node(&shapes, druid::Point::new(200.0, 25.0), 0.0).map(FileNode::path),
+            Some(&"/2".into())
         );
     }
 
-    // Determine the contrasting color by inverting the RGB values and adjusting the lightness
-    let contrasting_red = 1.0 - red;
-    let contrasting_green = 1.0 - green;
-    let contrasting_blue = 1.0 - blue;
+    #[test]
+    fn empty_state_before_first_scan() {
+        let mut data = AppState::default();
+        let empty_state = placeholder_message(&data).unwrap();
+        assert!(empty_state.contains("Traverse folder"), "{empty_state}");
+
+        data.scanning = true;
+        assert_eq!(placeholder_message(&data).as_deref(), Some("Scanning…"));
+
+        // A rescan keeps showing the previous tree
+        data.all_files = Some(tree());
+        assert_eq!(placeholder_message(&data), None);
+        data.scanning = false;
+        assert_eq!(placeholder_message(&data), None);
+    }
+
+    #[test]
+    fn directories_pass_through_hit_testing() {
+        let size = Size::new(300.0, 100.0);
+        for layout_mode in [LayoutMode::Bar, LayoutMode::Treemap, LayoutMode::Rows] {
+            let mut data = AppState {
+                all_files: Some(tree()),
+                layout_mode,
+                view_filter: ViewFilter::Directories,
+                ..Default::default()
+            };
+            let files = visible_nodes(&data, &tree());
+            let (shapes, tree) = layout_shapes(&data, &files, size);
+            let widget = VisualizationWidget {
+                size,
+                files: Some(Arc::new(files)),
+                shapes,
+                tree,
+                ..Default::default()
+            };
+            let center = druid::Point::new(150.0, 50.0);
+            let hit = match layout_mode {
+                LayoutMode::Bar => widget.bar_node_at(center.x),
+                LayoutMode::Treemap => widget.treemap_node_at(center),
+                LayoutMode::Rows => widget.rows_node_at(Path::new("/"), center),
+            }
+            .cloned()
+            .unwrap();
+            assert!(matches!(hit, FileNode::Directory { .. }));
+            assert_eq!(widget.drill_target(&hit), Some(&hit));
+
+            data.select_only(Some(hit.clone()));
+            assert_eq!(data.selected_size(), hit.size());
+            layout_shapes(&data, widget.files.as_ref().unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn folding_is_noted_only_when_nodes_are_folded() {
+        let nodes = (0..20)
+            .map(|i| FileNode::File {
+                path: format!("/{i}").into(),
+                size: Some(1),
+            })
+            .collect();
+        let files = VisibleNodes::new(nodes, Default::default());
+        let mut widget = VisualizationWidget {
+            size: Size::new(300.0, 100.0),
+            files: Some(Arc::new(files.clone())),
+            ..Default::default()
+        };
+        assert!(!widget.folds_nodes(LayoutMode::Bar));
+        assert!(!widget.folds_nodes(LayoutMode::Treemap));
+
+        // Fewer fit in a narrow bar, but a treemap still draws all of them
+        widget.size.width = MIN_BAR_WIDTH_PX;
+        assert!(widget.folds_nodes(LayoutMode::Bar));
+        assert!(!widget.folds_nodes(LayoutMode::Treemap));
+
+        widget.files = Some(Arc::new(cap_nodes(files, 10)));
+        assert!(widget.folds_nodes(LayoutMode::Treemap));
+    }
+
+    #[test]
+    fn layouts_are_kept_until_size_or_nodes_change() {
+        let size = Size::new(300.0, 100.0);
+        let files = Arc::new(visible_nodes(&AppState::default(), &tree()));
+        let mut widget = VisualizationWidget {
+            size,
+            files: Some(files.clone()),
+            laid_out: Some((size, files.clone())),
+            ..Default::default()
+        };
+        assert!(widget.is_laid_out_for(size));
+        assert!(!widget.is_laid_out_for(Size::new(200.0, 100.0)));
+
+        // Nodes derived again are laid out again, without comparing them to the old ones
+        widget.files = Some(Arc::new(files.as_ref().clone()));
+        assert!(!widget.is_laid_out_for(size));
+        widget.laid_out = None;
+        assert!(!widget.is_laid_out_for(size));
+    }
+
+    #[test]
+    fn selections_are_drawn_unless_filtered_out() {
+        let nested = FileNode::Directory {
+            path: "/sub".into(),
+            children: vector![FileNode::File {
+                path: "/sub/b.png".into(),
+                size: Some(2),
+            }],
+        };
+        let rs = FileNode::File {
+            path: "/a.rs".into(),
+            size: Some(1),
+        };
+        let root = FileNode::Directory {
+            path: "/".into(),
+            children: vector![nested.clone(), rs.clone()],
+        };
+        let mut data = AppState {
+            view_filter: ViewFilter::All,
+            ..Default::default()
+        };
+        let drawn = |data: &AppState, node: &FileNode| {
+            let nested = data.layout_mode == LayoutMode::Treemap && super::nests(data);
+            super::is_drawn(&visible_nodes(data, &root), nested, node)
+        };
+        let png = nested.find(Path::new("/sub/b.png")).unwrap().clone();
+        assert!(drawn(&data, &rs));
+        assert!(drawn(&data, &nested));
+        // Only a nested treemap draws what's in a directory
+        assert!(!drawn(&data, &png));
+        data.layout_mode = LayoutMode::Treemap;
+        assert!(drawn(&data, &png));
+
+        data.hidden_extensions.insert(String::from("rs"));
+        assert!(!drawn(&data, &rs));
+        data.hidden_extensions.clear();
+        data.view_filter = ViewFilter::Directories;
+        assert!(!drawn(&data, &rs));
+    }
 
-    Color::rgb(contrasting_red, contrasting_green, contrasting_blue)
+    #[test]
+    fn stand_ins_cant_be_clicked() {
+        let data = AppState {
+            layout_mode: LayoutMode::Treemap,
+            overview_granularity: OverviewGranularity::Files,
+            view_filter: ViewFilter::All,
+            ..Default::default()
+        };
+        let folded = FileNode::Directory {
+            path: "/a".into(),
+            children: vector![FileNode::Aggregate {
+                path: "/a/3 items".into(),
+                size: 30,
+                count: 3,
+            }],
+        };
+        let others = FileNode::Aggregate {
+            path: "2 others".into(),
+            size: 20,
+            count: 2,
+        };
+        let files = VisibleNodes::new(vector![folded.clone(), others.clone()], Metric::Bytes);
+        let size = Size::new(100.0, 100.0);
+        let (shapes, tree) = layout_shapes(&data, &files, size);
+        let widget = VisualizationWidget {
+            size,
+            files: Some(Arc::new(files)),
+            shapes,
+            tree,
+            ..Default::default()
+        };
+        let center_of = |node: &FileNode| {
+            let (_, rect, _) = widget
+                .shapes
+                .iter()
+                .find(|(shape, ..)| shape == node)
+                .unwrap();
+            rect.center()
+        };
+        // The folder around one is hit instead
+        let items = FileNode::Aggregate {
+            path: "/a/3 items".into(),
+            size: 30,
+            count: 3,
+        };
+        assert_eq!(widget.node_at(&data, center_of(&items)), Some(&folded));
+        assert_eq!(widget.node_at(&data, center_of(&others)), None);
+    }
+
+    #[test]
+    fn rows_map_points_to_their_row_and_file() {
+        let file = |path: &str, size| FileNode::File {
+            path: path.into(),
+            size: Some(size),
+        };
+        let nodes = vector![
+            file("/root/a/1", 1),
+            file("/root/a/2", 3),
+            file("/root/b/x/3", 100),
+            file("/root/4", 2),
+            file("5 others", 5),
+        ];
+        let rows = top_level_rows(&nodes, Path::new("/root"));
+        assert_eq!(rows, [0..2, 2..3, 3..4, 4..5]);
+
+        assert_eq!(row_at(4, 100.0, 0.0), Some(0));
+        assert_eq!(row_at(4, 100.0, 49.9), Some(1));
+        assert_eq!(row_at(4, 100.0, 99.9), Some(3));
+        assert_eq!(row_at(4, 100.0, 100.0), None);
+        assert_eq!(row_at(0, 100.0, 10.0), None);
+
+        // Each row is split by its own total, however small it is next to the others
+        let sizes: Vec<u64> = nodes.iter().map(FileNode::size).collect();
+        let size = Size::new(100.0, 100.0);
+        let at = |x, y| row_node_at(&rows, &sizes, size, druid::Point::new(x, y));
+        assert_eq!(at(10.0, 10.0), Some(0));
+        assert_eq!(at(30.0, 10.0), Some(1));
+        assert_eq!(at(99.0, 24.0), Some(1));
+        assert_eq!(at(1.0, 26.0), Some(2));
+        assert_eq!(at(50.0, 60.0), Some(3));
+        assert_eq!(at(50.0, 90.0), Some(4));
+        assert_eq!(at(50.0, 120.0), None);
+    }
+
+    #[test]
+    fn sparkline_is_the_bar_scaled_down() {
+        let data = AppState {
+            all_files: Some(tree()),
+            layout_mode: LayoutMode::Bar,
+            ..Default::default()
+        };
+        let files = visible_nodes(&data, &tree());
+        let (shapes, _) = layout_shapes(&data, &files, Size::new(600.0, 40.0));
+        let sparkline = bar_segments(&data);
+        assert_eq!(sparkline.len(), shapes.len());
+        for ((segment, _), (node, rect, _)) in sparkline.iter().zip(&shapes) {
+            assert_eq!(&segment.path, node.path());
+            assert!((segment.start_fraction * 600.0 - rect.x0).abs() < 1e-9);
+            assert!((segment.end_fraction * 600.0 - rect.x1).abs() < 1e-9);
+        }
+        assert_eq!(
+            sparkline.last().map(|(segment, _)| segment.end_fraction),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn layout_shortcut_cycles_modes() {
+        let mut mode = LayoutMode::default();
+        let mut visited = Vec::new();
+        for _ in 0..4 {
+            visited.push(mode);
+            mode = mode.next();
+        }
+        assert_eq!(
+            visited,
+            [
+                LayoutMode::Bar,
+                LayoutMode::Treemap,
+                LayoutMode::Rows,
+                LayoutMode::Bar
+            ]
+        );
+
+        let (old, new) = (
+            AppState::default(),
+            AppState {
+                layout_mode: LayoutMode::Bar.next(),
+                ..Default::default()
+            },
+        );
+        assert!(needs_repaint(&old, &new));
+    }
+
+    #[test]
+    fn visible_nodes_share_the_tree() {
+        let children: druid::im::Vector<_> = (0..20)
+            .map(|i| FileNode::File {
+                path: format!("/{i}").into(),
+                size: Some(i),
+            })
+            .collect();
+        let data = AppState {
+            all_files: Some(FileNode::Directory {
+                path: "/".into(),
+                children: children.clone(),
+            }),
+            view_filter: ViewFilter::All,
+            ..Default::default()
+        };
+        let mut widget = VisualizationWidget::default();
+        widget.update_files(&AppState::default(), &data);
+
+        // No node was copied out of the tree, and copies of the widget share what it derived
+        let files = widget.files.as_ref().unwrap();
+        assert!(files.nodes.ptr_eq(&children));
+        assert!(Arc::ptr_eq(files, widget.clone().files.as_ref().unwrap()));
+
+        // Unchanged data keeps the derived nodes instead of deriving them again
+        let before = Arc::clone(files);
+        widget.update_files(&data, &data);
+        assert!(Arc::ptr_eq(&before, widget.files.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn rescanned_sizes_grow_from_the_old_ones() {
+        let from = [("/grown", 100), ("/shrunk", 50), ("/gone", 7)]
+            .into_iter()
+            .map(|(path, size)| (path.into(), size))
+            .collect();
+        let nodes = vector![
+            FileNode::File {
+                path: "/grown".into(),
+                size: Some(300),
+            },
+            FileNode::File {
+                path: "/shrunk".into(),
+                size: Some(10),
+            },
+            FileNode::File {
+                path: "/new".into(),
+                size: Some(40),
+            },
+        ];
+        let sizes = [300, 10, 40];
+        let at = |t| interpolate_sizes(&from, &nodes, &sizes, t);
+        assert_eq!(at(0.0), [100, 50, 0]);
+        assert_eq!(at(0.25), [150, 40, 10]);
+        assert_eq!(at(0.5), [200, 30, 20]);
+        assert_eq!(at(1.0), sizes);
+        assert_eq!(at(2.0), sizes);
+    }
+
+    #[test]
+    fn rescans_animate_only_when_asked() {
+        let tree = |size| {
+            Some(FileNode::Directory {
+                path: "/".into(),
+                children: vector![FileNode::File {
+                    path: "/a".into(),
+                    size: Some(size),
+                }],
+            })
+        };
+        let before = AppState {
+            all_files: tree(1),
+            animate_rescans: true,
+            ..Default::default()
+        };
+        let after = AppState {
+            all_files: tree(2),
+            ..before.clone()
+        };
+        let mut widget = VisualizationWidget::default();
+        widget.update_files(&AppState::default(), &before);
+        assert!(widget.growth_animation.is_none());
+        assert!(widget.update_files(&before, &after));
+        let animation = widget.growth_animation.clone().unwrap();
+        assert_eq!(animation.from[Path::new("/a")], 1);
+        assert_eq!(animation.duration, 1.0);
+
+        // Snapping to the new sizes otherwise
+        let (before, after) = (
+            AppState {
+                animate_rescans: false,
+                ..before
+            },
+            AppState {
+                animate_rescans: false,
+                ..after
+            },
+        );
+        let mut widget = VisualizationWidget::default();
+        widget.update_files(&AppState::default(), &before);
+        assert!(!widget.update_files(&before, &after));
+    }
+
+    #[test]
+    fn degenerate_bar_widths_show_no_detail() {
+        assert_eq!(bar_capacity(0.0), None);
+        assert_eq!(bar_capacity(15.9), None);
+        assert_eq!(bar_capacity(f64::NAN), None);
+        assert_eq!(bar_capacity(16.0), Some(16));
+        assert_eq!(bar_capacity(4000.5), Some(4000));
+
+        let nodes = (0..100)
+            .map(|index| FileNode::File {
+                path: format!("/{index}").into(),
+                size: Some(index + 1),
+            })
+            .collect::<druid::im::Vector<_>>();
+        let files = VisibleNodes::new(nodes, Default::default());
+        assert!(fit_bar(&files, 10.0).is_none());
+        // Wide enough for every file, they're all kept
+        assert!(matches!(
+            fit_bar(&files, 2000.0),
+            Some(std::borrow::Cow::Borrowed(_))
+        ));
+        // Otherwise the largest files and one standing in for the rest fill the pixels
+        let fitted = fit_bar(&files, 40.0).unwrap();
+        assert_eq!(fitted.nodes.len(), 40);
+        assert_eq!(fitted.nodes[0].path(), Path::new("/61"));
+        assert_eq!(fitted.nodes[39].path(), Path::new("61 others"));
+        assert_eq!(
+            fitted.sizes.iter().sum::<u64>(),
+            files.sizes.iter().sum::<u64>()
+        );
+
+        let data = AppState {
+            layout_mode: LayoutMode::Bar,
+            ..Default::default()
+        };
+        let (shapes, _) = layout_shapes(&data, &files, Size::new(8.0, 20.0));
+        assert!(shapes.is_empty());
+        let (shapes, _) = layout_shapes(&data, &files, Size::new(40.0, 20.0));
+        assert_eq!(shapes.len(), 40);
+    }
 }
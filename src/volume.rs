@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use druid::Data;
+
+/// Space on the filesystem a path lives on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) struct VolumeStats {
+    pub(crate) total: u64,
+    pub(crate) free: u64,
+}
+
+impl VolumeStats {
+    pub(crate) fn of(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            total: fs2::total_space(path)?,
+            free: fs2::free_space(path)?,
+        })
+    }
+
+    pub(crate) fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// Fraction from 0.0 to 1.0 of the volume that `size` bytes take up.
+    pub(crate) fn fraction_of(&self, size: u64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (size as f64 / self.total as f64).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VolumeStats;
+
+    #[test]
+    fn fractions_of_volume() {
+        let volume = VolumeStats {
+            total: 200,
+            free: 50,
+        };
+        assert_eq!(volume.used(), 150);
+        assert_eq!(volume.fraction_of(volume.used()), 0.75);
+        assert_eq!(volume.fraction_of(20), 0.1);
+        // Hard links or a changing disk can make a scan add up to more than the volume
+        assert_eq!(volume.fraction_of(300), 1.0);
+
+        let empty = VolumeStats { total: 0, free: 0 };
+        assert_eq!(empty.fraction_of(20), 0.0);
+    }
+}
@@ -0,0 +1,67 @@
+use druid::{Color, LifeCycle, Rect, RenderContext, Size, Widget};
+
+/// Height of the bar in logical pixels
+const BAR_HEIGHT: f64 = 6.0;
+
+/// The whole volume of the last scan, with the used space and the scanned part of it marked.
+#[derive(Debug, Default)]
+pub(crate) struct VolumeBar;
+
+impl Widget<crate::AppState> for VolumeBar {
+    fn event(
+        &mut self,
+        _ctx: &mut druid::EventCtx,
+        _event: &druid::Event,
+        _data: &mut crate::AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &crate::AppState,
+        data: &crate::AppState,
+        _env: &druid::Env,
+    ) {
+        if old_data.volume != data.volume || old_data.tree_stats != data.tree_stats {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &druid::BoxConstraints,
+        _data: &crate::AppState,
+        _env: &druid::Env,
+    ) -> Size {
+        bc.constrain(Size::new(bc.max().width, BAR_HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &crate::AppState, _env: &druid::Env) {
+        let (Some(volume), Some(stats)) = (data.volume, data.tree_stats) else {
+            return;
+        };
+        let size = ctx.size();
+        let segment = |fraction: f64| Rect::new(0.0, 0.0, size.width * fraction, size.height);
+        ctx.fill(size.to_rect(), &Color::grey(0.2));
+        ctx.fill(
+            segment(volume.fraction_of(volume.used())),
+            &Color::grey(0.5),
+        );
+        ctx.fill(
+            segment(volume.fraction_of(stats.total_size)),
+            &Color::rgb(0.5, 0.0, 0.5),
+        );
+    }
+}